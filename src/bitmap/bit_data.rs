@@ -1,8 +1,17 @@
+use std::collections::HashMap;
+
 use super::bit_depth::BitDepth;
+use super::compression::Compression;
+use super::decode_warning::DecodeWarning;
+use super::decode_warning::Recovery;
+use super::error::BitmapError;
 use super::image::BitMap;
 use super::info_header::InfoHeader;
+use super::pixel_set::PixelSet;
+use super::quantizer;
 use super::rgb_quad::RgbQuad;
 use super::rgba::Rgba;
+use super::rle;
 
 ///
 /// Used for working with binary data when the image is read in or converted to
@@ -13,7 +22,6 @@ pub struct BitData {
     /// width of the image
     width: u32,
     /// height of the image
-    #[allow(dead_code)]
     height: u32,
     /// list of colors that are used in the image
     colors: Vec<Rgba>,
@@ -22,107 +30,237 @@ pub struct BitData {
     bytes: Vec<u8>,
     /// bit depth of the image
     bit_depth: BitDepth,
+    /// pixels that were skipped over while decoding a compressed image. They
+    /// don't use the color table and are transparent
+    skipped: PixelSet,
+    /// pixels that couldn't be read in the lenient mode. They don't use the
+    /// color table and are given the fill color
+    filled: PixelSet,
+    /// color of the filled pixels
+    fill: Rgba,
+    /// compression used when converting the bit data back into bytes
+    compression: Compression,
 }
 
 impl BitData {
     ///
     /// Create bit data from from_slice of bytes
     ///
+    /// Fails if there aren't enough bytes for every pixel, or if a pixel points
+    /// to a color that isn't in the color table. If there's a recovery, those
+    /// pixels are given the fill color instead. The padding after the last
+    /// row is optional, since some applications leave it out
+    ///
     pub fn from_slice(
         bit_stream: &[u8],
         info: &InfoHeader,
         bit_depth: BitDepth,
         colors: &RgbQuad,
-    ) -> BitData {
-        let mut bytes = Vec::new();
-        for index in 0..bit_stream.len() {
-            bytes.push(bit_stream[index]);
+        mut recovery: Option<&mut Recovery>,
+    ) -> Result<BitData, BitmapError> {
+        let row_size = BitData::get_row_byte_size(info.get_width(), bit_depth);
+        let last_row_size =
+            (info.get_width() as usize * bit_depth.get_step_counter() as usize + 7) / 8;
+        let needed = match info.get_height() as usize {
+            0 => 0,
+            height => row_size * (height - 1) + last_row_size,
+        };
+        let available = bit_stream.len().min(needed);
+        let mut fill = None;
+        if available < needed {
+            match recovery.as_deref_mut() {
+                Some(recovery) => {
+                    recovery.warn(DecodeWarning::PixelDataTruncated { needed, available });
+                    fill = Some(recovery.get_fill());
+                }
+                None => {
+                    return Err(BitmapError::Truncated {
+                        section: "pixel data",
+                        offset: 0,
+                        needed,
+                        available,
+                    })
+                }
+            }
         }
-        BitData {
+        // pad the last row back out if it was left short
+        let mut bytes = bit_stream[..available].to_vec();
+        bytes.resize(row_size * info.get_height() as usize, 0);
+        let mut data = BitData {
             width: info.get_width(),
             height: info.get_height(),
             bit_depth,
             colors: colors.clone_colors(),
             bytes,
+            skipped: PixelSet::new(),
+            filled: PixelSet::new(),
+            fill: Rgba::black(),
+            compression: Compression::Rgb,
+        };
+        if let Some(fill) = fill {
+            // give every pixel whose bits are past the end of the data the
+            // fill color
+            let width = info.get_width() as usize;
+            let step = bit_depth.get_step_counter() as usize;
+            data.fill = fill;
+            for i in 0..width * info.get_height() as usize {
+                let end = (i / width) * row_size + ((i % width + 1) * step + 7) / 8;
+                if end > available {
+                    data.filled.insert(i);
+                }
+            }
         }
+        data.check_indices(recovery)?;
+        Ok(data)
     }
 
     ///
-    /// Create bit data from a bitmap
+    /// Create bit data from a slice of run length encoded bytes. The bytes are
+    /// decoded so that the bit data looks exactly like it would if the image
+    /// wasn't compressed.
     ///
-    pub fn from_bitmap(bitmap: &BitMap, bit_depth: BitDepth) -> BitData {
-        let unique_colors = bitmap.get_all_unique_colors().clone();
-        let step = bit_depth.get_step_counter();
-
-        // figure out how much padding is on each row
-        // this is needed because for each row of a bmp image needs to finish
-        // with a width of bytes that is divisible by 4. Here we are figuring out
-        // how much bit padding and byte padding we need.
-        let bit_width = bitmap.get_width() * bit_depth.get_step_counter();
-        let bit_padding = match bit_width % 8 {
-            0 => 0,
-            _ => 8 - (bit_width % 8),
+    /// If there's a recovery and the data ends early, the pixels that weren't
+    /// decoded are left transparent like they would be after an end of bitmap
+    ///
+    pub fn from_rle_slice(
+        bit_stream: &[u8],
+        info: &InfoHeader,
+        bit_depth: BitDepth,
+        colors: &RgbQuad,
+        mut recovery: Option<&mut Recovery>,
+    ) -> Result<BitData, BitmapError> {
+        let (width, height) = (info.get_width(), info.get_height());
+        let (indices, skipped) = match bit_depth {
+            BitDepth::Color16Bit => {
+                rle::decode_rle4(bit_stream, width, height, recovery.as_deref_mut())?
+            }
+            _ => rle::decode_rle8(bit_stream, width, height, recovery.as_deref_mut())?,
         };
-        let byte_width = (bit_width + bit_padding) / 8;
-        let byte_padding = match byte_width % 4 {
-            0 => 0,
-            _ => 4 - (byte_width % 4),
+        let mut data = BitData {
+            width,
+            height,
+            bit_depth,
+            colors: colors.clone_colors(),
+            bytes: BitData::pack_indices(&indices, width, bit_depth),
+            skipped,
+            filled: PixelSet::new(),
+            fill: Rgba::black(),
+            compression: info.get_compression().unwrap_or(Compression::Rgb),
         };
-        let mut bytes =
-            Vec::with_capacity(((byte_width + byte_padding) * bitmap.get_height()) as usize);
-
-        let step = step as u8;
-        let mut byte: u8 = 0;
-        let mut counter: u32 = 0;
-        let mut shift: u32 = 0;
-        for i in 0..bitmap.get_pixels().len() {
-            let pixel = bitmap.get_pixels()[i];
-            let color_index = unique_colors.iter().position(|&c| *c == pixel).unwrap() as u8;
-            counter += step as u32;
-            shift = counter % 8;
-            if step != 8 {
-                byte = byte << step;
-            }
-            // if bit_depth is a Color2Bit then we want to push the bit onto the byte
-            byte += color_index;
+        data.check_indices(recovery)?;
+        Ok(data)
+    }
 
-            // push byte into data
-            if shift == 0 && i != 0 && bit_width >= 8 {
-                bytes.push(byte);
-                byte = 0;
+    ///
+    /// Make sure every pixel that uses the color table points to a color
+    /// inside of it
+    ///
+    /// Fails if a pixel points past the end of the color table, unless there's
+    /// a recovery in which case the pixel is given the fill color
+    ///
+    fn check_indices(&mut self, recovery: Option<&mut Recovery>) -> Result<(), BitmapError> {
+        let indices = self.get_indices();
+        let count = indices
+            .iter()
+            .enumerate()
+            .filter(|(i, index)| {
+                **index as usize >= self.colors.len() && self.get_override(*i).is_none()
+            })
+            .count();
+        if count == 0 {
+            return Ok(());
+        }
+        let recovery = match recovery {
+            Some(recovery) => recovery,
+            None => {
+                return Err(BitmapError::InvalidData(
+                    "a pixel points to a color that isn't in the color table",
+                ))
             }
-            // add padding to row
-            if counter % bit_width == 0 && i != 0 {
-                if bit_padding != 0 {
-                    byte = byte << bit_padding;
-                    bytes.push(byte);
-                }
-                byte = 0;
-                counter = 0;
-
-                for _ in 0..byte_padding {
-                    bytes.push(0);
-                }
+        };
+        recovery.warn(DecodeWarning::ColorIndexOutOfRange { count });
+        for (i, index) in indices.iter().enumerate() {
+            if *index as usize >= self.colors.len() && !self.skipped.contains(i) {
+                self.filled.insert(i);
             }
         }
-        if shift != 0 || (bit_depth == BitDepth::Color256Bit && bitmap.get_size() == 1) {
-            if step != 8 {
-                byte = byte << (8 - shift);
-            }
-            bytes.push(byte);
+        self.fill = recovery.get_fill();
+        Ok(())
+    }
+
+    ///
+    /// Get the color of a pixel that doesn't use the color table
+    ///
+    fn get_override(&self, i: usize) -> Option<Rgba> {
+        if self.skipped.contains(i) {
+            Some(Rgba::rgba(0, 0, 0, 0))
+        } else if self.filled.contains(i) {
+            Some(self.fill)
+        } else {
+            None
         }
-        if bytes.len() % 4 != 0 {
-            for _ in 0..byte_padding {
-                bytes.push(0);
-            }
+    }
+
+    ///
+    /// Create bit data from a bitmap, where each pixel points to its color in
+    /// the given colors. If a color is in the list more then once, the first
+    /// one is used, and if a color isn't in the list the closest one is used.
+    /// If the compression is run length encoding, the bytes are compressed
+    /// when they are written.
+    ///
+    pub fn from_bitmap(
+        bitmap: &BitMap,
+        bit_depth: BitDepth,
+        colors: &[Rgba],
+        compression: Compression,
+    ) -> BitData {
+        let mut positions = HashMap::new();
+        for (i, color) in colors.iter().enumerate() {
+            positions.entry(color).or_insert(i as u8);
         }
+        let indices: Vec<u8> = bitmap
+            .get_pixels()
+            .iter()
+            .map(|pixel| match positions.get(pixel) {
+                Some(index) => *index,
+                None => quantizer::get_nearest_index(colors, pixel),
+            })
+            .collect();
+        BitData::from_indices(
+            &indices,
+            bitmap.get_width(),
+            bit_depth,
+            colors.to_vec(),
+            compression,
+        )
+    }
 
+    ///
+    /// Create bit data from rows of indices that point into the given colors,
+    /// in the same order they are stored in. If the compression is run length
+    /// encoding, the bytes are compressed when they are written.
+    ///
+    pub fn from_indices(
+        indices: &[u8],
+        width: u32,
+        bit_depth: BitDepth,
+        colors: Vec<Rgba>,
+        compression: Compression,
+    ) -> BitData {
+        let height = match width {
+            0 => 0,
+            width => (indices.len() / width as usize) as u32,
+        };
         BitData {
-            width: bitmap.get_width(),
-            height: bitmap.get_height(),
+            width,
+            height,
             bit_depth,
-            colors: unique_colors.into_iter().map(Clone::clone).collect(),
-            bytes,
+            colors,
+            bytes: BitData::pack_indices(indices, width, bit_depth),
+            skipped: PixelSet::new(),
+            filled: PixelSet::new(),
+            fill: Rgba::black(),
+            compression,
         }
     }
 
@@ -130,76 +268,100 @@ impl BitData {
     /// Pass the bit data back as a from_slice of bytes
     ///
     pub fn as_bytes(&self) -> Vec<u8> {
-        self.bytes.clone()
+        match self.compression {
+            Compression::Rle8 => rle::encode_rle8(&self.get_indices(), self.width),
+            Compression::Rle4 => rle::encode_rle4(&self.get_indices(), self.width),
+            _ => self.bytes.clone(),
+        }
     }
 
     ///
     /// Convert bits into array of colors
     ///
+    /// Pixels that were skipped over while decoding a compressed image don't
+    /// have a color, so they are given back as transparent. Pixels that
+    /// couldn't be read in the lenient mode are given back as the fill color
+    ///
     pub fn as_rgba(&self) -> Vec<Rgba> {
-        let mut pixels = Vec::new();
-        let step = self.bit_depth.get_step_counter();
-        // figure out how much padding is on each row
-        // this is needed because for each row of a bmp image needs to finish
-        // with a width of bytes that is divisible by 4. Here we are figuring out
-        // how much bit padding and byte padding we need.
-        // TODO: move to it's own class
-        let bit_width = self.width * self.bit_depth.get_step_counter();
-        let bit_padding = match bit_width % 8 {
-            0 => 0,
-            _ => 8 - (self.width % 8),
-        };
-        let byte_width = (bit_width + bit_padding) / 8;
-        let byte_padding = match byte_width % 4 {
-            0 => 0,
-            _ => 4 - (byte_width % 4),
-        };
+        self.get_indices()
+            .iter()
+            .enumerate()
+            .map(|(i, index)| match self.get_override(i) {
+                Some(color) => color,
+                None => self.colors[*index as usize],
+            })
+            .collect()
+    }
 
-        // loop through all the bytes
-        let mut byte_padding_counter = 0;
-        let mut start_reading_again = false;
-        for byte in &self.bytes {
-            if byte_padding_counter > 0 {
-                byte_padding_counter = byte_padding_counter - 1;
-                continue;
-            }
-            // loop through the bits of the byte
-            for byte_indexes in (0..(8 / step)).rev() {
-                // so this works on every time after the pixels length
-                // reaches 72. We need a way to tell it that it is a new line
-                // (at lease once)
-                if pixels.len() as u32 % self.width == 0
-                    && pixels.len() != 0
-                    && !start_reading_again
-                {
-                    break;
-                }
+    ///
+    /// Get the index of the color of each pixel, or nothing if some pixels
+    /// don't use the color table
+    ///
+    pub fn as_indices(&self) -> Option<Vec<u8>> {
+        match self.skipped.is_empty() && self.filled.is_empty() {
+            true => Some(self.get_indices()),
+            false => None,
+        }
+    }
+
+    ///
+    /// Unpack the bytes into a list of indices, one for each pixel
+    ///
+    fn get_indices(&self) -> Vec<u8> {
+        let mut indices = Vec::with_capacity(self.width as usize * self.height as usize);
+        let step = self.bit_depth.get_step_counter() as usize;
+        let row_size = BitData::get_row_byte_size(self.width, self.bit_depth);
+        let mask = 0xff >> (8 - step);
+        if row_size == 0 {
+            return indices;
+        }
+
+        for row in self.bytes.chunks(row_size).take(self.height as usize) {
+            for x in 0..self.width as usize {
                 // bits could be:
                 // (1) -> 0 1 2 3 4 5 6 7
                 // (4) -> 0 1
                 // (8) -> 0
-                let starting_bit = byte_indexes * step;
-                let ending_bit = starting_bit + step;
-                let mut index: usize = 0;
-                for bit_index in (starting_bit..ending_bit).rev() {
-                    index = index << 1;
-                    let next_bit = (byte >> bit_index) & 1;
-                    index = index + next_bit as usize;
-                }
-                pixels.push(self.colors[index]);
-            }
-            if start_reading_again {
-                start_reading_again = false;
+                // where the first pixel is stored in the highest bits
+                let bit = x * step;
+                let shift = 8 - step - bit % 8;
+                indices.push((row[bit / 8] >> shift) & mask);
             }
-            if (pixels.len() as u32) % self.width == 0
-                && pixels.len() != 0
-                && start_reading_again == false
-            {
-                start_reading_again = true;
-                byte_padding_counter = byte_padding;
+        }
+        indices
+    }
+
+    ///
+    /// Get the number of bytes used to store a single row of the image.
+    ///
+    /// Each row of a bmp image needs to finish with a width of bytes that is
+    /// divisible by 4, so the row is padded with zeros
+    ///
+    fn get_row_byte_size(width: u32, bit_depth: BitDepth) -> usize {
+        let bit_width = width as usize * bit_depth.get_step_counter() as usize;
+        (bit_width + 31) / 32 * 4
+    }
+
+    ///
+    /// Pack a list of color indices into bytes, putting as many indices as the
+    /// bit depth allows into each byte and padding every row
+    ///
+    pub fn pack_indices(indices: &[u8], width: u32, bit_depth: BitDepth) -> Vec<u8> {
+        let step = bit_depth.get_step_counter() as usize;
+        let row_size = BitData::get_row_byte_size(width, bit_depth);
+        let mut bytes = Vec::new();
+        if width == 0 {
+            return bytes;
+        }
+        for row in indices.chunks(width as usize) {
+            let mut packed = vec![0; row_size];
+            for (x, index) in row.iter().enumerate() {
+                let bit = x * step;
+                packed[bit / 8] |= index << (8 - step - bit % 8);
             }
+            bytes.extend_from_slice(&packed);
         }
-        pixels
+        bytes
     }
 
     ///
@@ -213,7 +375,10 @@ impl BitData {
     /// Get the size of bit data in bytes
     ///
     pub fn get_bytes_size(&self) -> u32 {
-        self.bytes.len() as u32
+        match self.compression {
+            Compression::Rle8 | Compression::Rle4 => self.as_bytes().len() as u32,
+            _ => self.bytes.len() as u32,
+        }
     }
 }
 
@@ -221,7 +386,7 @@ impl BitData {
 impl std::fmt::Display for BitData {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for p in (0..self.bytes.len()).rev() {
-            write!(f, "{}:\t{:#b}\n", p, self.bytes[p]).unwrap();
+            writeln!(f, "{}:\t{:#b}", p, self.bytes[p]).unwrap();
         }
         write!(f, "")
     }
@@ -232,16 +397,22 @@ mod test {
     use super::BitData;
     use super::BitDepth;
     use super::BitMap;
+    use super::Compression;
     use super::Rgba;
 
+    fn from_bitmap(b: &BitMap, bit_depth: BitDepth, compression: Compression) -> BitData {
+        let colors: Vec<Rgba> = b.get_all_unique_colors().into_iter().copied().collect();
+        BitData::from_bitmap(b, bit_depth, &colors, compression)
+    }
+
     #[test]
     fn get_length_of_bit_data_length_2_bit_depth() {
         let b = BitMap::new(10, 10);
-        let data = BitData::from_bitmap(&b, BitDepth::Color2Bit);
-        assert_eq!(data.len(), 43);
-        assert_eq!(data.get_bytes_size(), 43);
+        let data = from_bitmap(&b, BitDepth::Color2Bit, Compression::Rgb);
+        assert_eq!(data.len(), 40);
+        assert_eq!(data.get_bytes_size(), 40);
         let b = BitMap::new(1, 1);
-        let data = BitData::from_bitmap(&b, BitDepth::Color2Bit);
+        let data = from_bitmap(&b, BitDepth::Color2Bit, Compression::Rgb);
         assert_eq!(data.len(), 4);
         assert_eq!(data.get_bytes_size(), 4);
     }
@@ -249,11 +420,11 @@ mod test {
     #[test]
     fn get_length_of_bit_data_length_16_bit_depth() {
         let b = BitMap::new(10, 10);
-        let data = BitData::from_bitmap(&b, BitDepth::Color16Bit);
+        let data = from_bitmap(&b, BitDepth::Color16Bit, Compression::Rgb);
         assert_eq!(data.len(), 80);
         assert_eq!(data.get_bytes_size(), 80);
         let b = BitMap::new(1, 1);
-        let data = BitData::from_bitmap(&b, BitDepth::Color16Bit);
+        let data = from_bitmap(&b, BitDepth::Color16Bit, Compression::Rgb);
         assert_eq!(data.len(), 4);
         assert_eq!(data.get_bytes_size(), 4);
     }
@@ -261,11 +432,11 @@ mod test {
     #[test]
     fn get_length_of_bit_data_length_256_bit_depth() {
         let b = BitMap::new(10, 10);
-        let data = BitData::from_bitmap(&b, BitDepth::Color256Bit);
-        assert_eq!(data.len(), 121);
-        assert_eq!(data.get_bytes_size(), 121);
+        let data = from_bitmap(&b, BitDepth::Color256Bit, Compression::Rgb);
+        assert_eq!(data.len(), 120);
+        assert_eq!(data.get_bytes_size(), 120);
         let b = BitMap::new(1, 1);
-        let data = BitData::from_bitmap(&b, BitDepth::Color256Bit);
+        let data = from_bitmap(&b, BitDepth::Color256Bit, Compression::Rgb);
         assert_eq!(data.len(), 4);
         assert_eq!(data.get_bytes_size(), 4);
     }
@@ -273,7 +444,7 @@ mod test {
     #[test]
     fn get_bit_data_as_rgb_bit_depth_2() {
         let b = BitMap::new(10, 10);
-        let data = BitData::from_bitmap(&b, BitDepth::Color2Bit);
+        let data = from_bitmap(&b, BitDepth::Color2Bit, Compression::Rgb);
         let colors = data.as_rgba();
         for i in 0..b.get_size() {
             assert!(&colors[i as usize] == &Rgba::white());
@@ -281,7 +452,7 @@ mod test {
 
         let mut b = BitMap::new(2, 1);
         b.set_pixel(0, 0, Rgba::black()).unwrap();
-        let data = BitData::from_bitmap(&b, BitDepth::Color2Bit);
+        let data = from_bitmap(&b, BitDepth::Color2Bit, Compression::Rgb);
         let colors = data.as_rgba();
         assert!(&colors[0] == &Rgba::black());
         assert!(&colors[1] == &Rgba::white());
@@ -290,7 +461,7 @@ mod test {
     #[test]
     fn get_bit_data_as_rgb_bit_depth_16() {
         let b = BitMap::new(10, 10);
-        let data = BitData::from_bitmap(&b, BitDepth::Color16Bit);
+        let data = from_bitmap(&b, BitDepth::Color16Bit, Compression::Rgb);
         let colors = data.as_rgba();
         for i in 0..b.get_size() {
             assert!(&colors[i as usize] == &Rgba::white());
@@ -300,7 +471,7 @@ mod test {
         b.set_pixel(0, 0, Rgba::black()).unwrap();
         b.set_pixel(1, 0, Rgba::rgb(255, 0, 0)).unwrap();
         b.set_pixel(2, 0, Rgba::rgb(0, 0, 255)).unwrap();
-        let data = BitData::from_bitmap(&b, BitDepth::Color16Bit);
+        let data = from_bitmap(&b, BitDepth::Color16Bit, Compression::Rgb);
         let colors = data.as_rgba();
         assert!(&colors[0] == &Rgba::black());
         assert!(&colors[1] == &Rgba::rgb(255, 0, 0));
@@ -311,28 +482,85 @@ mod test {
     #[test]
     fn get_bit_data_as_rgb_bit_depth_256() {
         let b = BitMap::new(10, 10);
-        let data = BitData::from_bitmap(&b, BitDepth::Color256Bit);
+        let data = from_bitmap(&b, BitDepth::Color256Bit, Compression::Rgb);
         let colors = data.as_rgba();
         for i in 0..b.get_size() {
             assert!(&colors[i as usize] == &Rgba::white());
         }
     }
 
+    #[test]
+    fn get_bit_data_as_rgb_with_odd_widths() {
+        for bit_depth in &[
+            BitDepth::Color2Bit,
+            BitDepth::Color16Bit,
+            BitDepth::Color256Bit,
+        ] {
+            for width in 1..20 {
+                let mut b = BitMap::new(width, 3);
+                b.set_pixel(0, 0, Rgba::black()).unwrap();
+                b.set_pixel(width - 1, 2, Rgba::black()).unwrap();
+                let data = from_bitmap(&b, *bit_depth, Compression::Rgb);
+                let row_size = ((width * *bit_depth as u32 + 31) / 32 * 4) as usize;
+                assert_eq!(data.len(), row_size * 3);
+                assert_eq!(&data.as_rgba(), b.get_pixels());
+            }
+        }
+    }
+
     #[test]
     fn get_bit_data_as_bytes() {
         let b = BitMap::new(10, 10);
 
-        let data = BitData::from_bitmap(&b, BitDepth::Color2Bit);
+        let data = from_bitmap(&b, BitDepth::Color2Bit, Compression::Rgb);
         for i in 0..data.as_bytes().len() {
             assert!(data.as_bytes()[i] == 0);
         }
-        let data = BitData::from_bitmap(&b, BitDepth::Color16Bit);
+        let data = from_bitmap(&b, BitDepth::Color16Bit, Compression::Rgb);
         for i in 0..data.as_bytes().len() {
             assert!(data.as_bytes()[i] == 0);
         }
-        let data = BitData::from_bitmap(&b, BitDepth::Color256Bit);
+        let data = from_bitmap(&b, BitDepth::Color256Bit, Compression::Rgb);
         for i in 0..data.as_bytes().len() {
             assert!(data.as_bytes()[i] == 0);
         }
     }
+
+    #[test]
+    fn get_run_length_encoded_bit_data_as_bytes() {
+        let mut b = BitMap::new(20, 10);
+        b.set_pixel(3, 4, Rgba::black()).unwrap();
+        let data = from_bitmap(&b, BitDepth::Color256Bit, Compression::Rle8);
+        assert_eq!(data.get_bytes_size() as usize, data.as_bytes().len());
+        assert!(data.get_bytes_size() < 200);
+        assert_eq!(&data.as_rgba(), b.get_pixels());
+        let data = from_bitmap(&b, BitDepth::Color16Bit, Compression::Rle4);
+        assert_eq!(data.get_bytes_size() as usize, data.as_bytes().len());
+        assert!(data.get_bytes_size() < 100);
+        assert_eq!(&data.as_rgba(), b.get_pixels());
+    }
+
+    #[test]
+    fn point_pixels_at_the_first_matching_color() {
+        let mut b = BitMap::new(2, 1);
+        b.set_pixel(0, 0, Rgba::black()).unwrap();
+        let colors = [
+            Rgba::rgb(1, 2, 3),
+            Rgba::white(),
+            Rgba::black(),
+            Rgba::white(),
+        ];
+        let data = BitData::from_bitmap(&b, BitDepth::Color16Bit, &colors, Compression::Rgb);
+        assert_eq!(data.as_bytes()[0], 0x21);
+        assert_eq!(&data.as_rgba(), b.get_pixels());
+    }
+
+    #[test]
+    fn point_missing_colors_at_the_closest_color() {
+        let mut b = BitMap::new(2, 1);
+        b.set_pixel(0, 0, Rgba::rgb(250, 10, 10)).unwrap();
+        let colors = [Rgba::black(), Rgba::white(), Rgba::rgb(255, 0, 0)];
+        let data = BitData::from_bitmap(&b, BitDepth::Color16Bit, &colors, Compression::Rgb);
+        assert_eq!(data.as_bytes()[0], 0x21);
+    }
 }
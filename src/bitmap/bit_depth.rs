@@ -1,11 +1,22 @@
 use super::image::BitMap;
 
+///
+/// The number of bits used to store each pixel of a bitmap
+///
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum BitDepth {
+    /// 1 bit per pixel, each pixel points to one of 2 colors
     Color2Bit = 1,
+    /// 4 bits per pixel, each pixel points to one of 16 colors
     Color16Bit = 4,
+    /// 8 bits per pixel, each pixel points to one of 256 colors
     Color256Bit = 8,
+    /// 16 bits per pixel, stored as 5 bits for red, green and blue (5-5-5) or
+    /// as 5 bits for red and blue and 6 bits for green (5-6-5)
+    HighColor = 16,
+    /// 24 bits per pixel, 8 bits for red, green and blue
     AllColors = 24,
+    /// 32 bits per pixel, 8 bits for red, green, blue and alpha
     AllColorsAndShades = 32,
 }
 
@@ -17,19 +28,33 @@ impl BitDepth {
     /// For the ColorNBit, we return the total length of how much each color
     /// takes up in """bits"""
     ///
-    /// For AllColors*, we return the total length of how much each color takes
-    /// up in """bytes"""
+    /// For HighColor and AllColors*, we return the total length of how much
+    /// each color takes up in """bytes"""
     ///
     pub fn get_step_counter(&self) -> u32 {
         match self {
             Self::Color2Bit => 1,
             Self::Color16Bit => 4,
             Self::Color256Bit => 8,
+            Self::HighColor => 2,
             Self::AllColors => 3,
             Self::AllColorsAndShades => 4,
         }
     }
 
+    ///
+    /// Get the number of colors the color table of the bit depth can hold, if
+    /// the bit depth uses one
+    ///
+    pub fn get_max_colors(&self) -> Option<usize> {
+        match self {
+            Self::Color2Bit => Some(2),
+            Self::Color16Bit => Some(16),
+            Self::Color256Bit => Some(256),
+            _ => None,
+        }
+    }
+
     ///
     /// Get a suggested bit depth depending on the colors contained inside of
     /// a array of colors
@@ -53,11 +78,12 @@ impl BitDepth {
 impl std::fmt::Display for BitDepth {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
-            Self::Color2Bit => write!(f, "BitDepth: Color2Bit\n"),
-            Self::Color16Bit => write!(f, "BitDepth: Color16Bit\n"),
-            Self::Color256Bit => write!(f, "BitDepth: Color256Bit\n"),
-            Self::AllColors => write!(f, "BitDepth: AllColors\n"),
-            Self::AllColorsAndShades => write!(f, "BitDepth: AllColorsAndShades\n"),
+            Self::Color2Bit => writeln!(f, "BitDepth: Color2Bit"),
+            Self::Color16Bit => writeln!(f, "BitDepth: Color16Bit"),
+            Self::Color256Bit => writeln!(f, "BitDepth: Color256Bit"),
+            Self::HighColor => writeln!(f, "BitDepth: HighColor"),
+            Self::AllColors => writeln!(f, "BitDepth: AllColors"),
+            Self::AllColorsAndShades => writeln!(f, "BitDepth: AllColorsAndShades"),
         }
     }
 }
@@ -73,6 +99,7 @@ mod test {
         assert_eq!(BitDepth::Color2Bit.get_step_counter(), 1);
         assert_eq!(BitDepth::Color16Bit.get_step_counter(), 4);
         assert_eq!(BitDepth::Color256Bit.get_step_counter(), 8);
+        assert_eq!(BitDepth::HighColor.get_step_counter(), 2);
         assert_eq!(BitDepth::AllColors.get_step_counter(), 3);
         assert_eq!(BitDepth::AllColorsAndShades.get_step_counter(), 4);
     }
@@ -0,0 +1,256 @@
+use super::error::BitmapError;
+use super::rgba::Rgba;
+use super::util;
+
+///
+/// Channel masks used to pull the red, green, blue and alpha values out of a
+/// pixel when the image is stored using bit fields. Each mask must be a
+/// contiguous run of bits. A mask of zero means the channel isn't stored.
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BitFields {
+    red: u32,
+    green: u32,
+    blue: u32,
+    alpha: u32,
+}
+
+///
+/// Used for constants
+///
+impl BitFields {
+    pub fn single_mask_size() -> usize {
+        4
+    }
+}
+
+///
+/// Core implementation
+///
+impl BitFields {
+    ///
+    /// Create a new set of channel masks
+    ///
+    pub fn new(red: u32, green: u32, blue: u32, alpha: u32) -> BitFields {
+        BitFields {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    ///
+    /// The masks used to store a 32 bit pixel as blue, green, red and alpha
+    ///
+    pub fn bgra() -> BitFields {
+        BitFields::new(0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000)
+    }
+
+    ///
+    /// The masks used to store a 16 bit pixel as 5 bits of red, green and blue
+    ///
+    pub fn rgb555() -> BitFields {
+        BitFields::new(0x7c00, 0x03e0, 0x001f, 0)
+    }
+
+    ///
+    /// The masks used to store a 16 bit pixel as 5 bits of red, 6 bits of green
+    /// and 5 bits of blue
+    ///
+    pub fn rgb565() -> BitFields {
+        BitFields::new(0xf800, 0x07e0, 0x001f, 0)
+    }
+
+    ///
+    /// Read in the masks from a slice of bytes. The masks are stored in the
+    /// order red, green, blue and optionally alpha.
+    ///
+    pub fn from_slice(bit_stream: &[u8], with_alpha: bool) -> Result<BitFields, BitmapError> {
+        let count = if with_alpha { 4 } else { 3 };
+        if bit_stream.len() < count * BitFields::single_mask_size() {
+            return Err(BitmapError::Truncated {
+                section: "bit field masks",
+                offset: 0,
+                needed: count * BitFields::single_mask_size(),
+                available: bit_stream.len(),
+            });
+        }
+        let mut i = 0;
+        Ok(BitFields {
+            red: util::byte_slice_to_u32(bit_stream, &mut i),
+            green: util::byte_slice_to_u32(bit_stream, &mut i),
+            blue: util::byte_slice_to_u32(bit_stream, &mut i),
+            alpha: if with_alpha {
+                util::byte_slice_to_u32(bit_stream, &mut i)
+            } else {
+                0
+            },
+        })
+    }
+
+    ///
+    /// Convert the masks to bytes in the order red, green, blue and optionally
+    /// alpha
+    ///
+    pub fn as_bytes(&self, with_alpha: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.red.to_le_bytes());
+        bytes.extend_from_slice(&self.green.to_le_bytes());
+        bytes.extend_from_slice(&self.blue.to_le_bytes());
+        if with_alpha {
+            bytes.extend_from_slice(&self.alpha.to_le_bytes());
+        }
+        bytes
+    }
+
+    ///
+    /// Get the mask for the red channel
+    ///
+    pub fn get_red(&self) -> u32 {
+        self.red
+    }
+
+    ///
+    /// Get the mask for the green channel
+    ///
+    pub fn get_green(&self) -> u32 {
+        self.green
+    }
+
+    ///
+    /// Get the mask for the blue channel
+    ///
+    pub fn get_blue(&self) -> u32 {
+        self.blue
+    }
+
+    ///
+    /// Get the mask for the alpha channel
+    ///
+    pub fn get_alpha(&self) -> u32 {
+        self.alpha
+    }
+
+    ///
+    /// Check that every mask is a contiguous run of bits
+    ///
+    pub fn is_valid(&self) -> bool {
+        [self.red, self.green, self.blue, self.alpha]
+            .iter()
+            .all(|mask| {
+                // shifting a contiguous mask all the way to the right leaves a
+                // value that is one less then a power of two
+                let bits = mask.checked_shr(mask.trailing_zeros()).unwrap_or(0);
+                bits & bits.wrapping_add(1) == 0
+            })
+    }
+
+    ///
+    /// Pull the colors out of a single pixel value
+    ///
+    /// Each channel is scaled up to fit between 0 - 255, alpha is scaled to fit
+    /// between 0 - 100. If there is no alpha mask, the pixel is fully visible.
+    ///
+    pub fn decode(&self, value: u32) -> Rgba {
+        let red = BitFields::extract(value, self.red, 255) as u8;
+        let green = BitFields::extract(value, self.green, 255) as u8;
+        let blue = BitFields::extract(value, self.blue, 255) as u8;
+        let alpha = match self.alpha {
+            0 => 100,
+            mask => BitFields::extract(value, mask, 100) as u8,
+        };
+        Rgba::rgba(red, green, blue, alpha)
+    }
+
+    ///
+    /// Pack a color into a single pixel value
+    ///
+    pub fn encode(&self, color: &Rgba) -> u32 {
+        BitFields::insert(color.get_red() as u32, 255, self.red)
+            | BitFields::insert(color.get_green() as u32, 255, self.green)
+            | BitFields::insert(color.get_blue() as u32, 255, self.blue)
+            | BitFields::insert(color.get_alpha() as u32, 100, self.alpha)
+    }
+
+    ///
+    /// Get the value of the channel described by the mask, scaled between
+    /// 0 and max
+    ///
+    fn extract(value: u32, mask: u32, max: u64) -> u64 {
+        if mask == 0 {
+            return 0;
+        }
+        let shift = mask.trailing_zeros();
+        let mask_max = (mask >> shift) as u64;
+        let channel = ((value & mask) >> shift) as u64;
+        (channel * max + mask_max / 2) / mask_max
+    }
+
+    ///
+    /// Scale a channel that is between 0 and max so that it fits in the mask
+    ///
+    fn insert(channel: u32, max: u64, mask: u32) -> u32 {
+        if mask == 0 {
+            return 0;
+        }
+        let shift = mask.trailing_zeros();
+        let mask_max = (mask >> shift) as u64;
+        let value = (channel as u64 * mask_max + max / 2) / max;
+        (value as u32) << shift
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitFields;
+    use super::Rgba;
+
+    #[test]
+    fn decode_and_encode_bgra_pixels() {
+        let fields = BitFields::bgra();
+        let color = Rgba::rgba(10, 20, 30, 100);
+        assert_eq!(fields.encode(&color), 0xff0a_141e);
+        assert_eq!(fields.decode(0xff0a_141e), color);
+        for alpha in 0..=100 {
+            let color = Rgba::rgba(1, 2, 3, alpha);
+            assert_eq!(fields.decode(fields.encode(&color)), color);
+        }
+    }
+
+    #[test]
+    fn decode_pixels_with_small_masks() {
+        // 5-6-5
+        let fields = BitFields::rgb565();
+        assert_eq!(fields.decode(0xffff), Rgba::white());
+        assert_eq!(fields.decode(0x0000), Rgba::black());
+        assert_eq!(fields.decode(0xf800), Rgba::rgb(255, 0, 0));
+        assert_eq!(fields.decode(0x0400), Rgba::rgb(0, 130, 0));
+        assert_eq!(fields.encode(&Rgba::rgb(0, 255, 0)), 0x07e0);
+        let fields = BitFields::rgb555();
+        assert_eq!(fields.decode(0x7fff), Rgba::white());
+        assert_eq!(fields.decode(0x0010), Rgba::rgb(0, 0, 132));
+        assert_eq!(fields.encode(&Rgba::rgb(0, 255, 0)), 0x03e0);
+    }
+
+    #[test]
+    fn read_masks_from_bytes() {
+        let fields = BitFields::bgra();
+        let bytes = fields.as_bytes(true);
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(BitFields::from_slice(&bytes, true).unwrap(), fields);
+        let without_alpha = BitFields::from_slice(&bytes, false).unwrap();
+        assert_eq!(
+            without_alpha,
+            BitFields::new(0x00ff_0000, 0x0000_ff00, 0xff, 0)
+        );
+        assert!(BitFields::from_slice(&bytes[..8], false).is_err());
+    }
+
+    #[test]
+    fn check_if_masks_are_valid() {
+        assert!(BitFields::bgra().is_valid());
+        assert!(BitFields::rgb555().is_valid());
+        assert!(!BitFields::new(0x7c01, 0x03e0, 0x001f, 0).is_valid());
+    }
+}
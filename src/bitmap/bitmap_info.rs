@@ -0,0 +1,293 @@
+use std::io::Read;
+
+use super::bit_depth::BitDepth;
+use super::compression::Compression;
+use super::error::BitmapError;
+use super::file::File;
+use super::file_header::FileHeader;
+use super::header_version::HeaderVersion;
+use super::info_header::InfoHeader;
+use super::rgb_quad::RgbQuad;
+use super::rgba::Rgba;
+use super::util;
+
+///
+/// Information about a bitmap file that is read from its headers and color
+/// table, without decoding any of the pixels
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct BitmapInfo {
+    /// width of the image, in pixels
+    width: u32,
+    /// height of the image, in pixels
+    height: u32,
+    /// if the rows are stored from the top of the image to the bottom
+    top_down: bool,
+    /// number of bits used to store each pixel, as stored in the header
+    bits_per_pixel: u16,
+    /// bit depth of the image, if it's one that can be decoded
+    bit_depth: Option<BitDepth>,
+    /// compression value stored in the header
+    compression: u32,
+    /// version of the info header
+    header_version: HeaderVersion,
+    /// horizontal resolution, in pixels per meter
+    x_pixels_per_meter: u32,
+    /// vertical resolution, in pixels per meter
+    y_pixels_per_meter: u32,
+    /// number of colors used, zero means every color the bit depth allows
+    colors_used: u32,
+    /// number of important colors, zero means every color is important
+    colors_important: u32,
+    /// size of the file as stored in the file header
+    file_size: u32,
+    /// offset from the start of the file to the pixel data
+    data_offset: u32,
+    /// size of the pixel data as stored in the info header
+    image_size: u32,
+    /// colors stored in the color table
+    palette: Vec<Rgba>,
+}
+
+impl BitmapInfo {
+    ///
+    /// Read the information about a .bmp file. Only the headers and color table
+    /// are read from the file
+    ///
+    /// Fails if filename doesn't end with ".bmp"
+    ///
+    pub fn read(filename: &str) -> Result<BitmapInfo, BitmapError> {
+        if !filename.ends_with(".bmp") {
+            return Err(BitmapError::InvalidArgument("File must end with '.bmp'"));
+        }
+        let file = std::fs::File::open(filename)?;
+        File::probe_reader(file)
+    }
+
+    ///
+    /// Read the information about a bitmap file that is already in memory
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<BitmapInfo, BitmapError> {
+        File::probe(bytes)
+    }
+
+    ///
+    /// Read the information about a bitmap file from any reader. Only the
+    /// headers and color table are read, so the reader is left at the end of
+    /// the color table
+    ///
+    pub fn from_reader<R: Read>(reader: R) -> Result<BitmapInfo, BitmapError> {
+        File::probe_reader(reader)
+    }
+
+    ///
+    /// Collect the information from the headers and color table of a file
+    ///
+    pub(crate) fn new(
+        file: &FileHeader,
+        info: &InfoHeader,
+        colors: &RgbQuad,
+    ) -> Result<BitmapInfo, BitmapError> {
+        let header_version = info
+            .get_version()
+            .ok_or(BitmapError::InvalidData("unknown info header version"))?;
+        Ok(BitmapInfo {
+            width: info.get_width(),
+            height: info.get_height(),
+            top_down: info.is_top_down(),
+            bits_per_pixel: info.get_bits_per_pixel(),
+            bit_depth: info.get_bit_depth(),
+            compression: info.get_compression_value(),
+            header_version,
+            x_pixels_per_meter: info.get_x_pixels_per_meter(),
+            y_pixels_per_meter: info.get_y_pixels_per_meter(),
+            colors_used: info.get_colors_used(),
+            colors_important: info.get_colors_important(),
+            file_size: file.get_size(),
+            data_offset: file.get_off_bits(),
+            image_size: info.get_size_image(),
+            palette: colors.clone_colors(),
+        })
+    }
+
+    ///
+    /// Get the width of the image, in pixels
+    ///
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    ///
+    /// Get the height of the image, in pixels
+    ///
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    ///
+    /// Check if the rows are stored from the top of the image to the bottom
+    ///
+    pub fn is_top_down(&self) -> bool {
+        self.top_down
+    }
+
+    ///
+    /// Get the number of bits used to store each pixel, even if it isn't
+    /// supported
+    ///
+    pub fn get_bits_per_pixel(&self) -> u16 {
+        self.bits_per_pixel
+    }
+
+    ///
+    /// Get the bit depth of the image, if it's one that can be decoded
+    ///
+    pub fn get_bit_depth(&self) -> Option<BitDepth> {
+        self.bit_depth
+    }
+
+    ///
+    /// Get the raw compression value stored in the header, even if it isn't
+    /// supported
+    ///
+    pub fn get_compression_value(&self) -> u32 {
+        self.compression
+    }
+
+    ///
+    /// Get the compression used to store the pixels, if it's a known one
+    ///
+    pub fn get_compression(&self) -> Option<Compression> {
+        Compression::from_u32(self.compression)
+    }
+
+    ///
+    /// Get the version of the info header
+    ///
+    pub fn get_header_version(&self) -> HeaderVersion {
+        self.header_version
+    }
+
+    ///
+    /// Get the horizontal and vertical resolution in pixels per meter, zero
+    /// means the resolution isn't given
+    ///
+    pub fn get_pixels_per_meter(&self) -> (u32, u32) {
+        (self.x_pixels_per_meter, self.y_pixels_per_meter)
+    }
+
+    ///
+    /// Get the horizontal and vertical resolution in dots per inch, rounded to
+    /// the nearest whole number
+    ///
+    pub fn get_dpi(&self) -> (u32, u32) {
+        (
+            util::pixels_per_meter_to_dpi(self.x_pixels_per_meter),
+            util::pixels_per_meter_to_dpi(self.y_pixels_per_meter),
+        )
+    }
+
+    ///
+    /// Get the number of colors used as stored in the header, zero means every
+    /// color that the bit depth allows
+    ///
+    pub fn get_colors_used(&self) -> u32 {
+        self.colors_used
+    }
+
+    ///
+    /// Get the number of colors that are important for showing the image, zero
+    /// means every color is important
+    ///
+    pub fn get_colors_important(&self) -> u32 {
+        self.colors_important
+    }
+
+    ///
+    /// Get the size of the file as stored in the file header
+    ///
+    pub fn get_file_size(&self) -> u32 {
+        self.file_size
+    }
+
+    ///
+    /// Get the offset from the start of the file to the pixel data
+    ///
+    pub fn get_data_offset(&self) -> u32 {
+        self.data_offset
+    }
+
+    ///
+    /// Get the size of the pixel data as stored in the info header, which can
+    /// be zero for uncompressed images
+    ///
+    pub fn get_image_size(&self) -> u32 {
+        self.image_size
+    }
+
+    ///
+    /// Get the colors stored in the color table, which is empty for images
+    /// that don't use one
+    ///
+    pub fn get_palette(&self) -> &[Rgba] {
+        &self.palette
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitDepth;
+    use super::BitmapInfo;
+    use super::Compression;
+    use super::HeaderVersion;
+    use crate::bitmap::image::BitMap;
+    use crate::bitmap::rgba::Rgba;
+    use crate::bitmap::save_options::SaveOptions;
+
+    fn create_bytes(options: &SaveOptions) -> Vec<u8> {
+        let mut bitmap = BitMap::new(3, 2);
+        bitmap.set_pixel(0, 0, Rgba::white()).unwrap();
+        bitmap.set_pixel(1, 1, Rgba::rgb(255, 0, 0)).unwrap();
+        bitmap.to_bytes(options).unwrap()
+    }
+
+    #[test]
+    fn read_info_without_pixel_data() {
+        let options = SaveOptions::new().with_bit_depth(BitDepth::Color16Bit);
+        let bytes = create_bytes(&options);
+        let info = BitmapInfo::from_bytes(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(info.get_width(), 3);
+        assert_eq!(info.get_height(), 2);
+        assert!(!info.is_top_down());
+        assert_eq!(info.get_bit_depth(), Some(BitDepth::Color16Bit));
+        assert_eq!(info.get_compression(), Some(Compression::Rgb));
+        assert_eq!(info.get_header_version(), HeaderVersion::Info);
+        assert_eq!(info.get_file_size() as usize, bytes.len());
+        let palette_size = info.get_palette().len() as u32;
+        assert_eq!(info.get_data_offset(), 14 + 40 + palette_size * 4);
+        assert!(info.get_palette().contains(&Rgba::white()));
+    }
+
+    #[test]
+    fn read_info_from_reader() {
+        let options = SaveOptions::new()
+            .with_bit_depth(BitDepth::Color256Bit)
+            .with_rle(true);
+        let bytes = create_bytes(&options);
+        let mut reader = &bytes[..];
+        let info = BitmapInfo::from_reader(&mut reader).unwrap();
+        assert_eq!(info, BitmapInfo::from_bytes(&bytes).unwrap());
+        assert_eq!(info.get_compression(), Some(Compression::Rle8));
+        // nothing past the color table is read
+        assert_eq!(reader.len(), bytes.len() - info.get_data_offset() as usize);
+    }
+
+    #[test]
+    fn fail_to_read_info_with_a_missing_color_table() {
+        let options = SaveOptions::new().with_bit_depth(BitDepth::Color256Bit);
+        let bytes = create_bytes(&options);
+        assert!(BitmapInfo::from_bytes(&bytes[..60]).is_err());
+        assert!(BitmapInfo::from_reader(&bytes[..60]).is_err());
+        assert!(BitmapInfo::from_reader(&bytes[..10]).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+///
+/// The compression method used to store the pixels of a bitmap
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Compression {
+    /// No compression, the pixels are stored as is
+    Rgb = 0,
+    /// Run length encoding for 8 bit images
+    Rle8 = 1,
+    /// Run length encoding for 4 bit images
+    Rle4 = 2,
+    /// Each pixel is stored as a 16 or 32 bit value that is split up into
+    /// red, green and blue channels using masks
+    BitFields = 3,
+    /// The pixels are stored as a JPEG image
+    Jpeg = 4,
+    /// The pixels are stored as a PNG image
+    Png = 5,
+    /// Same as bit fields, but with an alpha mask as well
+    AlphaBitFields = 6,
+}
+
+impl Compression {
+    ///
+    /// Get the compression method that is identified by the given value
+    ///
+    pub fn from_u32(value: u32) -> Option<Compression> {
+        match value {
+            0 => Some(Compression::Rgb),
+            1 => Some(Compression::Rle8),
+            2 => Some(Compression::Rle4),
+            3 => Some(Compression::BitFields),
+            4 => Some(Compression::Jpeg),
+            5 => Some(Compression::Png),
+            6 => Some(Compression::AlphaBitFields),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Check if the compression uses channel masks to store the pixels
+    ///
+    pub fn is_bit_fields(&self) -> bool {
+        *self == Compression::BitFields || *self == Compression::AlphaBitFields
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Compression;
+
+    #[test]
+    fn get_compression_from_value() {
+        assert_eq!(Compression::from_u32(0), Some(Compression::Rgb));
+        assert_eq!(Compression::from_u32(3), Some(Compression::BitFields));
+        assert_eq!(Compression::from_u32(6), Some(Compression::AlphaBitFields));
+        assert_eq!(Compression::from_u32(7), None);
+    }
+}
@@ -0,0 +1,236 @@
+use super::error::BitmapError;
+
+///
+/// Most bytes that are allocated for each pixel while an image is decoded. The
+/// decoded pixels take 4 bytes, and the copy of them that's given to the image
+/// takes another 4. Images with a color table use less, since their packed
+/// indices and the sets of pixels that don't use the color table take at most
+/// 2 bytes along with the copy of their colors
+///
+const PEAK_BYTES_PER_PIXEL: u64 = 8;
+
+///
+/// Limits on the size of the images that are decoded, so that a small file
+/// that declares a huge image fails before any memory is allocated for it
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DecodeLimits {
+    /// widest image that can be decoded
+    max_width: u32,
+    /// tallest image that can be decoded
+    max_height: u32,
+    /// most pixels an image can have
+    max_pixels: u64,
+    /// most bytes that can be allocated while decoding the pixels
+    max_alloc_bytes: u64,
+}
+
+impl DecodeLimits {
+    ///
+    /// Create the default limits, which allow images up to 65535 pixels wide
+    /// and tall with at most 2^28 pixels, using at most 1 GiB of memory
+    ///
+    pub fn new() -> DecodeLimits {
+        DecodeLimits {
+            max_width: 0xffff,
+            max_height: 0xffff,
+            max_pixels: 1 << 28,
+            max_alloc_bytes: 1 << 30,
+        }
+    }
+
+    ///
+    /// Create limits that allow any image the bitmap format can describe
+    ///
+    pub fn unlimited() -> DecodeLimits {
+        DecodeLimits {
+            max_width: u32::MAX,
+            max_height: u32::MAX,
+            max_pixels: u64::MAX,
+            max_alloc_bytes: u64::MAX,
+        }
+    }
+
+    ///
+    /// Only decode images that are at most this many pixels wide
+    ///
+    pub fn with_max_width(mut self, max_width: u32) -> DecodeLimits {
+        self.max_width = max_width;
+        self
+    }
+
+    ///
+    /// Only decode images that are at most this many pixels tall
+    ///
+    pub fn with_max_height(mut self, max_height: u32) -> DecodeLimits {
+        self.max_height = max_height;
+        self
+    }
+
+    ///
+    /// Only decode images that have at most this many pixels
+    ///
+    pub fn with_max_pixels(mut self, max_pixels: u64) -> DecodeLimits {
+        self.max_pixels = max_pixels;
+        self
+    }
+
+    ///
+    /// Only decode images whose pixels can be decoded using at most this many
+    /// bytes
+    ///
+    pub fn with_max_alloc_bytes(mut self, max_alloc_bytes: u64) -> DecodeLimits {
+        self.max_alloc_bytes = max_alloc_bytes;
+        self
+    }
+
+    ///
+    /// Get the widest image that can be decoded
+    ///
+    pub fn get_max_width(&self) -> u32 {
+        self.max_width
+    }
+
+    ///
+    /// Get the tallest image that can be decoded
+    ///
+    pub fn get_max_height(&self) -> u32 {
+        self.max_height
+    }
+
+    ///
+    /// Get the most pixels an image can have
+    ///
+    pub fn get_max_pixels(&self) -> u64 {
+        self.max_pixels
+    }
+
+    ///
+    /// Get the most bytes that can be allocated while decoding the pixels
+    ///
+    pub fn get_max_alloc_bytes(&self) -> u64 {
+        self.max_alloc_bytes
+    }
+
+    ///
+    /// Check an image with the given width and height against the limits
+    ///
+    /// Fails with the first limit that the image goes over
+    ///
+    pub(crate) fn check(&self, width: u32, height: u32) -> Result<(), BitmapError> {
+        self.check_with_kept(width, height, 0, 0)
+    }
+
+    ///
+    /// Check the next image of a file that holds several of them against the
+    /// limits. The images that were already read are kept in memory, so their
+    /// pixels and bytes count towards the limits along with the next image
+    ///
+    /// Fails with the first limit that the images go over
+    ///
+    pub(crate) fn check_with_kept(
+        &self,
+        width: u32,
+        height: u32,
+        kept_pixels: u64,
+        kept_bytes: u64,
+    ) -> Result<(), BitmapError> {
+        let pixels = (width as u64 * height as u64).saturating_add(kept_pixels);
+        let alloc_bytes = (width as u64 * height as u64)
+            .saturating_mul(PEAK_BYTES_PER_PIXEL)
+            .saturating_add(kept_bytes);
+        let checks = [
+            ("width", width as u64, self.max_width as u64),
+            ("height", height as u64, self.max_height as u64),
+            ("pixel count", pixels, self.max_pixels),
+            ("allocation size", alloc_bytes, self.max_alloc_bytes),
+        ];
+        for (limit, value, max) in checks.iter() {
+            if value > max {
+                return Err(BitmapError::LimitExceeded {
+                    limit,
+                    value: *value,
+                    max: *max,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> DecodeLimits {
+        DecodeLimits::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitmapError;
+    use super::DecodeLimits;
+
+    #[test]
+    fn default_limits_allow_normal_images() {
+        let limits = DecodeLimits::default();
+        assert!(limits.check(1920, 1080).is_ok());
+        assert!(limits.check(0xffff, 1).is_ok());
+        assert!(limits.check(0, 0).is_ok());
+    }
+
+    #[test]
+    fn report_which_limit_was_exceeded() {
+        let limits = DecodeLimits::new();
+        match limits.check(0xffff, 0xffff) {
+            Err(BitmapError::LimitExceeded { limit, value, max }) => {
+                assert_eq!(limit, "pixel count");
+                assert_eq!(value, 0xffff * 0xffff);
+                assert_eq!(max, 1 << 28);
+            }
+            _ => panic!("expected the pixel count limit to be exceeded"),
+        }
+        match limits.check(0x10000, 1) {
+            Err(BitmapError::LimitExceeded { limit, .. }) => assert_eq!(limit, "width"),
+            _ => panic!("expected the width limit to be exceeded"),
+        }
+        let limits = limits.with_max_alloc_bytes(800);
+        match limits.check(10, 11) {
+            Err(BitmapError::LimitExceeded { limit, value, .. }) => {
+                assert_eq!(limit, "allocation size");
+                assert_eq!(value, 880);
+            }
+            _ => panic!("expected the allocation size limit to be exceeded"),
+        }
+        assert!(limits.check(10, 10).is_ok());
+    }
+
+    #[test]
+    fn count_the_images_that_were_already_read() {
+        let limits = DecodeLimits::new().with_max_pixels(150);
+        assert!(limits.check_with_kept(10, 10, 50, 0).is_ok());
+        match limits.check_with_kept(10, 10, 51, 0) {
+            Err(BitmapError::LimitExceeded { limit, value, .. }) => {
+                assert_eq!(limit, "pixel count");
+                assert_eq!(value, 151);
+            }
+            _ => panic!("expected the pixel count limit to be exceeded"),
+        }
+        let limits = limits.with_max_alloc_bytes(1000);
+        assert!(limits.check_with_kept(10, 10, 0, 200).is_ok());
+        match limits.check_with_kept(10, 10, 0, 201) {
+            Err(BitmapError::LimitExceeded { limit, value, .. }) => {
+                assert_eq!(limit, "allocation size");
+                assert_eq!(value, 1001);
+            }
+            _ => panic!("expected the allocation size limit to be exceeded"),
+        }
+    }
+
+    #[test]
+    fn unlimited_allows_any_image() {
+        let limits = DecodeLimits::unlimited();
+        assert!(limits.check(u32::MAX, u32::MAX).is_ok());
+        let limits = limits.with_max_height(10);
+        assert_eq!(limits.get_max_height(), 10);
+        assert!(limits.check(1, 11).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use super::rgba::Rgba;
+
+///
+/// Describes something that was wrong with a file read in using the lenient
+/// decode mode, and how it was repaired
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DecodeWarning {
+    /// The file size stored in the file header didn't match the length of
+    /// the data, the size was ignored
+    WrongFileSize {
+        /// size stored in the file header
+        declared: u32,
+        /// length of the data that was read
+        actual: usize,
+    },
+    /// The offset to the pixels pointed into the headers or past the end of
+    /// the data, the pixels were read from right after the color table
+    PixelDataOffsetRepaired {
+        /// offset stored in the file header
+        declared: u32,
+        /// offset the pixels were read from
+        used: usize,
+    },
+    /// The color table had fewer colors then the info header declared, only
+    /// the colors that were found are used
+    ColorTableTruncated {
+        /// number of colors the info header declared
+        declared: u32,
+        /// number of colors that were found
+        found: u32,
+    },
+    /// The pixel data ended early. The rows that were missing were padded
+    /// with the fill color, or left transparent if the image was run length
+    /// encoded
+    PixelDataTruncated {
+        /// number of bytes needed to read every pixel, for run length encoded
+        /// images this is the number of bytes needed to read the next code
+        needed: usize,
+        /// number of bytes that were left
+        available: usize,
+    },
+    /// Some pixels pointed to colors that aren't in the color table, they
+    /// were given the fill color
+    ColorIndexOutOfRange {
+        /// number of pixels that were given the fill color
+        count: usize,
+    },
+}
+
+impl std::fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeWarning::WrongFileSize { declared, actual } => write!(
+                f,
+                "File header says the file is {} bytes but {} bytes were read",
+                declared, actual
+            ),
+            DecodeWarning::PixelDataOffsetRepaired { declared, used } => write!(
+                f,
+                "Pixel data offset of {} is invalid, read the pixels at offset {} instead",
+                declared, used
+            ),
+            DecodeWarning::ColorTableTruncated { declared, found } => write!(
+                f,
+                "Color table should have {} colors but only {} were found",
+                declared, found
+            ),
+            DecodeWarning::PixelDataTruncated { needed, available } => write!(
+                f,
+                "Pixel data needed {} bytes but only {} were left, padded the missing rows",
+                needed, available
+            ),
+            DecodeWarning::ColorIndexOutOfRange { count } => write!(
+                f,
+                "{} pixels point to colors that aren't in the color table, gave them the fill color",
+                count
+            ),
+        }
+    }
+}
+
+///
+/// Keeps track of the repairs made while decoding a file in the lenient mode
+///
+pub(crate) struct Recovery {
+    /// color given to pixels that couldn't be read
+    fill: Rgba,
+    /// every repair that was made, in the order they were made
+    warnings: Vec<DecodeWarning>,
+}
+
+impl Recovery {
+    pub fn new(fill: Rgba) -> Recovery {
+        Recovery {
+            fill,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn get_fill(&self) -> Rgba {
+        self.fill
+    }
+
+    pub fn warn(&mut self, warning: DecodeWarning) {
+        self.warnings.push(warning);
+    }
+
+    pub fn into_warnings(self) -> Vec<DecodeWarning> {
+        self.warnings
+    }
+}
@@ -0,0 +1,266 @@
+use super::error::BitmapError;
+use super::image::BitMap;
+use super::indexed_image::IndexedBitMap;
+use super::quantizer;
+use super::rgba::Rgba;
+
+///
+/// The pattern used to spread the difference between the color of a pixel
+/// and the palette color it's given to the pixels that haven't been given a
+/// color yet
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DitherMethod {
+    /// Spreads the difference over the next pixel and three pixels of the
+    /// row below
+    FloydSteinberg,
+    /// Spreads three quarters of the difference over six pixels, which keeps
+    /// more contrast but loses detail in very light and dark areas
+    Atkinson,
+    /// Jarvis, Judice and Ninke. Spreads the difference over twelve pixels of
+    /// the current row and the two rows below, giving smoother results at the
+    /// cost of speed
+    JarvisJudiceNinke,
+    /// Spreads the difference over ten pixels of the current row and the two
+    /// rows below, close to Jarvis, Judice and Ninke but a little faster
+    Sierra,
+}
+
+impl DitherMethod {
+    ///
+    /// Get the pixels the difference is spread over as an x and y offset from
+    /// the current pixel and a weight, along with the number the weights are
+    /// divided by
+    ///
+    fn get_kernel(&self) -> (&'static [(i32, usize, f32)], f32) {
+        match self {
+            DitherMethod::FloydSteinberg => {
+                (&[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)], 16.0)
+            }
+            DitherMethod::Atkinson => (
+                &[
+                    (1, 0, 1.0),
+                    (2, 0, 1.0),
+                    (-1, 1, 1.0),
+                    (0, 1, 1.0),
+                    (1, 1, 1.0),
+                    (0, 2, 1.0),
+                ],
+                8.0,
+            ),
+            DitherMethod::JarvisJudiceNinke => (
+                &[
+                    (1, 0, 7.0),
+                    (2, 0, 5.0),
+                    (-2, 1, 3.0),
+                    (-1, 1, 5.0),
+                    (0, 1, 7.0),
+                    (1, 1, 5.0),
+                    (2, 1, 3.0),
+                    (-2, 2, 1.0),
+                    (-1, 2, 3.0),
+                    (0, 2, 5.0),
+                    (1, 2, 3.0),
+                    (2, 2, 1.0),
+                ],
+                48.0,
+            ),
+            DitherMethod::Sierra => (
+                &[
+                    (1, 0, 5.0),
+                    (2, 0, 3.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 5.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 3.0),
+                    (1, 2, 2.0),
+                ],
+                32.0,
+            ),
+        }
+    }
+}
+
+///
+/// Error diffusion dithering, used when an image is given colors from a
+/// palette that doesn't have all of its colors. Instead of giving each pixel
+/// the closest color and leaving bands in gradients, the difference is passed
+/// on to the pixels around it so that the average color of an area stays
+/// close to the original
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Dither {
+    /// pattern used to spread the difference
+    method: DitherMethod,
+    /// go through every other row from right to left
+    serpentine: bool,
+}
+
+impl Dither {
+    ///
+    /// Create a dither that uses the given method, going through every row
+    /// from left to right
+    ///
+    pub fn new(method: DitherMethod) -> Dither {
+        Dither {
+            method,
+            serpentine: false,
+        }
+    }
+
+    ///
+    /// Go through every other row from right to left, which stops the
+    /// difference from always being pushed the same way and leaving diagonal
+    /// patterns in the image
+    ///
+    pub fn with_serpentine(mut self, serpentine: bool) -> Dither {
+        self.serpentine = serpentine;
+        self
+    }
+
+    ///
+    /// Get the pattern used to spread the difference
+    ///
+    pub fn get_method(&self) -> DitherMethod {
+        self.method
+    }
+
+    ///
+    /// Check if every other row is gone through from right to left
+    ///
+    pub fn is_serpentine(&self) -> bool {
+        self.serpentine
+    }
+
+    ///
+    /// Give every pixel of a bitmap a color from the palette, passing the
+    /// difference on to the pixels that come after it. The rows are gone
+    /// through from the top of the image to the bottom, and the resolution of
+    /// the bitmap is kept
+    ///
+    /// Fails if the palette is empty or has more then 256 colors
+    ///
+    pub fn apply(&self, bitmap: &BitMap, palette: &[Rgba]) -> Result<IndexedBitMap, BitmapError> {
+        let mut image =
+            IndexedBitMap::new(bitmap.get_width(), bitmap.get_height(), palette.to_vec())?;
+        let (x_pixels_per_meter, y_pixels_per_meter) = bitmap.get_pixels_per_meter();
+        image.set_pixels_per_meter(x_pixels_per_meter, y_pixels_per_meter);
+
+        let (kernel, divisor) = self.method.get_kernel();
+        let palette: Vec<[u32; 4]> = palette.iter().map(quantizer::get_channels).collect();
+        let width = bitmap.get_width() as usize;
+        // differences waiting to be added to the current row and the rows
+        // below it
+        let mut errors = vec![vec![[0.0f32; 4]; width]; 3];
+        for y in 0..bitmap.get_height() {
+            let backwards = self.serpentine && y % 2 == 1;
+            for i in 0..width {
+                let x = match backwards {
+                    true => width - i - 1,
+                    false => i,
+                };
+                let channels = quantizer::get_channels(bitmap.get_pixel(x as u32, y).unwrap());
+                let mut wanted = [0.0f32; 4];
+                let mut target = [0u32; 4];
+                for c in 0..4 {
+                    wanted[c] = channels[c] as f32 + errors[0][x][c];
+                    target[c] = wanted[c].round().clamp(0.0, 255.0) as u32;
+                }
+                let index = quantizer::get_nearest_channels(palette.iter().copied(), &target);
+                image.set_index(x as u32, y, index)?;
+
+                let chosen = palette[index as usize];
+                for (dx, dy, weight) in kernel {
+                    let dx = match backwards {
+                        true => -dx,
+                        false => *dx,
+                    };
+                    let nx = x as i32 + dx;
+                    if nx < 0 || nx >= width as i32 {
+                        continue;
+                    }
+                    for c in 0..4 {
+                        let error = wanted[c] - chosen[c] as f32;
+                        errors[*dy][nx as usize][c] += error * weight / divisor;
+                    }
+                }
+            }
+            // move on to the next row
+            errors.rotate_left(1);
+            errors[2] = vec![[0.0; 4]; width];
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitMap;
+    use super::Dither;
+    use super::DitherMethod;
+    use super::Rgba;
+
+    const METHODS: [DitherMethod; 4] = [
+        DitherMethod::FloydSteinberg,
+        DitherMethod::Atkinson,
+        DitherMethod::JarvisJudiceNinke,
+        DitherMethod::Sierra,
+    ];
+
+    fn create_gray(level: u8) -> BitMap {
+        let mut bitmap = BitMap::new(32, 32);
+        bitmap.clear_image(Rgba::rgb(level, level, level));
+        bitmap
+    }
+
+    fn count_white(bitmap: &BitMap) -> usize {
+        bitmap.get_pixels().iter().filter(|p| p.is_white()).count()
+    }
+
+    #[test]
+    fn kernels_spread_the_whole_difference() {
+        for method in METHODS.iter() {
+            let (kernel, divisor) = method.get_kernel();
+            let total: f32 = kernel.iter().map(|(_, _, weight)| weight).sum();
+            match method {
+                DitherMethod::Atkinson => assert_eq!(total / divisor, 0.75),
+                _ => assert_eq!(total, divisor),
+            }
+        }
+    }
+
+    #[test]
+    fn keep_the_average_color_of_an_area() {
+        let palette = [Rgba::black(), Rgba::white()];
+        let bitmap = create_gray(64);
+        for method in METHODS.iter() {
+            for serpentine in [false, true].iter() {
+                let dither = Dither::new(*method).with_serpentine(*serpentine);
+                let image = dither.apply(&bitmap, &palette).unwrap();
+                let white = count_white(&image.to_bitmap());
+                // a quarter of the pixels should be white, a little less for
+                // atkinson since it doesn't pass on the whole difference
+                assert!(white > 150 && white < 312, "{:?} gave {}", dither, white);
+            }
+        }
+    }
+
+    #[test]
+    fn keep_colors_that_are_in_the_palette() {
+        let palette = [Rgba::black(), Rgba::white()];
+        let mut bitmap = create_gray(255);
+        bitmap.set_pixel(3, 4, Rgba::black()).unwrap();
+        let dither = Dither::new(DitherMethod::FloydSteinberg);
+        let image = dither.apply(&bitmap, &palette).unwrap();
+        assert!(image.to_bitmap() == bitmap);
+    }
+
+    #[test]
+    fn fail_to_dither_to_an_empty_palette() {
+        let dither = Dither::new(DitherMethod::Sierra);
+        assert!(dither.apply(&create_gray(0), &[]).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+///
+/// Everything that can go wrong while reading, writing or editing a bitmap
+///
+#[derive(Debug)]
+pub enum BitmapError {
+    /// Reading from or writing to a file or stream failed
+    Io(std::io::Error),
+    /// The data ended before a part of the bitmap could be read
+    Truncated {
+        /// part of the bitmap that was being read
+        section: &'static str,
+        /// offset in bytes where the part of the bitmap starts
+        offset: usize,
+        /// number of bytes needed to read the part of the bitmap
+        needed: usize,
+        /// number of bytes that were left
+        available: usize,
+    },
+    /// The data doesn't start with 'BM', so it isn't a bitmap
+    BadMagic([u8; 2]),
+    /// The size of the info header doesn't match any known version
+    UnsupportedHeader(u32),
+    /// The pixels are compressed using a method that can't be read
+    UnsupportedCompression(u32),
+    /// The number of bits used for each pixel can't be read
+    UnsupportedBitDepth(u16),
+    /// The data is a bitmap, but some part of it doesn't make sense
+    InvalidData(&'static str),
+    /// A coordinate is outside of the image
+    OutOfBounds {
+        /// x coordinate that was given
+        x: u32,
+        /// y coordinate that was given
+        y: u32,
+        /// width of the image
+        width: u32,
+        /// height of the image
+        height: u32,
+    },
+    /// An argument that was passed in isn't allowed
+    InvalidArgument(&'static str),
+    /// The image is bigger then the decode limits allow
+    LimitExceeded {
+        /// name of the limit that was exceeded
+        limit: &'static str,
+        /// value the image needed
+        value: u64,
+        /// largest value the limit allows
+        max: u64,
+    },
+}
+
+impl BitmapError {
+    ///
+    /// Move the offset of truncated data errors, used when a part of the
+    /// bitmap was read from a slice that doesn't start at the beginning of
+    /// the file
+    ///
+    pub(crate) fn offset_by(self, start: usize) -> BitmapError {
+        match self {
+            BitmapError::Truncated {
+                section,
+                offset,
+                needed,
+                available,
+            } => BitmapError::Truncated {
+                section,
+                offset: offset + start,
+                needed,
+                available,
+            },
+            error => error,
+        }
+    }
+}
+
+impl std::fmt::Display for BitmapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BitmapError::Io(why) => write!(f, "Couldn't read or write bitmap: {}", why),
+            BitmapError::Truncated {
+                section,
+                offset,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Not enough data to read the {}, needed {} bytes at offset {} but only {} were left",
+                section, needed, offset, available
+            ),
+            BitmapError::BadMagic(magic) => write!(
+                f,
+                "Data isn't a bitmap, expected it to start with 'BM' but found {:?}",
+                magic
+            ),
+            BitmapError::UnsupportedHeader(size) => {
+                write!(f, "Info header with a size of {} isn't supported", size)
+            }
+            BitmapError::UnsupportedCompression(compression) => {
+                write!(f, "Compression type {} isn't supported", compression)
+            }
+            BitmapError::UnsupportedBitDepth(bit_depth) => {
+                write!(f, "Bit depth of {} isn't supported", bit_depth)
+            }
+            BitmapError::InvalidData(why) => write!(f, "Invalid bitmap: {}", why),
+            BitmapError::OutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "Pixel ({}, {}) is not contained inside of the {}x{} image",
+                x, y, width, height
+            ),
+            BitmapError::InvalidArgument(why) => write!(f, "{}", why),
+            BitmapError::LimitExceeded { limit, value, max } => write!(
+                f,
+                "Image {} of {} is over the decode limit of {}",
+                limit, value, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BitmapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BitmapError::Io(why) => Some(why),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for BitmapError {
+    fn from(why: std::io::Error) -> BitmapError {
+        BitmapError::Io(why)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitmapError;
+    use std::error::Error;
+
+    #[test]
+    fn io_errors_keep_their_source() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let error = BitmapError::from(io);
+        assert!(error.source().is_some());
+        assert!(error.to_string().contains("missing"));
+        assert!(BitmapError::InvalidArgument("bad").source().is_none());
+    }
+
+    #[test]
+    fn move_offset_of_truncated_data() {
+        let error = BitmapError::Truncated {
+            section: "info header",
+            offset: 2,
+            needed: 40,
+            available: 10,
+        };
+        match error.offset_by(14) {
+            BitmapError::Truncated { offset, .. } => assert_eq!(offset, 16),
+            _ => panic!("expected truncated data"),
+        }
+        assert!(matches!(
+            BitmapError::BadMagic(*b"XX").offset_by(14),
+            BitmapError::BadMagic(_)
+        ));
+    }
+}
@@ -1,10 +1,21 @@
+use std::io::Read;
+
 use super::bit_depth::BitDepth;
+use super::bitmap_info::BitmapInfo;
+use super::decode_limits::DecodeLimits;
+use super::decode_warning::DecodeWarning;
+use super::decode_warning::Recovery;
+use super::error::BitmapError;
 use super::file_data::FileData;
 use super::file_header::FileHeader;
+use super::header_version::HeaderVersion;
 use super::image::BitMap;
+use super::indexed_image::IndexedBitMap;
 use super::info_header::InfoHeader;
 use super::rgb_quad::RgbQuad;
 use super::rgba::Rgba;
+use super::save_options::SaveOptions;
+use super::util;
 
 pub struct File {
     file: FileHeader,
@@ -17,70 +28,100 @@ impl File {
     ///
     /// Read in Bitmap file from file
     ///
-    pub fn read(filename: &str) -> Result<File, String> {
-        use std::io::ErrorKind;
-        let byte_array = match std::fs::read(filename) {
-            Err(why) => {
-                return Err(String::from(match why.kind() {
-                    ErrorKind::NotFound => format!("File {} was not found!", filename),
-                    ErrorKind::PermissionDenied => format!(
-                        "Could not read file {} because program lacks privilege!",
-                        filename
-                    ),
-                    _ => format!("Couldn't read file {}!", filename),
-                }))
-            }
-            Ok(bytes) => bytes,
-        };
-        if byte_array.len() == 0 {
-            format!("File {} is empty!", filename);
-        }
+    pub fn read(filename: &str, limits: &DecodeLimits) -> Result<File, BitmapError> {
+        let byte_array = std::fs::read(filename)?;
+        File::from_slice_with_limits(&byte_array, limits)
+    }
 
-        let mut size_clamp = FileHeader::estimated_byte_size();
+    ///
+    /// Read in Bitmap file from file, repairing what can be repaired instead
+    /// of failing
+    ///
+    pub fn read_lenient(
+        filename: &str,
+        limits: &DecodeLimits,
+        recovery: &mut Recovery,
+    ) -> Result<File, BitmapError> {
+        let byte_array = std::fs::read(filename)?;
+        File::from_slice_lenient(&byte_array, limits, recovery)
+    }
 
-        // file header
-        let file_header_bytes = if byte_array.len() > size_clamp {
-            &byte_array[FileHeader::from_slice_range()]
-        } else {
-            return Err(String::from("Not enough data to parse bitmap header."));
-        };
-        let file = match FileHeader::from_slice(&file_header_bytes) {
-            Err(why) => return Err(String::from(why)),
-            Ok(f) => f,
-        };
+    ///
+    /// Read in Bitmap file from a reader
+    ///
+    pub fn from_reader<R: Read>(mut reader: R, limits: &DecodeLimits) -> Result<File, BitmapError> {
+        let mut byte_array = Vec::new();
+        reader.read_to_end(&mut byte_array)?;
+        File::from_slice_with_limits(&byte_array, limits)
+    }
 
-        // info header
-        size_clamp = size_clamp + InfoHeader::estimated_byte_size();
-        let file_info_bytes = if byte_array.len() > size_clamp {
-            &byte_array[InfoHeader::from_slice_range()]
-        } else {
-            return Err(String::from("Not enough data to parse bitmap info header."));
-        };
-        let info = match InfoHeader::from_slice(&file_info_bytes) {
-            Err(why) => return Err(String::from(why)),
-            Ok(i) => i,
-        };
+    ///
+    /// Read in Bitmap file from a slice of bytes using the default decode
+    /// limits
+    ///
+    pub fn from_slice(byte_array: &[u8]) -> Result<File, BitmapError> {
+        File::from_slice_with_limits(byte_array, &DecodeLimits::default())
+    }
 
-        // colors
-        let offset = (file.get_byte_size() + info.get_byte_size()) as usize;
-        let color_byte_size = info.get_colors_used() as usize * RgbQuad::single_rgb_quad_size();
-        size_clamp = offset + color_byte_size;
-        let color_bytes = if byte_array.len() > size_clamp {
-            &byte_array[offset..size_clamp]
-        } else {
-            return Err(String::from("Not enough data to parse bitmap colors."));
-        };
-        let colors = match RgbQuad::from_slice(&color_bytes) {
-            Err(why) => return Err(String::from(why)),
-            Ok(i) => i,
-        };
+    ///
+    /// Read in Bitmap file from a slice of bytes
+    ///
+    /// Any error caused by running out of data has its offset set from the
+    /// start of the slice. The size of the image is checked against the limits
+    /// before the color table or pixels are read
+    ///
+    pub fn from_slice_with_limits(
+        byte_array: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<File, BitmapError> {
+        File::decode(byte_array, limits, None)
+    }
+
+    ///
+    /// Read in Bitmap file from a slice of bytes, repairing what can be
+    /// repaired instead of failing. Each repair is recorded as a warning
+    ///
+    pub fn from_slice_lenient(
+        byte_array: &[u8],
+        limits: &DecodeLimits,
+        recovery: &mut Recovery,
+    ) -> Result<File, BitmapError> {
+        File::decode(byte_array, limits, Some(recovery))
+    }
+
+    ///
+    /// Decode the file, if there's a recovery the file is read in the lenient
+    /// mode
+    ///
+    fn decode(
+        byte_array: &[u8],
+        limits: &DecodeLimits,
+        mut recovery: Option<&mut Recovery>,
+    ) -> Result<File, BitmapError> {
+        let (file, info, colors, color_end) =
+            File::decode_headers(byte_array, limits, recovery.as_deref_mut())?;
 
         // data
-        let pixel_bytes = &byte_array[file.get_off_bits() as usize..byte_array.len()];
-        let data = match FileData::from_slice(&pixel_bytes, &info, &colors) {
-            Some(d) => d,
-            None => return Err(String::from("Couldn't read in pixels from file")),
+        let mut off_bits = file.get_off_bits() as usize;
+        if let Some(recovery) = recovery.as_deref_mut() {
+            if off_bits < color_end || off_bits > byte_array.len() {
+                recovery.warn(DecodeWarning::PixelDataOffsetRepaired {
+                    declared: file.get_off_bits(),
+                    used: color_end,
+                });
+                off_bits = color_end;
+            }
+        }
+        let pixel_bytes = match byte_array.get(off_bits..) {
+            Some(bytes) => bytes,
+            None => {
+                return Err(BitmapError::InvalidData(
+                    "pixel data starts after the end of the file",
+                ))
+            }
         };
+        let data = FileData::from_slice(pixel_bytes, &info, &colors, recovery)
+            .map_err(|why| why.offset_by(off_bits))?;
         Ok(File {
             file,
             info,
@@ -89,14 +130,224 @@ impl File {
         })
     }
 
+    ///
+    /// Decode the file header, info header and color table, along with where
+    /// the color table ends. The pixels aren't read
+    ///
+    fn decode_headers(
+        byte_array: &[u8],
+        limits: &DecodeLimits,
+        mut recovery: Option<&mut Recovery>,
+    ) -> Result<(FileHeader, InfoHeader, RgbQuad, usize), BitmapError> {
+        // file header
+        let file = FileHeader::from_slice(byte_array)?;
+        if let Some(recovery) = recovery.as_deref_mut() {
+            if file.get_size() as usize != byte_array.len() {
+                recovery.warn(DecodeWarning::WrongFileSize {
+                    declared: file.get_size(),
+                    actual: byte_array.len(),
+                });
+            }
+        }
+
+        // info header, the size of the header depends on which version it is
+        let info_offset = FileHeader::estimated_byte_size();
+        let info = InfoHeader::from_slice(&byte_array[info_offset..])
+            .map_err(|why| why.offset_by(info_offset))?;
+        limits.check(info.get_width(), info.get_height())?;
+
+        // colors
+        let offset = (file.get_byte_size() + info.get_byte_size()) as usize;
+        let entry_size = info.get_color_table_entry_size();
+        let color_byte_size = info.get_color_table_size() as usize * entry_size;
+        let mut color_end = offset.saturating_add(color_byte_size);
+        let off_bits = file.get_off_bits() as usize;
+        if let Some(recovery) = recovery {
+            // the color table can't run into the pixels or past the end
+            let mut available_end = byte_array.len();
+            if off_bits >= offset && off_bits < available_end {
+                available_end = off_bits;
+            }
+            if color_end > available_end {
+                let found = available_end.saturating_sub(offset) / entry_size;
+                recovery.warn(DecodeWarning::ColorTableTruncated {
+                    declared: info.get_color_table_size(),
+                    found: found as u32,
+                });
+                color_end = offset + found * entry_size;
+            }
+        }
+        let color_bytes = match byte_array.get(offset..color_end) {
+            Some(bytes) => bytes,
+            None => {
+                return Err(BitmapError::Truncated {
+                    section: "color table",
+                    offset,
+                    needed: color_byte_size,
+                    available: byte_array.len().saturating_sub(offset),
+                })
+            }
+        };
+        let colors =
+            RgbQuad::from_slice(color_bytes, entry_size).map_err(|why| why.offset_by(offset))?;
+        Ok((file, info, colors, color_end))
+    }
+
+    ///
+    /// Read the headers and color table of a bitmap from a slice of bytes,
+    /// without decoding the pixels
+    ///
+    /// The image isn't checked against any limits since no pixels are
+    /// allocated, and the pixel data doesn't have to be there
+    ///
+    pub fn probe(byte_array: &[u8]) -> Result<BitmapInfo, BitmapError> {
+        let (file, info, colors, _) =
+            File::decode_headers(byte_array, &DecodeLimits::unlimited(), None)?;
+        BitmapInfo::new(&file, &info, &colors)
+    }
+
+    ///
+    /// Read the headers and color table of a bitmap from a reader, without
+    /// reading the pixels. Only the bytes up to the end of the color table are
+    /// read from the reader
+    ///
+    pub fn probe_reader<R: Read>(reader: R) -> Result<BitmapInfo, BitmapError> {
+        let (file, info, colors) = File::read_headers(reader)?;
+        BitmapInfo::new(&file, &info, &colors)
+    }
+
+    ///
+    /// Read the file header, info header and color table from a reader,
+    /// leaving the reader at the end of the color table
+    ///
+    pub fn read_headers<R: Read>(
+        mut reader: R,
+    ) -> Result<(FileHeader, InfoHeader, RgbQuad), BitmapError> {
+        let mut byte_array = Vec::new();
+        // the file header and the size of the info header
+        let info_offset = FileHeader::estimated_byte_size();
+        let mut end = info_offset + InfoHeader::size_field_byte_size();
+        File::read_up_to(&mut reader, &mut byte_array, end)?;
+        FileHeader::from_slice(&byte_array)?;
+
+        // the info header, along with any masks that follow it
+        let mut i = info_offset;
+        // only read in headers of a known size, so that a huge size can't read
+        // in the rest of the stream. Any other size fails once the header is
+        // decoded below
+        let info_size = util::byte_slice_to_u32(&byte_array, &mut i);
+        end = match HeaderVersion::from_byte_size(info_size) {
+            Some(version) => info_offset + version as usize,
+            None => end,
+        };
+        File::read_up_to(&mut reader, &mut byte_array, end)?;
+        end += InfoHeader::trailing_mask_byte_size(&byte_array[info_offset..]);
+        File::read_up_to(&mut reader, &mut byte_array, end)?;
+        let info = InfoHeader::from_slice(&byte_array[info_offset..])
+            .map_err(|why| why.offset_by(info_offset))?;
+
+        // the color table
+        let color_byte_size =
+            info.get_color_table_size() as usize * info.get_color_table_entry_size();
+        end = (info_offset + info.get_byte_size() as usize).saturating_add(color_byte_size);
+        File::read_up_to(&mut reader, &mut byte_array, end)?;
+        let (file, info, colors, _) =
+            File::decode_headers(&byte_array, &DecodeLimits::unlimited(), None)?;
+        Ok((file, info, colors))
+    }
+
+    ///
+    /// Read from the reader until there are length bytes in the byte array, or
+    /// the reader runs out of data
+    ///
+    fn read_up_to<R: Read>(
+        reader: &mut R,
+        byte_array: &mut Vec<u8>,
+        length: usize,
+    ) -> Result<(), BitmapError> {
+        let remaining = length.saturating_sub(byte_array.len()) as u64;
+        reader.take(remaining).read_to_end(byte_array)?;
+        Ok(())
+    }
+
     ///
     /// Create a bitmap file from a bitmap image
     ///
     pub fn create(bitmap: &BitMap, bit_depth: BitDepth) -> File {
+        File::create_with_options(bitmap, bit_depth, &SaveOptions::default())
+    }
+
+    ///
+    /// Create a bitmap file from a bitmap image using the given save options
+    ///
+    pub fn create_with_options(
+        bitmap: &BitMap,
+        bit_depth: BitDepth,
+        options: &SaveOptions,
+    ) -> File {
         // TODO: Figure out if we can simplify this
-        let data = FileData::from_bitmap(bitmap, bit_depth);
+        let info = InfoHeader::from(bitmap, bit_depth, options);
+        let flipped;
+        let bitmap = if info.is_top_down() {
+            // creating a bitmap from a list of pixels flips the rows, which
+            // puts the top row first
+            let pixels = bitmap.get_pixels().clone();
+            flipped = BitMap::create(bitmap.get_width(), bitmap.get_height(), pixels).unwrap();
+            &flipped
+        } else {
+            bitmap
+        };
         let colors = RgbQuad::from(bitmap, bit_depth);
-        let info = InfoHeader::from(bitmap, bit_depth);
+        let data = FileData::from_bitmap(bitmap, bit_depth, &info, &colors);
+        File::assemble(info, colors, data)
+    }
+
+    ///
+    /// Create a 2, 16 or 256 color bitmap file from an indexed image, writing
+    /// its palette as the color table and its indices as they are
+    ///
+    /// The palette must fit in the color table of the bit depth
+    ///
+    pub fn create_indexed(
+        image: &IndexedBitMap,
+        bit_depth: BitDepth,
+        options: &SaveOptions,
+    ) -> File {
+        let palette = image.get_palette();
+        let mut info = InfoHeader::new(
+            image.get_width(),
+            image.get_height(),
+            bit_depth,
+            palette.len() as u32,
+            options,
+        );
+        let (x_pixels_per_meter, y_pixels_per_meter) = image.get_pixels_per_meter();
+        info.set_pixels_per_meter(x_pixels_per_meter, y_pixels_per_meter);
+        info.set_colors_important(image.get_colors_important());
+        let width = image.get_width() as usize;
+        let indices: Vec<u8> = match info.is_top_down() && width > 0 {
+            true => image
+                .get_indices()
+                .chunks(width)
+                .rev()
+                .flatten()
+                .copied()
+                .collect(),
+            false => image.get_indices().to_vec(),
+        };
+        let colors = RgbQuad::from_colors(palette.to_vec());
+        let data = FileData::from_indices(&indices, bit_depth, &info, &colors);
+        File::assemble(info, colors, data)
+    }
+
+    ///
+    /// Put the headers together with the color table and pixel data
+    ///
+    fn assemble(mut info: InfoHeader, colors: RgbQuad, data: FileData) -> File {
+        if info.is_run_length_encoded() {
+            // the size of compressed images must be stored in the header
+            info.set_size_image(data.get_bytes_size());
+        }
         let file = FileHeader::new(
             data.get_bytes_size(),
             colors.get_bytes_size(),
@@ -134,8 +385,70 @@ impl File {
         self.info.get_height()
     }
 
+    ///
+    /// Get the horizontal and vertical resolution in pixels per meter
+    ///
+    pub fn get_pixels_per_meter(&self) -> (u32, u32) {
+        (
+            self.info.get_x_pixels_per_meter(),
+            self.info.get_y_pixels_per_meter(),
+        )
+    }
+
+    ///
+    /// Get the colors of the color table if the pixels point into it, which is
+    /// only the case for 2, 16 and 256 color images
+    ///
+    pub fn get_palette(&self) -> Option<Vec<Rgba>> {
+        match self.data {
+            FileData::Bits(_) => Some(self.colors.clone_colors()),
+            FileData::Pixels(_) => None,
+        }
+    }
+
+    ///
+    /// Get the number of colors that are important for showing the image
+    ///
+    pub fn get_colors_important(&self) -> u32 {
+        self.info.get_colors_important()
+    }
+
+    ///
+    /// Get the index of the color of each pixel, starting with the bottom row,
+    /// if every pixel points into the color table
+    ///
+    pub fn get_indices(&self) -> Option<Vec<u8>> {
+        let mut indices = self.data.as_indices()?;
+        if self.info.is_top_down() {
+            File::flip_rows(&mut indices, self.info.get_width() as usize);
+        }
+        Some(indices)
+    }
+
+    ///
+    /// Get the pixels of the image, starting with the bottom row
+    ///
     pub fn get_bitmap_as_pixels(&self) -> Vec<Rgba> {
-        self.data.as_rgba()
+        let mut pixels = self.data.as_rgba();
+        if self.info.is_top_down() {
+            File::flip_rows(&mut pixels, self.info.get_width() as usize);
+        }
+        pixels
+    }
+
+    ///
+    /// Reverse the order of the rows in place, so that the rows of a top down
+    /// image start with the bottom row
+    ///
+    fn flip_rows<T>(values: &mut [T], width: usize) {
+        if width == 0 {
+            return;
+        }
+        let height = values.len() / width;
+        for y in 0..height / 2 {
+            let (top, bottom) = values.split_at_mut((height - 1 - y) * width);
+            top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+        }
     }
 }
 
@@ -159,8 +472,12 @@ impl std::fmt::Display for File {
 mod test {
     use super::BitDepth;
     use super::BitMap;
+    use super::BitmapError;
+    use super::DecodeLimits;
     use super::File;
     use super::Rgba;
+    use super::SaveOptions;
+    use crate::bitmap::header_version::HeaderVersion;
 
     #[test]
     fn check_files_height_and_width() {
@@ -193,4 +510,432 @@ mod test {
         let file = File::create(&b, BitDepth::AllColorsAndShades);
         assert_eq!(file.to_bytes().len(), file.calculate_file_size() as usize);
     }
+
+    #[test]
+    fn read_file_with_v4_and_v5_headers() {
+        let mut b = BitMap::new(3, 2);
+        b.set_pixel(0, 0, Rgba::rgba(255, 0, 0, 50)).unwrap();
+        b.set_pixel(2, 1, Rgba::black()).unwrap();
+        for version in &[HeaderVersion::V4, HeaderVersion::V5] {
+            for bit_depth in &[BitDepth::AllColors, BitDepth::AllColorsAndShades] {
+                let options = SaveOptions::new().with_header(*version);
+                let file = File::create_with_options(&b, *bit_depth, &options);
+                let bytes = file.to_bytes();
+                assert_eq!(bytes.len(), file.calculate_file_size() as usize);
+                let read = File::from_slice(&bytes).unwrap();
+                assert_eq!(read.get_width(), 3);
+                assert_eq!(read.get_height(), 2);
+                let info_bytes = File::create(&b, *bit_depth).to_bytes();
+                let expected = File::from_slice(&info_bytes)
+                    .unwrap()
+                    .get_bitmap_as_pixels();
+                assert_eq!(read.get_bitmap_as_pixels(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn read_file_with_alpha_mask() {
+        let mut b = BitMap::new(3, 2);
+        b.set_pixel(0, 0, Rgba::rgba(255, 0, 0, 50)).unwrap();
+        b.set_pixel(1, 1, Rgba::rgba(0, 0, 255, 0)).unwrap();
+        let options = SaveOptions::new().with_alpha_mask(true);
+        let file = File::create_with_options(&b, BitDepth::AllColorsAndShades, &options);
+        let bytes = file.to_bytes();
+        // alpha is scaled to fill the entire byte, the bottom row comes first
+        let pixel_start = bytes.len() - 4 * 6;
+        assert_eq!(bytes[pixel_start + 3], 255);
+        assert_eq!(bytes[pixel_start + 7], 0);
+        assert_eq!(bytes[pixel_start + 15], 128);
+        let read = File::from_slice(&bytes).unwrap();
+        assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+    }
+
+    #[test]
+    fn read_file_with_custom_bit_fields() {
+        let mut b = BitMap::new(2, 1);
+        b.set_pixel(0, 0, Rgba::rgb(1, 2, 3)).unwrap();
+        let file = File::create(&b, BitDepth::AllColorsAndShades);
+        let mut bytes = file.to_bytes();
+        // use a 40 byte header followed by masks for red, green, blue and alpha
+        // stored in that order, instead of blue, green, red
+        bytes[30] = 6;
+        let masks = [0xffu32, 0xff00, 0xff_0000, 0xff00_0000];
+        let mut mask_bytes = masks
+            .iter()
+            .flat_map(|m| m.to_le_bytes().to_vec())
+            .collect();
+        let mut pixels = bytes.split_off(54);
+        bytes.append(&mut mask_bytes);
+        bytes[10] += 16;
+        pixels[0] = 1;
+        pixels[1] = 2;
+        pixels[2] = 3;
+        pixels[3] = 255;
+        bytes.append(&mut pixels);
+        let read = File::from_slice(&bytes).unwrap();
+        let pixels = read.get_bitmap_as_pixels();
+        assert_eq!(pixels[0], Rgba::rgb(1, 2, 3));
+        assert_eq!(pixels[1], Rgba::rgba(255, 255, 255, 39));
+    }
+
+    #[test]
+    fn read_16_bit_files() {
+        let mut b = BitMap::new(3, 2);
+        b.set_pixel(0, 0, Rgba::rgb(255, 0, 0)).unwrap();
+        b.set_pixel(1, 0, Rgba::rgb(0, 255, 0)).unwrap();
+        b.set_pixel(2, 1, Rgba::rgb(0, 0, 255)).unwrap();
+        for version in &[HeaderVersion::Info, HeaderVersion::V4, HeaderVersion::V5] {
+            for rgb565 in &[false, true] {
+                let options = SaveOptions::new()
+                    .with_header(*version)
+                    .with_rgb565(*rgb565);
+                let file = File::create_with_options(&b, BitDepth::HighColor, &options);
+                let bytes = file.to_bytes();
+                assert_eq!(bytes.len(), file.calculate_file_size() as usize);
+                let read = File::from_slice(&bytes).unwrap();
+                assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+            }
+        }
+    }
+
+    #[test]
+    fn expand_16_bit_colors_to_8_bits() {
+        let mut b = BitMap::new(1, 1);
+        b.set_pixel(0, 0, Rgba::rgb(100, 150, 200)).unwrap();
+        let options = SaveOptions::new().with_rgb565(true);
+        let file = File::create_with_options(&b, BitDepth::HighColor, &options);
+        let read = File::from_slice(&file.to_bytes()).unwrap();
+        assert_eq!(read.get_bitmap_as_pixels()[0], Rgba::rgb(99, 150, 197));
+        let file = File::create(&b, BitDepth::HighColor);
+        let read = File::from_slice(&file.to_bytes()).unwrap();
+        assert_eq!(read.get_bitmap_as_pixels()[0], Rgba::rgb(99, 148, 197));
+    }
+
+    ///
+    /// Swap the pixels of an uncompressed file for run length encoded ones
+    ///
+    fn compress_file(bytes: &[u8], compression: u32, pixels: &[u8]) -> Vec<u8> {
+        let off_bits = u32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]);
+        let mut compressed = bytes[..off_bits as usize].to_vec();
+        compressed.extend_from_slice(pixels);
+        let file_size = compressed.len() as u32;
+        compressed[2..6].copy_from_slice(&file_size.to_le_bytes());
+        compressed[30..34].copy_from_slice(&compression.to_le_bytes());
+        compressed[34..38].copy_from_slice(&(pixels.len() as u32).to_le_bytes());
+        compressed
+    }
+
+    #[test]
+    fn read_rle8_files() {
+        let mut b = BitMap::new(5, 3);
+        b.set_pixel(0, 0, Rgba::black()).unwrap();
+        b.set_pixel(1, 1, Rgba::rgb(255, 0, 0)).unwrap();
+        b.set_pixel(4, 2, Rgba::rgb(0, 0, 255)).unwrap();
+        let bytes = File::create(&b, BitDepth::Color256Bit).to_bytes();
+        let uncompressed = File::from_slice(&bytes).unwrap();
+
+        // copy each row of indices using both encoded and absolute runs
+        let off_bits = bytes[10] as usize;
+        let mut pixels = Vec::new();
+        for row in bytes[off_bits..].chunks(8) {
+            pixels.extend_from_slice(&[0, 3, row[0], row[1], row[2], 0]);
+            pixels.extend_from_slice(&[1, row[3], 1, row[4], 0, 0]);
+        }
+        pixels.extend_from_slice(&[0, 1]);
+        let read = File::from_slice(&compress_file(&bytes, 1, &pixels)).unwrap();
+        assert_eq!(read.get_width(), 5);
+        assert_eq!(read.get_height(), 3);
+        assert_eq!(
+            read.get_bitmap_as_pixels(),
+            uncompressed.get_bitmap_as_pixels()
+        );
+        assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+    }
+
+    #[test]
+    fn read_rle8_files_with_skipped_pixels() {
+        let b = BitMap::new(2, 2);
+        let bytes = File::create(&b, BitDepth::Color256Bit).to_bytes();
+        // color the first pixel, move one up and end the bitmap
+        let pixels = [1, 0, 0, 2, 0, 1, 0, 1];
+        let read = File::from_slice(&compress_file(&bytes, 1, &pixels)).unwrap();
+        let transparent = Rgba::rgba(0, 0, 0, 0);
+        assert_eq!(
+            read.get_bitmap_as_pixels(),
+            vec![Rgba::white(), transparent, transparent, transparent]
+        );
+        assert!(File::from_slice(&compress_file(&bytes, 1, &pixels[..4])).is_err());
+    }
+
+    #[test]
+    fn read_rle4_files() {
+        let mut b = BitMap::new(5, 2);
+        b.set_pixel(0, 0, Rgba::black()).unwrap();
+        b.set_pixel(3, 0, Rgba::rgb(255, 0, 0)).unwrap();
+        b.set_pixel(4, 1, Rgba::rgb(0, 0, 255)).unwrap();
+        let bytes = File::create(&b, BitDepth::Color16Bit).to_bytes();
+        let uncompressed = File::from_slice(&bytes).unwrap();
+
+        // copy the first 3 pixels of each row as is, then repeat the last 2
+        let off_bits = bytes[10] as usize;
+        let mut pixels = Vec::new();
+        for row in bytes[off_bits..].chunks(4) {
+            pixels.extend_from_slice(&[0, 3, row[0], row[1] & 0xf0]);
+            pixels.extend_from_slice(&[2, (row[1] << 4) | (row[2] >> 4), 0, 0]);
+        }
+        pixels.extend_from_slice(&[0, 1]);
+        let read = File::from_slice(&compress_file(&bytes, 2, &pixels)).unwrap();
+        assert_eq!(
+            read.get_bitmap_as_pixels(),
+            uncompressed.get_bitmap_as_pixels()
+        );
+        assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+    }
+
+    #[test]
+    fn write_run_length_encoded_files() {
+        let mut b = BitMap::new(64, 32);
+        for x in 0..32 {
+            for y in 0..16 {
+                b.set_pixel(x, y, Rgba::rgb(255, 0, 0)).unwrap();
+            }
+        }
+        b.set_pixel(40, 20, Rgba::black()).unwrap();
+        let options = SaveOptions::new().with_rle(true);
+        for (bit_depth, compression) in &[(BitDepth::Color16Bit, 2), (BitDepth::Color256Bit, 1)] {
+            let uncompressed = File::create(&b, *bit_depth).to_bytes();
+            let file = File::create_with_options(&b, *bit_depth, &options);
+            let bytes = file.to_bytes();
+            assert_eq!(bytes.len(), file.calculate_file_size() as usize);
+            assert!(bytes.len() < uncompressed.len() / 4);
+            assert_eq!(bytes[30] as u32, *compression);
+            let size_image = u32::from_le_bytes([bytes[34], bytes[35], bytes[36], bytes[37]]);
+            assert_eq!(size_image as usize, bytes.len() - bytes[10] as usize);
+            let read = File::from_slice(&bytes).unwrap();
+            assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+        }
+    }
+
+    #[test]
+    fn read_and_write_top_down_files() {
+        let mut b = BitMap::new(3, 4);
+        b.set_pixel(0, 0, Rgba::black()).unwrap();
+        b.set_pixel(2, 3, Rgba::rgb(255, 0, 0)).unwrap();
+        let options = SaveOptions::new().with_top_down(true);
+        for bit_depth in &[
+            BitDepth::Color2Bit,
+            BitDepth::Color16Bit,
+            BitDepth::Color256Bit,
+            BitDepth::HighColor,
+            BitDepth::AllColors,
+            BitDepth::AllColorsAndShades,
+        ] {
+            if *bit_depth == BitDepth::Color2Bit {
+                b.set_pixel(2, 3, Rgba::black()).unwrap();
+            }
+            let bottom_up = File::create(&b, *bit_depth).to_bytes();
+            let bytes = File::create_with_options(&b, *bit_depth, &options).to_bytes();
+            let height = i32::from_le_bytes([bytes[22], bytes[23], bytes[24], bytes[25]]);
+            assert_eq!(height, -4);
+            // the first row of a top-down image is the last row of a
+            // bottom-up image, color tables may be in a different order
+            if *bit_depth as u32 >= 16 {
+                let row_size = (bytes.len() - bytes[10] as usize) / 4;
+                assert_eq!(
+                    &bytes[bytes[10] as usize..][..row_size],
+                    &bottom_up[bottom_up.len() - row_size..]
+                );
+            }
+            let read = File::from_slice(&bytes).unwrap();
+            assert_eq!(read.get_height(), 4);
+            assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+        }
+    }
+
+    #[test]
+    fn run_length_encoded_files_are_never_top_down() {
+        let b = BitMap::new(3, 4);
+        let options = SaveOptions::new().with_top_down(true).with_rle(true);
+        let mut bytes = File::create_with_options(&b, BitDepth::Color256Bit, &options).to_bytes();
+        assert_eq!(&bytes[22..26], &4i32.to_le_bytes());
+        bytes[22..26].copy_from_slice(&(-4i32).to_le_bytes());
+        assert!(File::from_slice(&bytes).is_err());
+    }
+
+    #[test]
+    fn fail_to_read_negative_width() {
+        let mut bytes = File::create(&BitMap::new(3, 4), BitDepth::AllColors).to_bytes();
+        bytes[18..22].copy_from_slice(&(-3i32).to_le_bytes());
+        assert!(File::from_slice(&bytes).is_err());
+    }
+
+    #[test]
+    fn read_files_missing_the_last_rows_padding() {
+        let mut b = BitMap::new(3, 2);
+        b.set_pixel(2, 0, Rgba::black()).unwrap();
+        for bit_depth in [BitDepth::Color2Bit, BitDepth::AllColors].iter() {
+            let bytes = File::create(&b, *bit_depth).to_bytes();
+            let read = File::from_slice(&bytes[..bytes.len() - 3]).unwrap();
+            assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+        }
+        let bytes = File::create(&b, BitDepth::AllColors).to_bytes();
+        match File::from_slice(&bytes[..bytes.len() - 4]) {
+            Err(BitmapError::Truncated {
+                section,
+                offset,
+                needed,
+                available,
+            }) => {
+                assert_eq!(section, "pixel data");
+                assert_eq!(offset, 54);
+                assert_eq!(needed, 21);
+                assert_eq!(available, 20);
+            }
+            _ => panic!("expected truncated pixel data"),
+        }
+    }
+
+    #[test]
+    fn fail_to_read_colors_outside_of_the_color_table() {
+        let b = BitMap::new(2, 2);
+        let mut bytes = File::create(&b, BitDepth::Color256Bit).to_bytes();
+        let off_bits = bytes.len() - 8;
+        bytes[off_bits] = 5;
+        assert!(matches!(
+            File::from_slice(&bytes),
+            Err(BitmapError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn fail_to_read_images_with_too_many_pixels() {
+        let mut bytes = File::create(&BitMap::new(3, 4), BitDepth::AllColors).to_bytes();
+        bytes[18..22].copy_from_slice(&0x10000i32.to_le_bytes());
+        bytes[22..26].copy_from_slice(&0x10001i32.to_le_bytes());
+        assert!(matches!(
+            File::from_slice(&bytes),
+            Err(BitmapError::InvalidData(_))
+        ));
+    }
+
+    ///
+    /// Rebuild a file that uses a BITMAPINFOHEADER using one of the OS/2
+    /// headers. The color table is filled up to the size the bit depth allows
+    ///
+    fn to_os2_file(bytes: &[u8], version: HeaderVersion) -> Vec<u8> {
+        let bit_depth = u16::from_le_bytes([bytes[28], bytes[29]]);
+        let colors_used = u32::from_le_bytes([bytes[46], bytes[47], bytes[48], bytes[49]]);
+        let off_bits = bytes[10] as usize;
+        let mut header = match version {
+            HeaderVersion::Core => {
+                let mut header = vec![12, 0, 0, 0];
+                header.extend_from_slice(&bytes[18..20]);
+                header.extend_from_slice(&bytes[22..24]);
+                header.extend_from_slice(&bytes[26..30]);
+                header
+            }
+            HeaderVersion::Os2Short => {
+                let mut header = vec![16, 0, 0, 0];
+                header.extend_from_slice(&bytes[18..30]);
+                header
+            }
+            _ => {
+                let mut header = bytes[14..54].to_vec();
+                header[0] = 64;
+                header[32..36].copy_from_slice(&[0, 0, 0, 0]);
+                header.resize(64, 0);
+                header
+            }
+        };
+        let mut colors = Vec::new();
+        if bit_depth <= 8 {
+            let entry_size = if version == HeaderVersion::Core { 3 } else { 4 };
+            for color in bytes[54..54 + colors_used as usize * 4].chunks(4) {
+                colors.extend_from_slice(&color[..entry_size]);
+            }
+            colors.resize(entry_size << bit_depth, 0);
+        }
+        let mut os2 = bytes[..14].to_vec();
+        os2.append(&mut header);
+        os2.append(&mut colors);
+        let new_off_bits = os2.len() as u32;
+        os2.extend_from_slice(&bytes[off_bits..]);
+        let file_size = os2.len() as u32;
+        os2[2..6].copy_from_slice(&file_size.to_le_bytes());
+        os2[10..14].copy_from_slice(&new_off_bits.to_le_bytes());
+        os2
+    }
+
+    #[test]
+    fn read_os2_files() {
+        let mut b = BitMap::new(5, 3);
+        b.set_pixel(0, 0, Rgba::black()).unwrap();
+        b.set_pixel(4, 2, Rgba::rgb(255, 0, 0)).unwrap();
+        for version in &[
+            HeaderVersion::Core,
+            HeaderVersion::Os2Short,
+            HeaderVersion::Os2,
+        ] {
+            for bit_depth in &[
+                BitDepth::Color16Bit,
+                BitDepth::Color256Bit,
+                BitDepth::AllColors,
+            ] {
+                let bytes = File::create(&b, *bit_depth).to_bytes();
+                let os2 = to_os2_file(&bytes, *version);
+                let read = File::from_slice(&os2).unwrap();
+                assert_eq!(read.get_width(), 5);
+                assert_eq!(read.get_height(), 3);
+                assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+                assert_eq!(read.to_bytes(), os2);
+            }
+        }
+    }
+
+    #[test]
+    fn read_file_from_reader() {
+        let mut b = BitMap::new(3, 2);
+        b.set_pixel(1, 1, Rgba::black()).unwrap();
+        let bytes = File::create(&b, BitDepth::AllColors).to_bytes();
+        let limits = DecodeLimits::default();
+        let read = File::from_reader(std::io::Cursor::new(&bytes), &limits).unwrap();
+        assert_eq!(&read.get_bitmap_as_pixels(), b.get_pixels());
+        assert!(File::from_reader(&bytes[..10], &limits).is_err());
+    }
+
+    #[test]
+    fn report_where_data_is_missing() {
+        let b = BitMap::new(2, 2);
+        let bytes = File::create(&b, BitDepth::Color256Bit).to_bytes();
+        match File::from_slice(&bytes[..20]) {
+            Err(BitmapError::Truncated {
+                section,
+                offset,
+                needed,
+                available,
+            }) => {
+                assert_eq!(section, "info header");
+                assert_eq!(offset, 14);
+                assert_eq!(needed, 40);
+                assert_eq!(available, 6);
+            }
+            _ => panic!("expected truncated info header"),
+        }
+        match File::from_slice(&bytes[..56]) {
+            Err(BitmapError::Truncated {
+                section, offset, ..
+            }) => {
+                assert_eq!(section, "color table");
+                assert_eq!(offset, 54);
+            }
+            _ => panic!("expected truncated color table"),
+        }
+        let mut not_a_bitmap = bytes.clone();
+        not_a_bitmap[0] = b'X';
+        assert!(matches!(
+            File::from_slice(&not_a_bitmap),
+            Err(BitmapError::BadMagic([b'X', b'M']))
+        ));
+    }
 }
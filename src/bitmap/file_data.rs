@@ -1,5 +1,8 @@
 use super::bit_data::BitData;
 use super::bit_depth::BitDepth;
+use super::compression::Compression;
+use super::decode_warning::Recovery;
+use super::error::BitmapError;
 use super::image::BitMap;
 use super::info_header::InfoHeader;
 use super::pixel_data::PixelData;
@@ -15,32 +18,110 @@ impl FileData {
     ///
     /// Read in bytes from a from_slice and convert it into image data (pixels)
     ///
-    pub fn from_slice(bit_stream: &[u8], info: &InfoHeader, colors: &RgbQuad) -> Option<FileData> {
-        match info.get_bit_depth() {
-            Some(b) => match b {
-                BitDepth::Color2Bit | BitDepth::Color16Bit | BitDepth::Color256Bit => Some(
-                    FileData::Bits(BitData::from_slice(bit_stream, info, b, colors)),
-                ),
-                BitDepth::AllColors | BitDepth::AllColorsAndShades => {
-                    Some(FileData::Pixels(PixelData::from_slice(bit_stream, info, b)))
+    /// Fails if the bit depth or compression isn't supported, or if they don't
+    /// work together. If there's a recovery, missing pixels are repaired
+    /// instead of failing
+    ///
+    pub fn from_slice(
+        bit_stream: &[u8],
+        info: &InfoHeader,
+        colors: &RgbQuad,
+        recovery: Option<&mut Recovery>,
+    ) -> Result<FileData, BitmapError> {
+        let bit_depth = info
+            .get_bit_depth()
+            .ok_or_else(|| BitmapError::UnsupportedBitDepth(info.get_bits_per_pixel()))?;
+        let compression = info
+            .get_compression()
+            .ok_or_else(|| BitmapError::UnsupportedCompression(info.get_compression_value()))?;
+        match (compression, bit_depth) {
+            (
+                Compression::Rgb,
+                BitDepth::Color2Bit | BitDepth::Color16Bit | BitDepth::Color256Bit,
+            ) => Ok(FileData::Bits(BitData::from_slice(
+                bit_stream, info, bit_depth, colors, recovery,
+            )?)),
+            (Compression::Rle8, BitDepth::Color256Bit)
+            | (Compression::Rle4, BitDepth::Color16Bit) => Ok(FileData::Bits(
+                BitData::from_rle_slice(bit_stream, info, bit_depth, colors, recovery)?,
+            )),
+            (Compression::Rgb, _) => Ok(FileData::Pixels(PixelData::from_slice(
+                bit_stream, info, bit_depth, recovery,
+            )?)),
+            (Compression::BitFields, BitDepth::HighColor)
+            | (Compression::BitFields, BitDepth::AllColorsAndShades)
+            | (Compression::AlphaBitFields, BitDepth::HighColor)
+            | (Compression::AlphaBitFields, BitDepth::AllColorsAndShades) => {
+                match info.get_bit_fields() {
+                    Some(fields) if fields.is_valid() => Ok(FileData::Pixels(
+                        PixelData::from_slice(bit_stream, info, bit_depth, recovery)?,
+                    )),
+                    _ => Err(BitmapError::InvalidData(
+                        "bit field masks must be a single run of bits",
+                    )),
                 }
-            },
-            None => None,
+            }
+            (Compression::Rle8, _) | (Compression::Rle4, _) => Err(BitmapError::InvalidData(
+                "run length encoding doesn't match the bit depth",
+            )),
+            (Compression::BitFields, _) | (Compression::AlphaBitFields, _) => Err(
+                BitmapError::InvalidData("bit fields can only be used by 16 and 32 bit images"),
+            ),
+            _ => Err(BitmapError::UnsupportedCompression(
+                info.get_compression_value(),
+            )),
         }
     }
 
     ///
-    /// Convert image data (pixels) from a bitmap into bits and bytes
+    /// Convert image data (pixels) from a bitmap into bits and bytes, using
+    /// the bit depth, compression and masks described by the info header
     ///
-    pub fn from_bitmap(bitmap: &BitMap, bit_depth: BitDepth) -> FileData {
+    pub fn from_bitmap(
+        bitmap: &BitMap,
+        bit_depth: BitDepth,
+        info: &InfoHeader,
+        colors: &RgbQuad,
+    ) -> FileData {
         match bit_depth {
             BitDepth::Color2Bit | BitDepth::Color16Bit | BitDepth::Color256Bit => {
-                FileData::Bits(BitData::from_bitmap(bitmap, bit_depth))
+                let compression = info.get_compression().unwrap_or(Compression::Rgb);
+                FileData::Bits(BitData::from_bitmap(
+                    bitmap,
+                    bit_depth,
+                    colors.get_colors(),
+                    compression,
+                ))
             }
-            _ => FileData::Pixels(PixelData::from_bitmap(bitmap, bit_depth)),
+            _ => FileData::Pixels(PixelData::from_bitmap(
+                bitmap,
+                bit_depth,
+                info.get_bit_fields(),
+            )),
         }
     }
 
+    ///
+    /// Convert rows of indices that point into the colors into bits and
+    /// bytes, using the bit depth and compression described by the info
+    /// header. The rows must be in the same order they are stored in
+    ///
+    pub fn from_indices(
+        indices: &[u8],
+        bit_depth: BitDepth,
+        info: &InfoHeader,
+        colors: &RgbQuad,
+    ) -> FileData {
+        let compression = info.get_compression().unwrap_or(Compression::Rgb);
+        FileData::Bits(BitData::from_indices(
+            indices,
+            info.get_width(),
+            bit_depth,
+            colors.clone_colors(),
+            compression,
+        ))
+    }
+
     ///
     /// Get then length of the lists of colors
     ///
@@ -80,6 +161,17 @@ impl FileData {
             FileData::Pixels(p) => p.as_rgba(),
         }
     }
+
+    ///
+    /// get the index of the color of each pixel, if every pixel points into
+    /// the color table
+    ///
+    pub fn as_indices(&self) -> Option<Vec<u8>> {
+        match self {
+            FileData::Bits(b) => b.as_indices(),
+            FileData::Pixels(_) => None,
+        }
+    }
 }
 
 #[cfg(debug_assertions)]
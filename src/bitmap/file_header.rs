@@ -1,3 +1,4 @@
+use super::error::BitmapError;
 use super::util;
 
 pub struct FileHeader {
@@ -15,10 +16,6 @@ pub struct FileHeader {
 /// Used for constants
 ///
 impl FileHeader {
-    pub const fn from_slice_range() -> std::ops::Range<usize> {
-        0..14
-    }
-
     pub const fn estimated_byte_size() -> usize {
         14
     }
@@ -53,9 +50,17 @@ impl FileHeader {
     /// @param {&[u8; 14]} 14 byte long slice
     /// @return {FileHeader}
     ///
-    pub fn from_slice(bit_stream: &[u8]) -> Result<FileHeader, &'static str> {
+    pub fn from_slice(bit_stream: &[u8]) -> Result<FileHeader, BitmapError> {
         if bit_stream.len() < 14 {
-            return Err("Error reading file header, not enough data found!");
+            return Err(BitmapError::Truncated {
+                section: "file header",
+                offset: 0,
+                needed: 14,
+                available: bit_stream.len(),
+            });
+        }
+        if bit_stream[0..2] != *b"BM" {
+            return Err(BitmapError::BadMagic([bit_stream[0], bit_stream[1]]));
         }
         let bitmap_type = [bit_stream[0] as char, bit_stream[1] as char];
         let mut i = 2;
@@ -85,6 +90,10 @@ impl FileHeader {
         14
     }
 
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
     pub fn get_off_bits(&self) -> u32 {
         self.off_bits
     }
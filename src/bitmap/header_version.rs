@@ -0,0 +1,76 @@
+///
+/// The different versions of the info header that can be found inside of a
+/// bitmap. Each version is identified by the size of the header in bytes.
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HeaderVersion {
+    /// BITMAPCOREHEADER, used by OS/2 1.x. The width and height are stored as
+    /// 16 bit numbers and each color in the color table takes 3 bytes
+    Core = 12,
+    /// OS/2 2.x BITMAPINFOHEADER2 that only stores the width, height, planes
+    /// and bit depth
+    Os2Short = 16,
+    /// BITMAPINFOHEADER
+    Info = 40,
+    /// OS/2 2.x BITMAPINFOHEADER2
+    Os2 = 64,
+    /// BITMAPV4HEADER
+    V4 = 108,
+    /// BITMAPV5HEADER
+    V5 = 124,
+}
+
+impl HeaderVersion {
+    ///
+    /// Get the header version that has the given size in bytes
+    ///
+    pub fn from_byte_size(size: u32) -> Option<HeaderVersion> {
+        match size {
+            12 => Some(HeaderVersion::Core),
+            16 => Some(HeaderVersion::Os2Short),
+            40 => Some(HeaderVersion::Info),
+            64 => Some(HeaderVersion::Os2),
+            108 => Some(HeaderVersion::V4),
+            124 => Some(HeaderVersion::V5),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Check if the header is only used by old OS/2 bitmaps. These headers
+    /// can be read, but are never written.
+    ///
+    pub fn is_os2(&self) -> bool {
+        matches!(
+            self,
+            HeaderVersion::Core | HeaderVersion::Os2Short | HeaderVersion::Os2
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HeaderVersion;
+
+    #[test]
+    fn get_header_version_from_byte_size() {
+        assert_eq!(HeaderVersion::from_byte_size(40), Some(HeaderVersion::Info));
+        assert_eq!(HeaderVersion::from_byte_size(108), Some(HeaderVersion::V4));
+        assert_eq!(HeaderVersion::from_byte_size(124), Some(HeaderVersion::V5));
+        assert_eq!(HeaderVersion::from_byte_size(12), Some(HeaderVersion::Core));
+        assert_eq!(
+            HeaderVersion::from_byte_size(16),
+            Some(HeaderVersion::Os2Short)
+        );
+        assert_eq!(HeaderVersion::from_byte_size(64), Some(HeaderVersion::Os2));
+        assert_eq!(HeaderVersion::from_byte_size(41), None);
+    }
+
+    #[test]
+    fn check_if_header_version_is_os2() {
+        assert!(HeaderVersion::Core.is_os2());
+        assert!(HeaderVersion::Os2.is_os2());
+        assert!(!HeaderVersion::Info.is_os2());
+        assert!(!HeaderVersion::V5.is_os2());
+    }
+}
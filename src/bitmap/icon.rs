@@ -0,0 +1,785 @@
+use std::io::Read;
+use std::io::Write;
+use std::mem::size_of;
+
+use super::bit_depth::BitDepth;
+use super::decode_limits::DecodeLimits;
+use super::error::BitmapError;
+use super::file_data::FileData;
+use super::image::BitMap;
+use super::info_header::InfoHeader;
+use super::rgb_quad::RgbQuad;
+use super::rgba::Rgba;
+use super::save_options::SaveOptions;
+use super::util;
+
+///
+/// The bytes every png file starts with
+///
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+///
+/// Number of bytes used by the header at the start of an icon or cursor
+///
+const DIRECTORY_BYTE_SIZE: usize = 6;
+
+///
+/// Number of bytes used to describe each image of an icon or cursor
+///
+const ENTRY_BYTE_SIZE: usize = 16;
+
+///
+/// The kind of file the images are stored in
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IconKind {
+    /// A Windows icon, a .ico file
+    Icon = 1,
+    /// A Windows cursor, a .cur file, where every image has a hotspot
+    Cursor = 2,
+}
+
+///
+/// The pixels of one of the images, which are either stored as a bitmap or
+/// as a png file
+///
+#[derive(Clone, PartialEq)]
+enum IconData {
+    Bitmap(BitMap),
+    Png(Vec<u8>),
+}
+
+///
+/// One of the images stored in an icon or cursor, usually each image has a
+/// different size
+///
+#[derive(Clone, PartialEq)]
+pub struct IconImage {
+    /// pixels of the image
+    data: IconData,
+    /// width of the image, in pixels
+    width: u32,
+    /// height of the image, in pixels
+    height: u32,
+    /// point of a cursor that clicks, measured from the top left of the image
+    hotspot: (u16, u16),
+}
+
+impl IconImage {
+    ///
+    /// Create an image from a bitmap. Fully transparent pixels are stored
+    /// using the mask of the image, so they are kept by every program that
+    /// can show icons
+    ///
+    /// Fails if the bitmap is empty or is wider or higher then 256 pixels
+    ///
+    pub fn from_bitmap(bitmap: BitMap) -> Result<IconImage, BitmapError> {
+        let (width, height) = (bitmap.get_width(), bitmap.get_height());
+        IconImage::check_size(width, height)?;
+        Ok(IconImage {
+            data: IconData::Bitmap(bitmap),
+            width,
+            height,
+            hotspot: (0, 0),
+        })
+    }
+
+    ///
+    /// Create an image from the bytes of a png file, which are stored as they
+    /// are. Large icons are often stored this way to save space
+    ///
+    /// Fails if the bytes aren't a png file, or if the image is empty or is
+    /// wider or higher then 256 pixels
+    ///
+    pub fn from_png(bytes: Vec<u8>) -> Result<IconImage, BitmapError> {
+        let (width, height) = get_png_size(&bytes)
+            .ok_or(BitmapError::InvalidArgument("Bytes must be a png file."))?;
+        IconImage::check_size(width, height)?;
+        Ok(IconImage {
+            data: IconData::Png(bytes),
+            width,
+            height,
+            hotspot: (0, 0),
+        })
+    }
+
+    ///
+    /// Set the point of the cursor that clicks, measured from the top left of
+    /// the image. The hotspot is only saved in cursors
+    ///
+    pub fn with_hotspot(mut self, x: u16, y: u16) -> IconImage {
+        self.hotspot = (x, y);
+        self
+    }
+
+    ///
+    /// Check that an image can be described by the directory of an icon
+    ///
+    fn check_size(width: u32, height: u32) -> Result<(), BitmapError> {
+        if width == 0 || height == 0 || width > 256 || height > 256 {
+            return Err(BitmapError::InvalidArgument(
+                "Icon images must be between 1 and 256 pixels wide and high.",
+            ));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Get the width of the image
+    ///
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    ///
+    /// Get the height of the image
+    ///
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    ///
+    /// Get the point of the cursor that clicks, measured from the top left of
+    /// the image. Images read from icons always have a hotspot of zero
+    ///
+    pub fn get_hotspot(&self) -> (u16, u16) {
+        self.hotspot
+    }
+
+    ///
+    /// Get the pixels of the image, if it's stored as a bitmap
+    ///
+    pub fn get_bitmap(&self) -> Option<&BitMap> {
+        match &self.data {
+            IconData::Bitmap(bitmap) => Some(bitmap),
+            IconData::Png(_) => None,
+        }
+    }
+
+    ///
+    /// Get the bytes of the png file, if the image is stored as one
+    ///
+    pub fn get_png(&self) -> Option<&[u8]> {
+        match &self.data {
+            IconData::Bitmap(_) => None,
+            IconData::Png(bytes) => Some(bytes),
+        }
+    }
+
+    ///
+    /// Check if the image is stored as a png file
+    ///
+    pub fn is_png(&self) -> bool {
+        self.get_png().is_some()
+    }
+}
+
+///
+/// A Windows icon or cursor, which holds one or more images that are
+/// usually the same picture at different sizes
+///
+#[derive(Clone, PartialEq)]
+pub struct Icon {
+    /// if the images are stored in an icon or a cursor
+    kind: IconKind,
+    /// images in the order they are stored
+    images: Vec<IconImage>,
+}
+
+///
+/// This block deals with constructors, and getters and setters
+///
+impl Icon {
+    ///
+    /// Create an icon or cursor without any images
+    ///
+    pub fn new(kind: IconKind) -> Icon {
+        Icon {
+            kind,
+            images: Vec::new(),
+        }
+    }
+
+    ///
+    /// Create an icon that holds each of the bitmaps, usually the same
+    /// picture at different sizes
+    ///
+    /// Fails if any of the bitmaps is empty or is wider or higher then 256
+    /// pixels
+    ///
+    pub fn from_bitmaps(bitmaps: &[BitMap]) -> Result<Icon, BitmapError> {
+        let mut icon = Icon::new(IconKind::Icon);
+        for bitmap in bitmaps {
+            icon.add_image(IconImage::from_bitmap(bitmap.clone())?);
+        }
+        Ok(icon)
+    }
+
+    ///
+    /// Read in every image of an .ico or .cur file using the default decode
+    /// limits
+    ///
+    /// Fails if filename doesn't end with ".ico" or ".cur"
+    ///
+    pub fn read(filename: &str) -> Result<Icon, BitmapError> {
+        if !filename.ends_with(".ico") && !filename.ends_with(".cur") {
+            return Err(BitmapError::InvalidArgument(
+                "File must end with '.ico' or '.cur'",
+            ));
+        }
+        let bytes = std::fs::read(filename)?;
+        Icon::from_bytes(&bytes)
+    }
+
+    ///
+    /// Decode every image of an icon or cursor that is already in memory,
+    /// using the default decode limits
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<Icon, BitmapError> {
+        Icon::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    ///
+    /// Decode every image of an icon or cursor that is already in memory.
+    /// Each bitmap is checked against the limits before its pixels are read,
+    /// along with every image that was read before it. Png images aren't
+    /// decoded, so only the bytes they're copied into are counted
+    ///
+    pub fn from_bytes_with_limits(
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<Icon, BitmapError> {
+        if bytes.len() < DIRECTORY_BYTE_SIZE {
+            return Err(BitmapError::Truncated {
+                section: "icon directory",
+                offset: 0,
+                needed: DIRECTORY_BYTE_SIZE,
+                available: bytes.len(),
+            });
+        }
+        let mut i = 0;
+        let reserved = util::byte_slice_to_u16(bytes, &mut i);
+        let kind = match (reserved, util::byte_slice_to_u16(bytes, &mut i)) {
+            (0, 1) => IconKind::Icon,
+            (0, 2) => IconKind::Cursor,
+            _ => return Err(BitmapError::InvalidData("data isn't an icon or cursor")),
+        };
+        let count = util::byte_slice_to_u16(bytes, &mut i) as usize;
+        let needed = DIRECTORY_BYTE_SIZE + count * ENTRY_BYTE_SIZE;
+        if bytes.len() < needed {
+            return Err(BitmapError::Truncated {
+                section: "icon directory",
+                offset: 0,
+                needed,
+                available: bytes.len(),
+            });
+        }
+
+        let mut icon = Icon::new(kind);
+        let mut kept_pixels = 0u64;
+        let mut kept_bytes = 0u64;
+        for entry in 0..count {
+            // the width, height, color count and reserved byte are skipped
+            // since the image describes itself
+            let mut i = DIRECTORY_BYTE_SIZE + entry * ENTRY_BYTE_SIZE + 4;
+            let hotspot = (
+                util::byte_slice_to_u16(bytes, &mut i),
+                util::byte_slice_to_u16(bytes, &mut i),
+            );
+            let size = util::byte_slice_to_u32(bytes, &mut i) as usize;
+            let offset = util::byte_slice_to_u32(bytes, &mut i) as usize;
+            let image_bytes = match bytes.get(offset..offset.saturating_add(size)) {
+                Some(image_bytes) => image_bytes,
+                None => {
+                    return Err(BitmapError::Truncated {
+                        section: "icon image",
+                        offset,
+                        needed: size,
+                        available: bytes.len().saturating_sub(offset),
+                    })
+                }
+            };
+            let (data, width, height) = match get_png_size(image_bytes) {
+                Some((width, height)) => {
+                    kept_bytes = kept_bytes.saturating_add(image_bytes.len() as u64);
+                    limits.check_with_kept(0, 0, kept_pixels, kept_bytes)?;
+                    (IconData::Png(image_bytes.to_vec()), width, height)
+                }
+                None => {
+                    let bitmap = decode_bitmap(image_bytes, limits, kept_pixels, kept_bytes)
+                        .map_err(|why| why.offset_by(offset))?;
+                    let (width, height) = (bitmap.get_width(), bitmap.get_height());
+                    let pixels = width as u64 * height as u64;
+                    kept_pixels = kept_pixels.saturating_add(pixels);
+                    kept_bytes = kept_bytes.saturating_add(pixels * size_of::<Rgba>() as u64);
+                    (IconData::Bitmap(bitmap), width, height)
+                }
+            };
+            icon.add_image(IconImage {
+                data,
+                width,
+                height,
+                // icons store the color planes and bits per pixel instead
+                hotspot: match kind {
+                    IconKind::Icon => (0, 0),
+                    IconKind::Cursor => hotspot,
+                },
+            });
+        }
+        Ok(icon)
+    }
+
+    ///
+    /// Read every image of an icon or cursor from any reader, using the
+    /// default decode limits
+    ///
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Icon, BitmapError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Icon::from_bytes(&bytes)
+    }
+
+    ///
+    /// Get if the images are stored in an icon or a cursor
+    ///
+    pub fn get_kind(&self) -> IconKind {
+        self.kind
+    }
+
+    ///
+    /// Get the images in the order they are stored
+    ///
+    pub fn get_images(&self) -> &[IconImage] {
+        &self.images
+    }
+
+    ///
+    /// Add an image after the images that are already stored
+    ///
+    pub fn add_image(&mut self, image: IconImage) {
+        self.images.push(image);
+    }
+}
+
+///
+/// This block deals with saving icons and cursors
+///
+impl Icon {
+    ///
+    /// Save every image to a location on disk. Bitmaps with partly
+    /// transparent pixels are saved as 32 bit images, the rest are saved
+    /// using the smallest bit depth that fits their colors
+    ///
+    /// Fails if there are no images, or more images then a directory can
+    /// count
+    ///
+    pub fn save_as(&self, filename: &str) -> Result<(), BitmapError> {
+        let bit_stream = self.to_bytes()?;
+        let mut file = std::fs::File::create(filename)?;
+        file.write_all(&bit_stream)?;
+        Ok(())
+    }
+
+    ///
+    /// Encode every image and write them to any writer, see [Icon::save_as]
+    /// for how each image is stored
+    ///
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), BitmapError> {
+        let bit_stream = self.to_bytes()?;
+        writer.write_all(&bit_stream)?;
+        Ok(())
+    }
+
+    ///
+    /// Encode every image into the bytes of an icon or cursor, see
+    /// [Icon::save_as] for how each image is stored
+    ///
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BitmapError> {
+        if self.images.is_empty() || self.images.len() > u16::MAX as usize {
+            return Err(BitmapError::InvalidArgument(
+                "Icons must have between 1 and 65535 images.",
+            ));
+        }
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&(self.kind as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.images.len() as u16).to_le_bytes());
+
+        let mut images = Vec::new();
+        let mut offset = DIRECTORY_BYTE_SIZE + self.images.len() * ENTRY_BYTE_SIZE;
+        for image in &self.images {
+            let (mut image_bytes, color_count, bits_per_pixel) = match &image.data {
+                IconData::Bitmap(bitmap) => encode_bitmap(bitmap),
+                IconData::Png(png) => (png.clone(), 0, 32),
+            };
+            let (planes_or_x, bits_or_y) = match self.kind {
+                IconKind::Icon => (1, bits_per_pixel),
+                IconKind::Cursor => image.hotspot,
+            };
+            // a size of 256 doesn't fit in a byte, so it's stored as zero
+            bytes.push(image.width as u8);
+            bytes.push(image.height as u8);
+            bytes.push(color_count);
+            bytes.push(0);
+            bytes.extend_from_slice(&planes_or_x.to_le_bytes());
+            bytes.extend_from_slice(&bits_or_y.to_le_bytes());
+            bytes.extend_from_slice(&(image_bytes.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += image_bytes.len();
+            images.append(&mut image_bytes);
+        }
+        bytes.append(&mut images);
+        Ok(bytes)
+    }
+}
+
+///
+/// Get the width and height of a png file from its header, if the bytes
+/// are a png file
+///
+fn get_png_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.len() < 24 || !bytes.starts_with(&PNG_SIGNATURE) || &bytes[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+    let height = u32::from_be_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
+    Some((width, height))
+}
+
+///
+/// Get the number of bytes used to store each row of the mask, which uses a
+/// bit for every pixel and is padded to a multiple of 4 bytes
+///
+fn get_mask_row_byte_size(width: u32) -> usize {
+    (width as usize + 31) / 32 * 4
+}
+
+///
+/// Read in a bitmap stored in an icon or cursor, which has no file header
+/// and is followed by a mask where every set bit marks a transparent pixel.
+/// The height in the info header counts the rows of the image and the mask
+///
+/// 32 bit images use their own alpha instead of the mask, unless every pixel
+/// has an alpha of zero which is how older programs write them. The pixels
+/// and bytes of the images that were already read count towards the limits
+///
+fn decode_bitmap(
+    bytes: &[u8],
+    limits: &DecodeLimits,
+    kept_pixels: u64,
+    kept_bytes: u64,
+) -> Result<BitMap, BitmapError> {
+    let stored = InfoHeader::from_slice(bytes)?;
+    let info = stored.for_icon_pixels();
+    let (width, height) = (info.get_width(), info.get_height());
+    limits.check_with_kept(width, height, kept_pixels, kept_bytes)?;
+    if width == 0 || height == 0 {
+        return Err(BitmapError::InvalidData("icon image has no pixels"));
+    }
+    if info.is_run_length_encoded() {
+        return Err(BitmapError::InvalidData(
+            "icon images can't be run length encoded",
+        ));
+    }
+
+    // colors
+    let offset = stored.get_byte_size() as usize;
+    let entry_size = stored.get_color_table_entry_size();
+    let color_byte_size = stored.get_color_table_size() as usize * entry_size;
+    let color_end = offset.saturating_add(color_byte_size);
+    let color_bytes = match bytes.get(offset..color_end) {
+        Some(color_bytes) => color_bytes,
+        None => {
+            return Err(BitmapError::Truncated {
+                section: "color table",
+                offset,
+                needed: color_byte_size,
+                available: bytes.len().saturating_sub(offset),
+            })
+        }
+    };
+    let colors =
+        RgbQuad::from_slice(color_bytes, entry_size).map_err(|why| why.offset_by(offset))?;
+
+    // pixels, followed by the mask
+    let data = FileData::from_slice(&bytes[color_end..], &info, &colors, None)
+        .map_err(|why| why.offset_by(color_end))?;
+    let mask_start = color_end + info.get_row_byte_size() * height as usize;
+    let mask_row_size = get_mask_row_byte_size(width);
+    let mask_size = mask_row_size * height as usize;
+    let mask = match bytes.get(mask_start..mask_start.saturating_add(mask_size)) {
+        Some(mask) => mask,
+        None => {
+            return Err(BitmapError::Truncated {
+                section: "icon mask",
+                offset: mask_start,
+                needed: mask_size,
+                available: bytes.len().saturating_sub(mask_start),
+            })
+        }
+    };
+
+    let mut pixels = data.as_rgba();
+    let has_alpha =
+        info.get_bits_per_pixel() == 32 && pixels.iter().any(|pixel| pixel.get_alpha() > 0);
+    if !has_alpha {
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let x = i % width as usize;
+            let row = i / width as usize;
+            let is_transparent = mask[row * mask_row_size + x / 8] & (0x80 >> (x % 8)) != 0;
+            let alpha = match is_transparent {
+                true => 0,
+                false => 100,
+            };
+            *pixel = Rgba::rgba(pixel.get_red(), pixel.get_green(), pixel.get_blue(), alpha);
+        }
+    }
+    // the rows are stored from the bottom of the image to the top
+    let rows = pixels.chunks(width as usize).rev();
+    BitMap::create(width, height, rows.flatten().copied().collect())
+}
+
+///
+/// Encode a bitmap the way it's stored in an icon or cursor, along with the
+/// number of colors in its color table and the number of bits per pixel.
+///
+/// Bitmaps with partly transparent pixels are stored as 32 bit images. The
+/// rest are stored using the smallest bit depth that fits their colors, with
+/// the transparent pixels drawn black and left out by the mask
+///
+fn encode_bitmap(bitmap: &BitMap) -> (Vec<u8>, u8, u16) {
+    let width = bitmap.get_width();
+    let height = bitmap.get_height();
+    let partly_transparent = bitmap
+        .get_pixels()
+        .iter()
+        .any(|pixel| pixel.get_alpha() > 0 && pixel.get_alpha() < 100);
+    let (image, bit_depth) = match partly_transparent {
+        true => (bitmap.clone(), BitDepth::AllColorsAndShades),
+        false => {
+            let rows = bitmap.get_pixels().chunks(width as usize).rev();
+            let pixels = rows
+                .flatten()
+                .map(|pixel| match pixel.get_alpha() {
+                    0 => Rgba::black(),
+                    _ => *pixel,
+                })
+                .collect();
+            let opaque = BitMap::create(width, height, pixels).unwrap();
+            let bit_depth = BitDepth::get_suggested_bit_depth(&opaque);
+            (opaque, bit_depth)
+        }
+    };
+
+    let options = SaveOptions::default();
+    let info = InfoHeader::from(&image, bit_depth, &options).for_rows(height * 2);
+    let colors = RgbQuad::from(&image, bit_depth);
+    let data = FileData::from_bitmap(&image, bit_depth, &info.for_icon_pixels(), &colors);
+    let mut bytes = info.as_bytes();
+    bytes.append(&mut colors.as_bytes());
+    bytes.append(&mut data.as_bytes());
+
+    let mask_row_size = get_mask_row_byte_size(width);
+    for row in bitmap.get_pixels().chunks(width as usize) {
+        let mut mask = vec![0u8; mask_row_size];
+        for (x, pixel) in row.iter().enumerate() {
+            if pixel.get_alpha() == 0 {
+                mask[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        bytes.append(&mut mask);
+    }
+    // a color table with 256 colors doesn't fit in a byte, so it's stored
+    // as zero
+    let color_count = match colors.len() {
+        0..=255 => colors.len() as u8,
+        _ => 0,
+    };
+    (bytes, color_count, bit_depth as u16)
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitMap;
+    use super::BitmapError;
+    use super::DecodeLimits;
+    use super::Icon;
+    use super::IconImage;
+    use super::IconKind;
+    use super::Rgba;
+    use super::PNG_SIGNATURE;
+
+    fn create_png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        bytes.extend_from_slice(&13u32.to_be_bytes());
+        bytes.extend_from_slice(b"IHDR");
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&[8, 6, 0, 0, 0]);
+        bytes
+    }
+
+    fn create_checkers(size: u32, colors: &[Rgba]) -> BitMap {
+        let mut bitmap = BitMap::new(size, size);
+        for y in 0..size {
+            for x in 0..size {
+                let color = colors[((x + y) % colors.len() as u32) as usize];
+                bitmap.set_pixel(x, y, color).unwrap();
+            }
+        }
+        bitmap
+    }
+
+    #[test]
+    fn save_and_read_icons_with_many_sizes() {
+        let clear = Rgba::rgba(0, 0, 0, 0);
+        let two_colors = create_checkers(16, &[Rgba::rgb(200, 10, 10), clear]);
+        let many_colors = BitMap::create(
+            20,
+            1,
+            (0..20)
+                .map(|i| Rgba::rgb(i * 10, 255 - i * 10, i))
+                .collect(),
+        )
+        .unwrap();
+        let mut shaded = create_checkers(33, &[Rgba::rgba(10, 20, 30, 50), Rgba::white()]);
+        shaded.set_pixel(0, 0, clear).unwrap();
+        let bitmaps = [two_colors, many_colors, shaded];
+
+        let bytes = Icon::from_bitmaps(&bitmaps).unwrap().to_bytes().unwrap();
+        assert_eq!(&bytes[..6], &[0, 0, 1, 0, 3, 0]);
+        // bits per pixel of each entry
+        assert_eq!(bytes[6 + 6], 1);
+        assert_eq!(bytes[22 + 6], 8);
+        assert_eq!(bytes[38 + 6], 32);
+        let icon = Icon::from_bytes(&bytes).unwrap();
+        assert_eq!(icon.get_kind(), IconKind::Icon);
+        assert_eq!(icon.get_images().len(), 3);
+        for (image, bitmap) in icon.get_images().iter().zip(bitmaps.iter()) {
+            assert_eq!(image.get_width(), bitmap.get_width());
+            assert_eq!(image.get_height(), bitmap.get_height());
+            assert!(image.get_bitmap().unwrap() == bitmap);
+            assert!(!image.is_png());
+        }
+    }
+
+    #[test]
+    fn keep_cursor_hotspots() {
+        let bitmap = create_checkers(32, &[Rgba::black(), Rgba::white()]);
+        let mut cursor = Icon::new(IconKind::Cursor);
+        let image = IconImage::from_bitmap(bitmap).unwrap().with_hotspot(5, 9);
+        cursor.add_image(image);
+        let read = Icon::from_bytes(&cursor.to_bytes().unwrap()).unwrap();
+        assert_eq!(read.get_kind(), IconKind::Cursor);
+        assert_eq!(read.get_images()[0].get_hotspot(), (5, 9));
+        assert!(read == cursor);
+
+        // icons store the planes and bits per pixel where cursors store the
+        // hotspot
+        let mut icon = Icon::new(IconKind::Icon);
+        icon.add_image(cursor.get_images()[0].clone());
+        let read = Icon::from_bytes(&icon.to_bytes().unwrap()).unwrap();
+        assert_eq!(read.get_images()[0].get_hotspot(), (0, 0));
+    }
+
+    #[test]
+    fn store_png_images_as_they_are() {
+        let png = create_png_header(256, 256);
+        let mut icon = Icon::new(IconKind::Icon);
+        icon.add_image(IconImage::from_png(png.clone()).unwrap());
+        let bytes = icon.to_bytes().unwrap();
+        // a size of 256 is stored as zero
+        assert_eq!(&bytes[6..8], &[0, 0]);
+        let read = Icon::from_bytes(&bytes).unwrap();
+        let image = &read.get_images()[0];
+        assert!(image.is_png());
+        assert_eq!(image.get_png(), Some(&png[..]));
+        assert_eq!((image.get_width(), image.get_height()), (256, 256));
+        assert!(image.get_bitmap().is_none());
+
+        assert!(IconImage::from_png(vec![1, 2, 3]).is_err());
+        assert!(IconImage::from_png(create_png_header(257, 16)).is_err());
+    }
+
+    #[test]
+    fn use_the_mask_for_32_bit_images_without_alpha() {
+        let bitmap = create_checkers(8, &[Rgba::rgba(10, 20, 30, 50), Rgba::black()]);
+        let mut bytes = Icon::from_bitmaps(&[bitmap]).unwrap().to_bytes().unwrap();
+        // clear the alpha of every pixel, the mask marks nothing as
+        // transparent so every pixel should become opaque
+        let pixels = 22 + 40;
+        for i in 0..64 {
+            bytes[pixels + i * 4 + 3] = 0;
+        }
+        let read = Icon::from_bytes(&bytes).unwrap();
+        let read = read.get_images()[0].get_bitmap().unwrap();
+        assert_eq!(read.get_pixel(0, 0), Some(&Rgba::rgb(10, 20, 30)));
+        assert_eq!(read.get_pixel(1, 0), Some(&Rgba::black()));
+    }
+
+    #[test]
+    fn fail_to_read_broken_icons() {
+        let bitmap = create_checkers(4, &[Rgba::black(), Rgba::white()]);
+        let bytes = Icon::from_bitmaps(&[bitmap]).unwrap().to_bytes().unwrap();
+        let mut wrong_kind = bytes.clone();
+        wrong_kind[2] = 3;
+        assert!(matches!(
+            Icon::from_bytes(&wrong_kind),
+            Err(BitmapError::InvalidData(_))
+        ));
+        match Icon::from_bytes(&bytes[..bytes.len() - 1]) {
+            Err(BitmapError::Truncated { section, .. }) => assert_eq!(section, "icon image"),
+            _ => panic!("expected truncated data"),
+        }
+        // make the image claim to be one byte shorter, leaving out the mask
+        let mut short = bytes.clone();
+        short[14] -= 1;
+        match Icon::from_bytes(&short) {
+            Err(BitmapError::Truncated {
+                section, offset, ..
+            }) => {
+                assert_eq!(section, "icon mask");
+                assert!(offset > 22);
+            }
+            _ => panic!("expected truncated data"),
+        }
+        assert!(Icon::from_bytes(&bytes[..10]).is_err());
+    }
+
+    #[test]
+    fn count_every_image_against_the_limits() {
+        // point every entry of the directory at the same image
+        let bitmap = create_checkers(16, &[Rgba::black(), Rgba::white()]);
+        let bytes = Icon::from_bitmaps(&[bitmap]).unwrap().to_bytes().unwrap();
+        let repeat = |count: usize| {
+            let mut repeated = vec![0, 0, 1, 0, count as u8, 0];
+            let offset = (6 + count * 16) as u32;
+            for _ in 0..count {
+                repeated.extend_from_slice(&bytes[6..18]);
+                repeated.extend_from_slice(&offset.to_le_bytes());
+            }
+            repeated.extend_from_slice(&bytes[22..]);
+            repeated
+        };
+        let limits = DecodeLimits::new().with_max_pixels(16 * 16 * 3);
+        assert!(Icon::from_bytes_with_limits(&repeat(3), &limits).is_ok());
+        match Icon::from_bytes_with_limits(&repeat(4), &limits) {
+            Err(BitmapError::LimitExceeded { limit, value, .. }) => {
+                assert_eq!(limit, "pixel count");
+                assert_eq!(value, 16 * 16 * 4);
+            }
+            _ => panic!("expected the pixel count limit to be exceeded"),
+        }
+        let limits = DecodeLimits::new().with_max_alloc_bytes(16 * 16 * 4 * 3 + 16 * 16 * 8);
+        assert!(Icon::from_bytes_with_limits(&repeat(4), &limits).is_ok());
+        assert!(Icon::from_bytes_with_limits(&repeat(5), &limits).is_err());
+    }
+
+    #[test]
+    fn fail_to_save_images_that_dont_fit() {
+        assert!(Icon::new(IconKind::Icon).to_bytes().is_err());
+        assert!(Icon::from_bitmaps(&[BitMap::new(257, 10)]).is_err());
+        assert!(Icon::from_bitmaps(&[BitMap::new(0, 0)]).is_err());
+        assert!(Icon::from_bitmaps(&[BitMap::new(256, 1)]).is_ok());
+    }
+}
@@ -1,8 +1,19 @@
 use std::collections::HashSet;
+use std::io::{Read, Write};
 
 use super::bit_depth::BitDepth;
+use super::decode_limits::DecodeLimits;
+use super::decode_warning::DecodeWarning;
+use super::decode_warning::Recovery;
+use super::error::BitmapError;
 use super::file::File;
+use super::indexed_image::IndexedBitMap;
+use super::lenient_options::LenientOptions;
+use super::monochrome;
+use super::monochrome::MonochromeMethod;
+use super::quantizer::Quantizer;
 use super::rgba::Rgba;
+use super::save_options::SaveOptions;
 use super::util;
 
 ///
@@ -18,6 +29,18 @@ pub struct BitMap {
     height: u32,
     /// each pixel as an rgba
     pixels: Vec<Rgba>,
+    /// horizontal resolution in pixels per meter, zero if it isn't known
+    x_pixels_per_meter: u32,
+    /// vertical resolution in pixels per meter, zero if it isn't known
+    y_pixels_per_meter: u32,
+    /// color table of the file read from, if its pixels pointed into one
+    palette: Option<Vec<Rgba>>,
+    /// index of each pixel in the palette as it was read in, starting with
+    /// the bottom row. A pixel keeps its index while it's still the color
+    /// the index points to
+    indices: Option<Vec<u8>>,
+    /// number of colors in the palette that are important, zero if all are
+    colors_important: u32,
 }
 
 ///
@@ -27,22 +50,136 @@ impl BitMap {
     ///
     /// Create a bitmap by reading in a .bmp file
     ///
-    /// Fails if filename doesn't end with ".bmp"
+    /// Fails if filename doesn't end with ".bmp", or if the image is bigger
+    /// then the default decode limits allow
     ///
-    pub fn read(filename: &str) -> Result<BitMap, String> {
+    pub fn read(filename: &str) -> Result<BitMap, BitmapError> {
+        BitMap::read_with_limits(filename, &DecodeLimits::default())
+    }
+
+    ///
+    /// Create a bitmap by reading in a .bmp file, failing before the pixels
+    /// are read if the image is bigger then the limits allow
+    ///
+    pub fn read_with_limits(filename: &str, limits: &DecodeLimits) -> Result<BitMap, BitmapError> {
         if !filename.ends_with(".bmp") {
-            return Err(String::from("File must end with '.bmp'"));
+            return Err(BitmapError::InvalidArgument("File must end with '.bmp'"));
         }
-        let file = match File::read(filename) {
-            Err(why) => return Err(why),
-            Ok(f) => f,
-        };
-        Ok(BitMap {
-            filename: Some(String::from(filename)),
+        let file = File::read(filename, limits)?;
+        Ok(BitMap::from_file(&file, Some(String::from(filename))))
+    }
+
+    ///
+    /// Create a bitmap by decoding the bytes of a bitmap file that is already
+    /// in memory, using the default decode limits
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<BitMap, BitmapError> {
+        let file = File::from_slice(bytes)?;
+        Ok(BitMap::from_file(&file, None))
+    }
+
+    ///
+    /// Create a bitmap by decoding the bytes of a bitmap file that is already
+    /// in memory, failing before the pixels are read if the image is bigger
+    /// then the limits allow
+    ///
+    pub fn from_bytes_with_limits(
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<BitMap, BitmapError> {
+        let file = File::from_slice_with_limits(bytes, limits)?;
+        Ok(BitMap::from_file(&file, None))
+    }
+
+    ///
+    /// Create a bitmap by reading a bitmap file from any reader, for example
+    /// a network stream or an open file, using the default decode limits
+    ///
+    pub fn from_reader<R: Read>(reader: R) -> Result<BitMap, BitmapError> {
+        BitMap::from_reader_with_limits(reader, &DecodeLimits::default())
+    }
+
+    ///
+    /// Create a bitmap by reading a bitmap file from any reader, failing
+    /// before the pixels are read if the image is bigger then the limits allow
+    ///
+    pub fn from_reader_with_limits<R: Read>(
+        reader: R,
+        limits: &DecodeLimits,
+    ) -> Result<BitMap, BitmapError> {
+        let file = File::from_reader(reader, limits)?;
+        Ok(BitMap::from_file(&file, None))
+    }
+
+    ///
+    /// Create a bitmap by reading in a .bmp file that may be partially corrupt
+    ///
+    /// Problems that can be repaired don't fail, instead they are given back
+    /// as warnings along with the image. A wrong file size is ignored, a color
+    /// table that is cut short only uses the colors that are there, and
+    /// missing pixels or pixels that point outside of the color table are
+    /// given the fill color from the options.
+    ///
+    /// Fails if filename doesn't end with ".bmp", or if the file can't be
+    /// repaired
+    ///
+    pub fn read_lenient(
+        filename: &str,
+        options: &LenientOptions,
+    ) -> Result<(BitMap, Vec<DecodeWarning>), BitmapError> {
+        if !filename.ends_with(".bmp") {
+            return Err(BitmapError::InvalidArgument("File must end with '.bmp'"));
+        }
+        let mut recovery = Recovery::new(options.get_fill());
+        let file = File::read_lenient(filename, options.get_limits(), &mut recovery)?;
+        let bitmap = BitMap::from_file(&file, Some(String::from(filename)));
+        Ok((bitmap, recovery.into_warnings()))
+    }
+
+    ///
+    /// Create a bitmap by decoding the bytes of a bitmap file that may be
+    /// partially corrupt. Repairs are made the same way as
+    /// [BitMap::read_lenient].
+    ///
+    pub fn from_bytes_lenient(
+        bytes: &[u8],
+        options: &LenientOptions,
+    ) -> Result<(BitMap, Vec<DecodeWarning>), BitmapError> {
+        let mut recovery = Recovery::new(options.get_fill());
+        let file = File::from_slice_lenient(bytes, options.get_limits(), &mut recovery)?;
+        Ok((BitMap::from_file(&file, None), recovery.into_warnings()))
+    }
+
+    ///
+    /// Create a bitmap by reading a bitmap file that may be partially corrupt
+    /// from any reader. Repairs are made the same way as
+    /// [BitMap::read_lenient].
+    ///
+    pub fn from_reader_lenient<R: Read>(
+        mut reader: R,
+        options: &LenientOptions,
+    ) -> Result<(BitMap, Vec<DecodeWarning>), BitmapError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        BitMap::from_bytes_lenient(&bytes, options)
+    }
+
+    ///
+    /// Create a bitmap from a bitmap file that has been read in
+    ///
+    fn from_file(file: &File, filename: Option<String>) -> BitMap {
+        let (x_pixels_per_meter, y_pixels_per_meter) = file.get_pixels_per_meter();
+        BitMap {
+            filename,
             width: file.get_width(),
             height: file.get_height(),
             pixels: file.get_bitmap_as_pixels(),
-        })
+            x_pixels_per_meter,
+            y_pixels_per_meter,
+            palette: file.get_palette(),
+            indices: file.get_indices(),
+            colors_important: file.get_colors_important(),
+        }
     }
 
     ///
@@ -56,18 +193,23 @@ impl BitMap {
             filename: None,
             width,
             height,
-            pixels: vec![white; (width * height) as usize],
+            pixels: vec![white; width as usize * height as usize],
+            x_pixels_per_meter: 0,
+            y_pixels_per_meter: 0,
+            palette: None,
+            indices: None,
+            colors_important: 0,
         }
     }
 
     ///
     /// Create a new image from a list of pixels
     ///
-    pub fn create(width: u32, height: u32, mut pixels: Vec<Rgba>) -> Result<BitMap, &'static str> {
+    pub fn create(width: u32, height: u32, mut pixels: Vec<Rgba>) -> Result<BitMap, BitmapError> {
         if (width * height) as usize != pixels.len() {
-            return Err(
+            return Err(BitmapError::InvalidArgument(
                 "The area of the image must match the number of pixels you are passing in.",
-            );
+            ));
         }
         for x in 0..width {
             for y in 0..height / 2 {
@@ -81,7 +223,12 @@ impl BitMap {
             filename: None,
             width,
             height,
-            pixels: pixels,
+            pixels,
+            x_pixels_per_meter: 0,
+            y_pixels_per_meter: 0,
+            palette: None,
+            indices: None,
+            colors_important: 0,
         })
     }
 
@@ -135,6 +282,102 @@ impl BitMap {
         File::create(self, BitDepth::AllColors).calculate_file_size()
     }
 
+    ///
+    /// Get the horizontal and vertical resolution in pixels per meter, zero
+    /// means the resolution isn't known
+    ///
+    pub fn get_pixels_per_meter(&self) -> (u32, u32) {
+        (self.x_pixels_per_meter, self.y_pixels_per_meter)
+    }
+
+    ///
+    /// Set the horizontal and vertical resolution in pixels per meter, which
+    /// is written out when the image is saved
+    ///
+    pub fn set_pixels_per_meter(&mut self, x_pixels_per_meter: u32, y_pixels_per_meter: u32) {
+        self.x_pixels_per_meter = x_pixels_per_meter;
+        self.y_pixels_per_meter = y_pixels_per_meter;
+    }
+
+    ///
+    /// Get the horizontal and vertical resolution in dots per inch, rounded to
+    /// the nearest whole number
+    ///
+    pub fn get_dpi(&self) -> (u32, u32) {
+        (
+            util::pixels_per_meter_to_dpi(self.x_pixels_per_meter),
+            util::pixels_per_meter_to_dpi(self.y_pixels_per_meter),
+        )
+    }
+
+    ///
+    /// Set the horizontal and vertical resolution in dots per inch. The
+    /// resolution is stored in pixels per meter, so it's rounded to the
+    /// nearest pixel per meter
+    ///
+    pub fn set_dpi(&mut self, x_dpi: u32, y_dpi: u32) {
+        self.x_pixels_per_meter = util::dpi_to_pixels_per_meter(x_dpi);
+        self.y_pixels_per_meter = util::dpi_to_pixels_per_meter(y_dpi);
+    }
+
+    ///
+    /// Get the palette of the image, which is the color table of the 2, 16 or
+    /// 256 color file it was read from. The palette isn't changed when the
+    /// pixels are, and is only written if the image is saved with
+    /// [SaveOptions::with_keep_palette]
+    ///
+    pub fn get_palette(&self) -> Option<&[Rgba]> {
+        self.palette.as_deref()
+    }
+
+    ///
+    /// Set the palette that is written when the image is saved with
+    /// [SaveOptions::with_keep_palette], or remove it
+    ///
+    /// Fails if the palette has more then 256 colors
+    ///
+    pub fn set_palette(&mut self, palette: Option<Vec<Rgba>>) -> Result<(), BitmapError> {
+        if palette.as_ref().map_or(false, |p| p.len() > 256) {
+            return Err(BitmapError::InvalidArgument(
+                "A palette can't have more then 256 colors.",
+            ));
+        }
+        self.palette = palette;
+        Ok(())
+    }
+
+    ///
+    /// Get the index of each pixel in the palette as it was read in, starting
+    /// with the bottom row
+    ///
+    pub(crate) fn get_indices(&self) -> Option<&[u8]> {
+        self.indices.as_deref()
+    }
+
+    ///
+    /// Set the index of each pixel in the palette, starting with the bottom
+    /// row, or remove them
+    ///
+    pub(crate) fn set_indices(&mut self, indices: Option<Vec<u8>>) {
+        self.indices = indices;
+    }
+
+    ///
+    /// Get the number of colors at the start of the palette that are important
+    /// for showing the image, zero means every color is important
+    ///
+    pub fn get_colors_important(&self) -> u32 {
+        self.colors_important
+    }
+
+    ///
+    /// Set the number of colors at the start of the palette that are
+    /// important, which is written out when the palette is kept
+    ///
+    pub fn set_colors_important(&mut self, colors_important: u32) {
+        self.colors_important = colors_important;
+    }
+
     ///
     /// Get a reference to the file name of the bitmap if it exists
     ///
@@ -154,12 +397,18 @@ impl BitMap {
     ///
     /// Get all the unique colors from pixels, remove any duplicates
     ///
+    /// The colors are returned in the order they first appear in, so calling
+    /// this more then once always gives back the same list.
+    ///
     pub fn get_all_unique_colors(&self) -> Vec<&Rgba> {
-        let mut unique_colors = HashSet::new();
+        let mut seen = HashSet::new();
+        let mut unique_colors = Vec::new();
         for c in &self.pixels {
-            unique_colors.insert(c);
+            if seen.insert(c) {
+                unique_colors.push(c);
+            }
         }
-        unique_colors.into_iter().collect::<Vec<_>>()
+        unique_colors
     }
 
     ///
@@ -184,10 +433,10 @@ impl BitMap {
     ///
     /// Fail if no original location is linked to the current bitmap
     ///
-    pub fn save(&self) -> Result<(), String> {
+    pub fn save(&self) -> Result<(), BitmapError> {
         match self.filename.as_ref() {
             Some(f) => self.save_as(f),
-            None => Err(String::from(
+            None => Err(BitmapError::InvalidArgument(
                 "Couldn't save image because you didn't read in the bitmap from an image",
             )),
         }
@@ -196,14 +445,113 @@ impl BitMap {
     ///
     /// Save the image to a new location on disk
     ///
-    pub fn save_as(&self, filename: &str) -> Result<(), String> {
+    pub fn save_as(&self, filename: &str) -> Result<(), BitmapError> {
         // check to see if any pixels are transparent
         let bit_depth = if self.is_image_transparent() {
             BitDepth::AllColorsAndShades
         } else {
             BitDepth::AllColors
         };
-        self.save_as_file(filename, bit_depth)
+        self.save_as_file(filename, bit_depth, &SaveOptions::default())
+    }
+
+    ///
+    /// Save the image to a new location on disk using the given options
+    ///
+    /// If the options don't specify a bit depth, the image is saved as a 32 bit
+    /// image if any pixels are transparent or if an alpha mask was requested,
+    /// otherwise it's saved as a 24 bit image. When run length encoding is
+    /// requested, the image is saved as a 16 or 256 color image if it has few
+    /// enough colors.
+    ///
+    /// Fails if the image has more colors then the requested bit depth can
+    /// store in its color table, unless the options have a quantizer or a
+    /// dither to reduce the colors
+    ///
+    pub fn save_as_with_options(
+        &self,
+        filename: &str,
+        options: &SaveOptions,
+    ) -> Result<(), BitmapError> {
+        let bit_depth = self.get_bit_depth_to_save_as(options)?;
+        self.save_as_file(filename, bit_depth, options)
+    }
+
+    ///
+    /// Encode the image and write it to any writer, for example a network
+    /// stream or an in memory buffer. The bit depth is picked the same way
+    /// as [BitMap::save_as_with_options].
+    ///
+    pub fn write_to<W: Write>(
+        &self,
+        mut writer: W,
+        options: &SaveOptions,
+    ) -> Result<(), BitmapError> {
+        let bit_stream = self.to_bytes(options)?;
+        writer.write_all(&bit_stream)?;
+        Ok(())
+    }
+
+    ///
+    /// Encode the image into the bytes of a bitmap file. The bit depth is
+    /// picked the same way as [BitMap::save_as_with_options].
+    ///
+    pub fn to_bytes(&self, options: &SaveOptions) -> Result<Vec<u8>, BitmapError> {
+        let bit_depth = self.get_bit_depth_to_save_as(options)?;
+        Ok(self.create_file(bit_depth, options)?.to_bytes())
+    }
+
+    ///
+    /// Get the bit depth to save the image at using the given options
+    ///
+    /// Fails if the image has more colors then the requested bit depth can
+    /// store in its color table, if the palette is kept or fixed and has more
+    /// colors then the bit depth can store, or if a fixed palette is used with
+    /// a bit depth that has no color table
+    ///
+    fn get_bit_depth_to_save_as(&self, options: &SaveOptions) -> Result<BitDepth, BitmapError> {
+        let kept_palette = match options.get_palette() {
+            Some(palette) => Some(palette.len()),
+            None if options.keeps_palette() => self.palette.as_ref().map(|p| p.len()),
+            None => None,
+        };
+        let bit_depth = match options.get_bit_depth() {
+            Some(bit_depth) => bit_depth,
+            None if kept_palette.is_some() => match (kept_palette, options.is_rle()) {
+                (Some(0..=2), false) => BitDepth::Color2Bit,
+                (Some(0..=16), _) => BitDepth::Color16Bit,
+                _ => BitDepth::Color256Bit,
+            },
+            None if options.is_rle() => match BitDepth::get_suggested_bit_depth(self) {
+                BitDepth::Color2Bit => BitDepth::Color16Bit,
+                bit_depth => bit_depth,
+            },
+            None if self.is_image_transparent() || options.has_alpha_mask() => {
+                BitDepth::AllColorsAndShades
+            }
+            None => BitDepth::AllColors,
+        };
+        if options.get_palette().is_some() && bit_depth.get_max_colors().is_none() {
+            return Err(BitmapError::InvalidArgument(
+                "A fixed palette can only be saved with 2, 16 or 256 color bit depths.",
+            ));
+        }
+        if let Some(max_colors) = bit_depth.get_max_colors() {
+            let reduces_colors =
+                options.get_quantizer().is_some() || options.get_dither().is_some();
+            let colors = match kept_palette {
+                Some(colors) => colors,
+                // the colors are reduced to fit when the file is created
+                None if reduces_colors => 0,
+                None => self.get_all_unique_colors().len(),
+            };
+            if colors > max_colors {
+                return Err(BitmapError::InvalidArgument(
+                    "Image has too many colors to be saved with the requested bit depth.",
+                ));
+            }
+        }
+        Ok(bit_depth)
     }
 
     ///
@@ -217,12 +565,12 @@ impl BitMap {
     /// if there are more then 256 colors and all alphas are 100, 24 bit
     /// if there are more then 256 colors and at least one alpha is not 100, 32 bit
     ///
-    pub fn simplify_and_save(&self) -> Result<(), String> {
+    pub fn simplify_and_save(&self) -> Result<(), BitmapError> {
         let bit_depth = BitDepth::get_suggested_bit_depth(self);
 
         match self.filename.as_ref() {
-            Some(f) => self.save_as_file(f, bit_depth),
-            None => Err(String::from(
+            Some(f) => self.save_as_file(f, bit_depth, &SaveOptions::default()),
+            None => Err(BitmapError::InvalidArgument(
                 "Couldn't save image because you didn't read in the bitmap from an image",
             )),
         }
@@ -239,26 +587,69 @@ impl BitMap {
     /// if there are more then 256 colors and all alphas are 100, 24 bit
     /// if there are more then 256 colors and at least one alpha is not 100, 32 bit
     ///
-    pub fn simplify_and_save_as(&self, filename: &str) -> Result<(), String> {
+    pub fn simplify_and_save_as(&self, filename: &str) -> Result<(), BitmapError> {
         let bit_depth = BitDepth::get_suggested_bit_depth(self);
-        self.save_as_file(filename, bit_depth)
+        self.save_as_file(filename, bit_depth, &SaveOptions::default())
     }
 
     ///
     /// Actually save the file using the given filename and bit depth
     ///
-    fn save_as_file(&self, filename: &str, bit_depth: BitDepth) -> Result<(), String> {
-        let file = File::create(self, bit_depth);
-        use std::io::Write;
-        let mut bit_stream = file.to_bytes();
-        let mut file = match std::fs::File::create(filename) {
-            Err(why) => return Err(format!("Couldn't create {}: {}", filename, why).to_owned()),
-            Ok(file) => file,
+    fn save_as_file(
+        &self,
+        filename: &str,
+        bit_depth: BitDepth,
+        options: &SaveOptions,
+    ) -> Result<(), BitmapError> {
+        let bit_stream = self.create_file(bit_depth, options)?.to_bytes();
+        let mut file = std::fs::File::create(filename)?;
+        file.write_all(&bit_stream)?;
+        Ok(())
+    }
+
+    ///
+    /// Create the file that is saved, using the palette of the image as the
+    /// color table if it's kept and the bit depth has a color table. Pixels
+    /// that are still the color they were read in as keep their index
+    ///
+    /// If there's a quantizer or a dither and the image has too many colors
+    /// for the color table, the colors are reduced to fit. If the palette is
+    /// kept but some colors in the image aren't in it, the image is dithered
+    /// to the palette, or fails if there's no dither
+    ///
+    fn create_file(&self, bit_depth: BitDepth, options: &SaveOptions) -> Result<File, BitmapError> {
+        let max_colors = match bit_depth.get_max_colors() {
+            Some(max_colors) => max_colors,
+            None => return Ok(File::create_with_options(self, bit_depth, options)),
         };
-        match file.write_all(bit_stream.as_mut_slice()) {
-            Err(why) => Err(format!("Couldn't write to {}: {}", filename, why).to_owned()),
-            Ok(_) => Ok(()),
+        let palette = self.palette.as_deref().filter(|_| options.keeps_palette());
+        let dither = options.get_dither();
+        if let Some(fixed) = options.get_palette() {
+            let image = fixed.apply(self, dither.as_ref())?;
+            return Ok(File::create_indexed(&image, bit_depth, options));
+        } else if let Some(palette) = palette {
+            let colors: HashSet<&Rgba> = palette.iter().collect();
+            if self.pixels.iter().any(|pixel| !colors.contains(pixel)) {
+                let dither = dither.ok_or(BitmapError::InvalidArgument(
+                    "Image has colors that aren't in its palette.",
+                ))?;
+                let mut image = dither.apply(self, palette)?;
+                image.set_colors_important(self.colors_important);
+                return Ok(File::create_indexed(&image, bit_depth, options));
+            }
+            let image = IndexedBitMap::from_bitmap(self)?;
+            return Ok(File::create_indexed(&image, bit_depth, options));
+        } else if (options.get_quantizer().is_some() || dither.is_some())
+            && self.get_all_unique_colors().len() > max_colors
+        {
+            let quantizer = options.get_quantizer().unwrap_or(Quantizer::MedianCut);
+            let image = match dither {
+                Some(dither) => quantizer.quantize_with_dither(self, max_colors, &dither)?,
+                None => quantizer.quantize(self, max_colors)?,
+            };
+            return Ok(File::create_indexed(&image, bit_depth, options));
         }
+        Ok(File::create_with_options(self, bit_depth, options))
     }
 }
 
@@ -269,7 +660,7 @@ impl BitMap {
 impl BitMap {
     /// Crop an image by a percentage. The tuple provides the x and y coordinates
     /// to crop by. View [BitMap::crop_by_coordinates] for more information.
-    pub fn crop_by_tuple(&self, (x, y): (u32, u32), crop_factor: f32) -> Result<BitMap, &'static str> {
+    pub fn crop_by_tuple(&self, (x, y): (u32, u32), crop_factor: f32) -> Result<BitMap, BitmapError> {
         self.crop_by_coordinates(x, y, crop_factor)
     }
 
@@ -282,9 +673,11 @@ impl BitMap {
     /// and the x and y is set at 0 and 0 respectively, new x and y coordinates
     /// will be computed so that the crop will work. In this example, the x and
     /// y coordinates would change to by 25% pixel place of the image. 
-    pub fn crop_by_coordinates(&self, x: u32, y: u32, crop_factor: f32) -> Result<BitMap, &'static str> {
+    pub fn crop_by_coordinates(&self, x: u32, y: u32, crop_factor: f32) -> Result<BitMap, BitmapError> {
         if crop_factor >= 1.0 && crop_factor <= 0.0 {
-            return Err("Crop factor must be between the value of 0 and 1.");
+            return Err(BitmapError::InvalidArgument(
+                "Crop factor must be between the value of 0 and 1.",
+            ));
         }
 
         let (from_x, from_y, to_x, to_y) = util::calculate_crop(self.width, self.height, x, y, crop_factor);
@@ -300,9 +693,9 @@ impl BitMap {
     /// @param {u32} ending x position
     /// @param {u32} ending y position
     ///
-    /// @exception {&'static str} error message
-    /// if the starting x and ending x or starting y and ending y is out of the
-    /// image with height or width, throw an error
+    /// @exception {BitmapError} invalid argument if the starting position is
+    /// after the ending position, out of bounds if the ending x or ending y is
+    /// outside of the image
     ///
     pub fn crop(
         &self,
@@ -310,14 +703,18 @@ impl BitMap {
         from_y: u32,
         to_x: u32,
         to_y: u32,
-    ) -> Result<BitMap, &'static str> {
+    ) -> Result<BitMap, BitmapError> {
         if from_x > to_x {
-            return Err("From x must be less then to x.");
+            return Err(BitmapError::InvalidArgument(
+                "From x must be less then to x.",
+            ));
         }
         let width = to_x - from_x;
 
         if from_y > to_y {
-            return Err("From y must be less then to y.");
+            return Err(BitmapError::InvalidArgument(
+                "From y must be less then to y.",
+            ));
         }
         let height = to_y - from_y;
 
@@ -327,7 +724,12 @@ impl BitMap {
         }
 
         if to_x > self.width || to_y > self.height {
-            return Err("cropped image exceeds the bounds of the current image.");
+            return Err(BitmapError::OutOfBounds {
+                x: to_x,
+                y: to_y,
+                width: self.width,
+                height: self.height,
+            });
         }
 
         let mut colors = vec![Rgba::white(); area as usize];
@@ -338,13 +740,18 @@ impl BitMap {
                 // let colors_index = (((height - y - from_y - 1) * width) + x - from_x) as usize;
                 // let colors_index = ((y - from_y) * width + x - from_x) as usize;
                 colors[counter] = self.pixels[index];
-                counter = counter + 1;
+                counter += 1;
             }
         }
 
         match BitMap::create(width, height, colors) {
             Err(why) => Err(why),
-            Ok(b) => Ok(b),
+            Ok(mut b) => {
+                b.set_pixels_per_meter(self.x_pixels_per_meter, self.y_pixels_per_meter);
+                b.palette = self.palette.clone();
+                b.colors_important = self.colors_important;
+                Ok(b)
+            }
         }
     }
 
@@ -356,15 +763,25 @@ impl BitMap {
         bitmap: &BitMap,
         start_at_x: u32,
         start_at_y: u32,
-    ) -> Result<(), &'static str> {
+    ) -> Result<(), BitmapError> {
         if start_at_x > self.width || start_at_y > self.height {
-            return Err("Starting position is outside of the image.");
+            return Err(BitmapError::OutOfBounds {
+                x: start_at_x,
+                y: start_at_y,
+                width: self.width,
+                height: self.height,
+            });
         }
 
         let end_at_x = start_at_x + bitmap.get_width();
         let end_at_y = start_at_y + bitmap.get_height();
         if end_at_x > self.width || end_at_y > self.height {
-            return Err("Bitmap being pasted doesn't fit inside image.");
+            return Err(BitmapError::OutOfBounds {
+                x: end_at_x,
+                y: end_at_y,
+                width: self.width,
+                height: self.height,
+            });
         }
 
         for x in start_at_x..end_at_x {
@@ -385,7 +802,7 @@ impl BitMap {
         bitmap: &BitMap,
         start_at_x: u32,
         start_at_y: u32,
-    ) -> Result<(), &'static str> {
+    ) -> Result<(), BitmapError> {
         let end_at_x = start_at_x + bitmap.get_width();
         let end_at_y = start_at_y + bitmap.get_height();
 
@@ -418,9 +835,14 @@ impl BitMap {
     /// @param {u32} y position
     /// @param {Rgba} color to set pixel
     ///
-    pub fn set_pixel(&mut self, x: u32, y: u32, color: Rgba) -> Result<(), &'static str> {
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Rgba) -> Result<(), BitmapError> {
         if y >= self.height || x >= self.width {
-            return Err("Pixel is not contained inside of the image.");
+            return Err(BitmapError::OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            });
         }
         // images are saved upside down, so to get the pixel we flip it right side up
         let index = self.get_index(x, y);
@@ -437,6 +859,15 @@ impl BitMap {
         }
     }
 
+    ///
+    /// Convert image to only black and white pixels, so it can be saved as a
+    /// 2 color image by [BitMap::simplify_and_save_as]. Transparent pixels are
+    /// treated as if they were drawn on white
+    ///
+    pub fn color_to_monochrome(&mut self, method: MonochromeMethod) {
+        self.pixels = monochrome::convert(method, &self.pixels, self.width, self.height);
+    }
+
     ///
     /// Find all the pixels that are the same as the from color and convert them
     /// all to the "to" color.
@@ -467,9 +898,14 @@ impl BitMap {
     /// @param {u32} y position
     /// @param {Rgba} color to use to replace the other color
     ///
-    pub fn fill_region(&mut self, x: u32, y: u32, color: Rgba) -> Result<(), &'static str> {
+    pub fn fill_region(&mut self, x: u32, y: u32, color: Rgba) -> Result<(), BitmapError> {
         if y >= self.height || x >= self.width {
-            return Err("Pixel is not contained inside of the image.");
+            return Err(BitmapError::OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            });
         }
         // images are saved upside down, so to get the pixel we flip it right side up
         let starting_index = self.get_index(x, y);
@@ -477,8 +913,7 @@ impl BitMap {
         let old_color = self.pixels[starting_index];
         let mut visited = Vec::new();
         let mut unvisited = vec![starting_index];
-        while unvisited.len() > 0 {
-            let index = unvisited.pop().unwrap();
+        while let Some(index) = unvisited.pop() {
             if old_color == self.pixels[index] {
                 self.pixels[index] = color;
                 visited.push(index);
@@ -522,9 +957,11 @@ impl BitMap {
     /// Resize the current image by using nearest neighbor algorithm. Scale image
     /// to image size * the factor
     ///
-    pub fn fast_resize_by(&mut self, factor: f32) -> Result<(), &'static str> {
+    pub fn fast_resize_by(&mut self, factor: f32) -> Result<(), BitmapError> {
         if factor <= 0.0 {
-            return Err("Can't resize by a negative value.");
+            return Err(BitmapError::InvalidArgument(
+                "Can't resize by a negative value.",
+            ));
         }
         let width = (factor * (self.width as f32)).round();
         let height = (factor * (self.height as f32)).round();
@@ -574,9 +1011,11 @@ impl BitMap {
     /// Resize the current image by using bilinear interpolation algorithm. Scale
     /// image to image size * the factor
     ///
-    pub fn resize_by(&mut self, factor: f32) -> Result<(), &'static str> {
+    pub fn resize_by(&mut self, factor: f32) -> Result<(), BitmapError> {
         if factor <= 0.0 {
-            return Err("Can't resize by a negative value.");
+            return Err(BitmapError::InvalidArgument(
+                "Can't resize by a negative value.",
+            ));
         }
         let width = (factor * (self.width as f32)).round();
         let height = (factor * (self.height as f32)).round();
@@ -667,9 +1106,11 @@ impl BitMap {
     /// Resize the current image by using bicubic interpolation algorithm. Scale
     /// image to image size * the factor
     ///
-    pub fn slow_resize_by(&mut self, factor: f32) -> Result<(), &'static str> {
+    pub fn slow_resize_by(&mut self, factor: f32) -> Result<(), BitmapError> {
         if factor <= 0.0 {
-            return Err("Can't resize by a negative value.");
+            return Err(BitmapError::InvalidArgument(
+                "Can't resize by a negative value.",
+            ));
         }
         let width = (factor * (self.width as f32)).round();
         let height = (factor * (self.height as f32)).round();
@@ -712,12 +1153,12 @@ impl BitMap {
 
                 let mut old_old_index = ((w.floor() * self.width as f32) + v.floor()) as usize;
                 if old_old_index >= (self.width * self.height) as usize {
-                    old_old_index = old_old_index - 1
+                    old_old_index -= 1
                 }
 
                 // https://www.paulinternet.nl/?page=bicubic
                 // get the 3 colors from the old image
-                for i in 0..colors.len() {
+                for (i, slot) in colors.iter_mut().enumerate() {
                     let j = i as isize - 1;
                     let old_index_modified = (j * self.width as isize) + old_old_index as isize;
 
@@ -729,13 +1170,9 @@ impl BitMap {
                     {
                         // we have a problem because current index is less then
                         // the actual image
-                        if i == 0 {
-                            old_old_index
-                        } else if i == 1 {
-                            old_old_index
-                        } else if i == 2 {
+                        if i == 2 {
                             let check_old_index_modified =
-                                (j - 1 * self.width as isize) + old_old_index as isize;
+                                (j - (self.width as isize)) + old_old_index as isize;
                             if check_old_index_modified < 0
                                 || check_old_index_modified > (self.width * self.height) as isize
                             {
@@ -784,7 +1221,7 @@ impl BitMap {
                         x_factor,
                     );
 
-                    colors[i] = color;
+                    *slot = color;
                 }
                 // interpolate all 4 rows into one pixel
                 let color = Rgba::cubic_interpolate(
@@ -807,14 +1244,12 @@ impl BitMap {
         let mut new_pixels = Vec::with_capacity(self.get_size() as usize);
         for x in (0..self.width).rev() {
             for y in (0..self.height).rev() {
-                new_pixels.push(self.get_pixel(x, y).unwrap().clone());
+                new_pixels.push(*self.get_pixel(x, y).unwrap());
             }
         }
 
         self.pixels = new_pixels;
-        let temp_width = self.width;
-        self.width = self.height;
-        self.height = temp_width;
+        std::mem::swap(&mut self.width, &mut self.height);
     }
 
     ///
@@ -824,14 +1259,12 @@ impl BitMap {
         let mut new_pixels = Vec::with_capacity(self.get_size() as usize);
         for x in 0..self.width {
             for y in 0..self.height {
-                new_pixels.push(self.get_pixel(x, y).unwrap().clone());
+                new_pixels.push(*self.get_pixel(x, y).unwrap());
             }
         }
 
         self.pixels = new_pixels;
-        let temp_width = self.width;
-        self.width = self.height;
-        self.height = temp_width;
+        std::mem::swap(&mut self.width, &mut self.height);
     }
 }
 
@@ -857,21 +1290,21 @@ impl PartialEq for BitMap {
 #[cfg(debug_assertions)]
 impl std::fmt::Display for BitMap {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        if self.filename.is_some() {
-            write!(f, "filename: {} ", self.filename.as_ref().unwrap()).unwrap()
+        if let Some(filename) = self.filename.as_ref() {
+            write!(f, "filename: {} ", filename).unwrap()
         }
-        write!(
+        writeln!(
             f,
-            "width: {}\t height: {}\t pixels: {}\n",
+            "width: {}\t height: {}\t pixels: {}",
             self.width,
             self.height,
             self.pixels.len()
         )
         .unwrap();
         for c in &self.pixels {
-            write!(f, "{}\n", c).unwrap();
+            writeln!(f, "{}", c).unwrap();
         }
-        write!(f, "\n")
+        writeln!(f)
     }
 }
 
@@ -879,8 +1312,8 @@ impl std::fmt::Display for BitMap {
 impl std::fmt::Debug for BitMap {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Bitmap: {{ ").unwrap();
-        if self.filename.is_some() {
-            write!(f, "filename: {} ", self.filename.as_ref().unwrap()).unwrap();
+        if let Some(filename) = self.filename.as_ref() {
+            write!(f, "filename: {} ", filename).unwrap();
         } else {
             write!(f, "filename: None ").unwrap();
         }
@@ -896,8 +1329,12 @@ impl std::fmt::Debug for BitMap {
 
 #[cfg(test)]
 mod test {
+    use super::BitDepth;
     use super::BitMap;
+    use super::BitmapError;
+    use super::IndexedBitMap;
     use super::Rgba;
+    use super::SaveOptions;
 
     #[test]
     fn try_to_read_in_file_that_doesnt_end_with_bmp() {
@@ -940,20 +1377,31 @@ mod test {
     fn crop_image_bigger_then_image() {
         let image = BitMap::new(10, 10);
         let crop = image.crop(0, 0, 11, 11);
-        assert!(crop.is_err());
+        assert!(matches!(
+            crop,
+            Err(BitmapError::OutOfBounds {
+                x: 11,
+                y: 11,
+                width: 10,
+                height: 10
+            })
+        ));
     }
 
     #[test]
     fn crop_image_negatively() {
         let image = BitMap::new(10, 10);
         let crop = image.crop(5, 5, 0, 0);
-        assert!(crop.is_err());
+        assert!(matches!(crop, Err(BitmapError::InvalidArgument(_))));
     }
 
     #[test]
     fn cant_set_pixel_outside_of_image() {
         let mut image = BitMap::new(10, 10);
-        assert!(image.set_pixel(10, 10, Rgba::black()).is_err());
+        assert!(matches!(
+            image.set_pixel(10, 10, Rgba::black()),
+            Err(BitmapError::OutOfBounds { x: 10, y: 10, .. })
+        ));
         assert!(image.set_pixel(1000, 1000, Rgba::black()).is_err());
     }
 
@@ -1147,4 +1595,68 @@ mod test {
             assert!(pixel.is_black())
         }
     }
+
+    #[test]
+    fn keep_resolution_when_cropping() {
+        let mut image = BitMap::new(10, 10);
+        assert_eq!(image.get_dpi(), (0, 0));
+        image.set_dpi(300, 150);
+        assert_eq!(image.get_pixels_per_meter(), (11811, 5906));
+        assert_eq!(image.get_dpi(), (300, 150));
+        let cropped = image.crop(0, 0, 5, 5).unwrap();
+        assert_eq!(cropped.get_dpi(), (300, 150));
+        image.set_pixels_per_meter(2835, 2835);
+        assert_eq!(image.get_dpi(), (72, 72));
+    }
+
+    #[test]
+    fn save_with_the_palette_of_the_image() {
+        let mut image = BitMap::new(3, 1);
+        image.set_pixel(0, 0, Rgba::black()).unwrap();
+        let palette = vec![Rgba::rgb(9, 9, 9), Rgba::black(), Rgba::white()];
+        image.set_palette(Some(palette.clone())).unwrap();
+        image.set_colors_important(2);
+        let options = SaveOptions::new().with_keep_palette(true);
+        let read = BitMap::from_bytes(&image.to_bytes(&options).unwrap()).unwrap();
+        assert_eq!(read.get_palette(), Some(&palette[..]));
+        assert_eq!(read.get_colors_important(), 2);
+        assert!(read == image);
+        let cropped = read.crop(0, 0, 1, 1).unwrap();
+        assert_eq!(cropped.get_palette(), Some(&palette[..]));
+
+        // without the option a new palette is built
+        let read = BitMap::from_bytes(&image.to_bytes(&SaveOptions::new()).unwrap()).unwrap();
+        assert_eq!(read.get_palette(), None);
+        assert_eq!(read.get_colors_important(), 0);
+    }
+
+    #[test]
+    fn keep_indices_that_point_at_duplicate_palette_colors() {
+        let mut palette = vec![Rgba::black(), Rgba::white(), Rgba::black()];
+        palette.resize(256, Rgba::black());
+        let image = IndexedBitMap::create(4, 1, palette, vec![0, 1, 2, 3]).unwrap();
+        let options = SaveOptions::new()
+            .with_bit_depth(BitDepth::Color256Bit)
+            .with_keep_palette(true);
+        let bytes = image.to_bytes(&options).unwrap();
+        let mut read = BitMap::from_bytes(&bytes).unwrap();
+        assert_eq!(read.to_bytes(&options).unwrap(), bytes);
+
+        // pixels that are changed are pointed at the first matching color
+        read.set_pixel(1, 0, Rgba::black()).unwrap();
+        let saved = IndexedBitMap::from_bytes(&read.to_bytes(&options).unwrap()).unwrap();
+        assert_eq!(saved.get_indices(), &[0, 0, 2, 3]);
+    }
+
+    #[test]
+    fn fail_to_save_with_a_palette_that_doesnt_fit() {
+        let mut image = BitMap::new(3, 1);
+        image.set_palette(Some(vec![Rgba::black()])).unwrap();
+        let options = SaveOptions::new().with_keep_palette(true);
+        assert!(image.to_bytes(&options).is_err());
+        image.set_palette(Some(vec![Rgba::white(); 3])).unwrap();
+        let options = options.with_bit_depth(BitDepth::Color2Bit);
+        assert!(image.to_bytes(&options).is_err());
+        assert!(image.set_palette(Some(vec![Rgba::white(); 257])).is_err());
+    }
 }
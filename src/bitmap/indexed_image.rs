@@ -0,0 +1,642 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use super::bit_depth::BitDepth;
+use super::decode_limits::DecodeLimits;
+use super::error::BitmapError;
+use super::file::File;
+use super::image::BitMap;
+use super::rgba::Rgba;
+use super::save_options::SaveOptions;
+
+///
+/// In memory representation of a 2, 16 or 256 color image, where each pixel
+/// is the index of its color in a palette. Images read in keep their color
+/// table and indices exactly as they were stored, so they can be edited and
+/// saved again without the colors ever being looked up.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedBitMap {
+    /// width of image
+    width: u32,
+    /// height of image
+    height: u32,
+    /// colors the pixels point to, at most 256
+    palette: Vec<Rgba>,
+    /// index of the color of each pixel, starting with the bottom row
+    indices: Vec<u8>,
+    /// number of colors in the palette that are important, zero if all are
+    colors_important: u32,
+    /// horizontal resolution in pixels per meter, zero if it isn't known
+    x_pixels_per_meter: u32,
+    /// vertical resolution in pixels per meter, zero if it isn't known
+    y_pixels_per_meter: u32,
+}
+
+///
+/// This block deals with constructors, and getters and setters
+///
+impl IndexedBitMap {
+    ///
+    /// Create an indexed image by reading in a 2, 16 or 256 color .bmp file
+    ///
+    /// Fails if filename doesn't end with ".bmp", if the image is bigger then
+    /// the default decode limits allow, or if the pixels don't point into a
+    /// color table
+    ///
+    pub fn read(filename: &str) -> Result<IndexedBitMap, BitmapError> {
+        if !filename.ends_with(".bmp") {
+            return Err(BitmapError::InvalidArgument("File must end with '.bmp'"));
+        }
+        let file = File::read(filename, &DecodeLimits::default())?;
+        IndexedBitMap::from_file(&file)
+    }
+
+    ///
+    /// Create an indexed image by decoding the bytes of a 2, 16 or 256 color
+    /// bitmap file that is already in memory, using the default decode limits
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<IndexedBitMap, BitmapError> {
+        let file = File::from_slice(bytes)?;
+        IndexedBitMap::from_file(&file)
+    }
+
+    ///
+    /// Create an indexed image by reading a 2, 16 or 256 color bitmap file
+    /// from any reader, using the default decode limits
+    ///
+    pub fn from_reader<R: Read>(reader: R) -> Result<IndexedBitMap, BitmapError> {
+        let file = File::from_reader(reader, &DecodeLimits::default())?;
+        IndexedBitMap::from_file(&file)
+    }
+
+    ///
+    /// Create an indexed image from a bitmap file that has been read in
+    ///
+    /// Fails if some pixels don't point into the color table, which happens
+    /// when the file isn't a 2, 16 or 256 color image, or when a run length
+    /// encoded image skips over pixels
+    ///
+    fn from_file(file: &File) -> Result<IndexedBitMap, BitmapError> {
+        let (palette, indices) = match (file.get_palette(), file.get_indices()) {
+            (Some(palette), Some(indices)) => (palette, indices),
+            _ => {
+                return Err(BitmapError::InvalidArgument(
+                    "Only 2, 16 and 256 color images can be read as indexed images.",
+                ))
+            }
+        };
+        let (x_pixels_per_meter, y_pixels_per_meter) = file.get_pixels_per_meter();
+        Ok(IndexedBitMap {
+            width: file.get_width(),
+            height: file.get_height(),
+            palette,
+            indices,
+            colors_important: file.get_colors_important(),
+            x_pixels_per_meter,
+            y_pixels_per_meter,
+        })
+    }
+
+    ///
+    /// Create a new indexed image in memory
+    ///
+    /// Every pixel points to the first color of the palette. Fails if the
+    /// palette is empty or has more then 256 colors
+    ///
+    pub fn new(width: u32, height: u32, palette: Vec<Rgba>) -> Result<IndexedBitMap, BitmapError> {
+        IndexedBitMap::check_palette(&palette)?;
+        Ok(IndexedBitMap {
+            width,
+            height,
+            palette,
+            indices: vec![0; width as usize * height as usize],
+            colors_important: 0,
+            x_pixels_per_meter: 0,
+            y_pixels_per_meter: 0,
+        })
+    }
+
+    ///
+    /// Create a new indexed image from a palette and a list of indices, one
+    /// for each pixel starting with the top row
+    ///
+    /// Fails if the palette is empty or has more then 256 colors, if the area
+    /// of the image doesn't match the number of indices, or if an index is
+    /// outside of the palette
+    ///
+    pub fn create(
+        width: u32,
+        height: u32,
+        palette: Vec<Rgba>,
+        indices: Vec<u8>,
+    ) -> Result<IndexedBitMap, BitmapError> {
+        IndexedBitMap::check_palette(&palette)?;
+        if width as usize * height as usize != indices.len() {
+            return Err(BitmapError::InvalidArgument(
+                "The area of the image must match the number of indices you are passing in.",
+            ));
+        }
+        if indices.iter().any(|&i| i as usize >= palette.len()) {
+            return Err(BitmapError::InvalidArgument(
+                "Every index must point to a color in the palette.",
+            ));
+        }
+        let indices = match width {
+            0 => indices,
+            width => indices
+                .chunks(width as usize)
+                .rev()
+                .flatten()
+                .copied()
+                .collect(),
+        };
+        Ok(IndexedBitMap {
+            width,
+            height,
+            palette,
+            indices,
+            colors_important: 0,
+            x_pixels_per_meter: 0,
+            y_pixels_per_meter: 0,
+        })
+    }
+
+    ///
+    /// Create an indexed image from a bitmap. If the bitmap has a palette with
+    /// every color of the image in it, that palette is used as it is along
+    /// with the number of important colors, and pixels that are still the
+    /// color they were read in as keep their index. Otherwise a palette is
+    /// built from the colors of the image. Either way the resolution is kept
+    ///
+    /// Fails if the image has more then 256 colors and no palette
+    ///
+    pub fn from_bitmap(bitmap: &BitMap) -> Result<IndexedBitMap, BitmapError> {
+        let mut positions = HashMap::new();
+        let mut colors_important = 0;
+        let mut kept_indices = bitmap
+            .get_indices()
+            .filter(|indices| indices.len() == bitmap.get_pixels().len());
+        if let Some(palette) = bitmap.get_palette() {
+            for (i, color) in palette.iter().enumerate() {
+                positions.entry(*color).or_insert(i as u8);
+            }
+            colors_important = bitmap.get_colors_important();
+        }
+        let mut palette = bitmap.get_palette().map(|p| p.to_vec()).unwrap_or_default();
+        if bitmap
+            .get_pixels()
+            .iter()
+            .any(|p| !positions.contains_key(p))
+        {
+            // the palette doesn't cover the image, so build a new one
+            positions.clear();
+            palette.clear();
+            colors_important = 0;
+            kept_indices = None;
+            for color in bitmap.get_all_unique_colors() {
+                if palette.len() == 256 {
+                    return Err(BitmapError::InvalidArgument(
+                        "Image has more then 256 colors, so it can't be indexed.",
+                    ));
+                }
+                positions.insert(*color, palette.len() as u8);
+                palette.push(*color);
+            }
+        }
+        if palette.is_empty() {
+            // an image without any pixels still needs a color
+            palette.push(Rgba::white());
+        }
+        let indices = bitmap
+            .get_pixels()
+            .iter()
+            .enumerate()
+            .map(|(i, p)| match kept_indices.map(|indices| indices[i]) {
+                Some(index) if palette.get(index as usize) == Some(p) => index,
+                _ => positions[p],
+            })
+            .collect();
+        let (x_pixels_per_meter, y_pixels_per_meter) = bitmap.get_pixels_per_meter();
+        Ok(IndexedBitMap {
+            width: bitmap.get_width(),
+            height: bitmap.get_height(),
+            palette,
+            indices,
+            colors_important,
+            x_pixels_per_meter,
+            y_pixels_per_meter,
+        })
+    }
+
+    ///
+    /// Convert the indexed image into a bitmap. The palette, the indices, the
+    /// number of important colors and the resolution are kept, so saving the
+    /// bitmap with [SaveOptions::with_keep_palette] writes the same color
+    /// table and pixel data
+    ///
+    pub fn to_bitmap(&self) -> BitMap {
+        let width = self.width.max(1) as usize;
+        let pixels: Vec<Rgba> = self
+            .indices
+            .chunks(width)
+            .rev()
+            .flatten()
+            .map(|&i| self.palette[i as usize])
+            .collect();
+        let mut bitmap = BitMap::create(self.width, self.height, pixels).unwrap();
+        bitmap.set_palette(Some(self.palette.clone())).unwrap();
+        bitmap.set_indices(Some(self.indices.clone()));
+        bitmap.set_colors_important(self.colors_important);
+        bitmap.set_pixels_per_meter(self.x_pixels_per_meter, self.y_pixels_per_meter);
+        bitmap
+    }
+
+    ///
+    /// Get the index of the color of a pixel at a specific x and y coordinate
+    ///
+    pub fn get_index(&self, x: u32, y: u32) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.indices[self.get_position(x, y)])
+    }
+
+    ///
+    /// Set the index of the color of a pixel at a specific x and y coordinate
+    ///
+    /// Fails if the coordinate is outside of the image, or if the index is
+    /// outside of the palette
+    ///
+    pub fn set_index(&mut self, x: u32, y: u32, index: u8) -> Result<(), BitmapError> {
+        if x >= self.width || y >= self.height {
+            return Err(BitmapError::OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            });
+        }
+        self.check_index(index)?;
+        let position = self.get_position(x, y);
+        self.indices[position] = index;
+        Ok(())
+    }
+
+    ///
+    /// Get the color of a pixel at a specific x and y coordinate
+    ///
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<&Rgba> {
+        self.get_index(x, y).map(|i| &self.palette[i as usize])
+    }
+
+    ///
+    /// Get the index of the color of every pixel, starting with the bottom row
+    ///
+    pub fn get_indices(&self) -> &[u8] {
+        &self.indices
+    }
+
+    ///
+    /// Get the colors the pixels point to
+    ///
+    pub fn get_palette(&self) -> &[Rgba] {
+        &self.palette
+    }
+
+    ///
+    /// Get the width of the image
+    ///
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    ///
+    /// Get the height of the image
+    ///
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    ///
+    /// Get the number of colors at the start of the palette that are important
+    /// for showing the image, zero means every color is important
+    ///
+    pub fn get_colors_important(&self) -> u32 {
+        self.colors_important
+    }
+
+    ///
+    /// Set the number of colors at the start of the palette that are
+    /// important, which is written out when the image is saved
+    ///
+    pub fn set_colors_important(&mut self, colors_important: u32) {
+        self.colors_important = colors_important;
+    }
+
+    ///
+    /// Get the horizontal and vertical resolution in pixels per meter, zero
+    /// means the resolution isn't known
+    ///
+    pub fn get_pixels_per_meter(&self) -> (u32, u32) {
+        (self.x_pixels_per_meter, self.y_pixels_per_meter)
+    }
+
+    ///
+    /// Set the horizontal and vertical resolution in pixels per meter, which
+    /// is written out when the image is saved
+    ///
+    pub fn set_pixels_per_meter(&mut self, x_pixels_per_meter: u32, y_pixels_per_meter: u32) {
+        self.x_pixels_per_meter = x_pixels_per_meter;
+        self.y_pixels_per_meter = y_pixels_per_meter;
+    }
+
+    ///
+    /// Get the position of a pixel in the list of indices. The rows are
+    /// stored upside down, the same way they are in a bitmap
+    ///
+    fn get_position(&self, x: u32, y: u32) -> usize {
+        (((self.height - y - 1) * self.width) + x) as usize
+    }
+
+    ///
+    /// Check that a palette has at least one color and at most 256
+    ///
+    fn check_palette(palette: &[Rgba]) -> Result<(), BitmapError> {
+        if palette.is_empty() || palette.len() > 256 {
+            return Err(BitmapError::InvalidArgument(
+                "A palette must have between 1 and 256 colors.",
+            ));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Check that an index points to a color in the palette
+    ///
+    fn check_index(&self, index: u8) -> Result<(), BitmapError> {
+        if index as usize >= self.palette.len() {
+            return Err(BitmapError::InvalidArgument(
+                "Index is outside of the palette.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+///
+/// This block deals with editing the palette
+///
+impl IndexedBitMap {
+    ///
+    /// Change the color of an entry in the palette, which changes the color
+    /// of every pixel that points to it
+    ///
+    pub fn set_palette_color(&mut self, index: u8, color: Rgba) -> Result<(), BitmapError> {
+        self.check_index(index)?;
+        self.palette[index as usize] = color;
+        Ok(())
+    }
+
+    ///
+    /// Add a color to the end of the palette and get back its index
+    ///
+    /// Fails if the palette already has 256 colors
+    ///
+    pub fn add_palette_color(&mut self, color: Rgba) -> Result<u8, BitmapError> {
+        if self.palette.len() == 256 {
+            return Err(BitmapError::InvalidArgument(
+                "A palette must have between 1 and 256 colors.",
+            ));
+        }
+        self.palette.push(color);
+        Ok((self.palette.len() - 1) as u8)
+    }
+
+    ///
+    /// Swap two entries of the palette. The pixels that point to them are
+    /// changed as well, so the image looks the same
+    ///
+    pub fn swap_palette_colors(&mut self, a: u8, b: u8) -> Result<(), BitmapError> {
+        self.check_index(a)?;
+        self.check_index(b)?;
+        self.palette.swap(a as usize, b as usize);
+        for index in self.indices.iter_mut() {
+            if *index == a {
+                *index = b;
+            } else if *index == b {
+                *index = a;
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Remove the entries of the palette that no pixel points to, keeping the
+    /// order of the rest, and get back the number of entries removed. The
+    /// number of important colors is lowered to match
+    ///
+    /// At least one color is always kept, so an image without any pixels
+    /// keeps the first entry
+    ///
+    pub fn compact_palette(&mut self) -> usize {
+        let mut used = [false; 256];
+        for &index in &self.indices {
+            used[index as usize] = true;
+        }
+        if self.indices.is_empty() {
+            used[0] = true;
+        }
+        let mut remap = [0u8; 256];
+        let mut palette = Vec::new();
+        let mut colors_important = 0;
+        for (i, color) in self.palette.iter().enumerate() {
+            if !used[i] {
+                continue;
+            }
+            if (i as u32) < self.colors_important {
+                colors_important += 1;
+            }
+            remap[i] = palette.len() as u8;
+            palette.push(*color);
+        }
+        let removed = self.palette.len() - palette.len();
+        for index in self.indices.iter_mut() {
+            *index = remap[*index as usize];
+        }
+        self.palette = palette;
+        self.colors_important = colors_important;
+        removed
+    }
+}
+
+///
+/// This block deals with saving the image
+///
+impl IndexedBitMap {
+    ///
+    /// Save the image to a location on disk using the given options. The
+    /// palette is written as the color table and every index is kept
+    ///
+    /// If the options don't give a bit depth, the smallest one that can hold
+    /// the palette is used, and 2 color images that are run length encoded
+    /// are saved as 16 color images. Fails if the bit depth doesn't use a
+    /// color table or can't hold the palette
+    ///
+    pub fn save_as(&self, filename: &str, options: &SaveOptions) -> Result<(), BitmapError> {
+        let bit_stream = self.to_bytes(options)?;
+        let mut file = std::fs::File::create(filename)?;
+        file.write_all(&bit_stream)?;
+        Ok(())
+    }
+
+    ///
+    /// Encode the image and write it to any writer. The bit depth is picked
+    /// the same way as [IndexedBitMap::save_as].
+    ///
+    pub fn write_to<W: Write>(
+        &self,
+        mut writer: W,
+        options: &SaveOptions,
+    ) -> Result<(), BitmapError> {
+        let bit_stream = self.to_bytes(options)?;
+        writer.write_all(&bit_stream)?;
+        Ok(())
+    }
+
+    ///
+    /// Encode the image into the bytes of a bitmap file. The bit depth is
+    /// picked the same way as [IndexedBitMap::save_as].
+    ///
+    pub fn to_bytes(&self, options: &SaveOptions) -> Result<Vec<u8>, BitmapError> {
+        let colors = self.palette.len();
+        let bit_depth = match options.get_bit_depth() {
+            Some(bit_depth) => bit_depth,
+            None if colors <= 2 && !options.is_rle() => BitDepth::Color2Bit,
+            None if colors <= 16 => BitDepth::Color16Bit,
+            None => BitDepth::Color256Bit,
+        };
+        let max_colors = match bit_depth.get_max_colors() {
+            Some(max_colors) => max_colors,
+            None => {
+                return Err(BitmapError::InvalidArgument(
+                    "Indexed images can only be saved as 2, 16 or 256 color images.",
+                ))
+            }
+        };
+        if colors > max_colors {
+            return Err(BitmapError::InvalidArgument(
+                "Palette has too many colors for the requested bit depth.",
+            ));
+        }
+        Ok(File::create_indexed(self, bit_depth, options).to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitDepth;
+    use super::BitMap;
+    use super::IndexedBitMap;
+    use super::Rgba;
+    use super::SaveOptions;
+
+    fn create_image() -> IndexedBitMap {
+        let palette = vec![Rgba::black(), Rgba::white(), Rgba::rgb(255, 0, 0)];
+        IndexedBitMap::create(3, 2, palette, vec![0, 1, 2, 2, 1, 1]).unwrap()
+    }
+
+    #[test]
+    fn create_indexed_image_from_the_top_row_down() {
+        let image = create_image();
+        assert_eq!(image.get_index(0, 0), Some(0));
+        assert_eq!(image.get_index(0, 1), Some(2));
+        assert_eq!(image.get_pixel(2, 0), Some(&Rgba::rgb(255, 0, 0)));
+        assert_eq!(image.get_index(3, 0), None);
+        assert_eq!(image.get_indices(), &[2, 1, 1, 0, 1, 2]);
+    }
+
+    #[test]
+    fn fail_to_create_indexed_image_with_bad_indices() {
+        let palette = vec![Rgba::black()];
+        assert!(IndexedBitMap::create(2, 1, palette.clone(), vec![0, 1]).is_err());
+        assert!(IndexedBitMap::create(2, 1, palette, vec![0]).is_err());
+        assert!(IndexedBitMap::new(2, 1, Vec::new()).is_err());
+        assert!(IndexedBitMap::new(2, 1, vec![Rgba::black(); 257]).is_err());
+    }
+
+    #[test]
+    fn set_index_inside_of_the_palette() {
+        let mut image = create_image();
+        image.set_index(1, 1, 0).unwrap();
+        assert_eq!(image.get_index(1, 1), Some(0));
+        assert!(image.set_index(1, 1, 3).is_err());
+        assert!(image.set_index(3, 1, 0).is_err());
+    }
+
+    #[test]
+    fn swap_palette_colors_without_changing_the_image() {
+        let mut image = create_image();
+        let before = image.to_bitmap();
+        image.swap_palette_colors(0, 2).unwrap();
+        assert_eq!(image.get_palette()[0], Rgba::rgb(255, 0, 0));
+        assert_eq!(image.get_index(0, 0), Some(2));
+        assert!(image.to_bitmap() == before);
+        assert!(image.swap_palette_colors(0, 3).is_err());
+    }
+
+    #[test]
+    fn recolor_palette_entry() {
+        let mut image = create_image();
+        image.set_palette_color(1, Rgba::rgb(0, 255, 0)).unwrap();
+        assert_eq!(image.get_pixel(1, 0), Some(&Rgba::rgb(0, 255, 0)));
+        assert!(image.set_palette_color(3, Rgba::black()).is_err());
+        assert_eq!(image.add_palette_color(Rgba::black()).unwrap(), 3);
+    }
+
+    #[test]
+    fn compact_unused_palette_entries() {
+        let mut image = create_image();
+        image.add_palette_color(Rgba::black()).unwrap();
+        image.set_index(0, 0, 1).unwrap();
+        image.set_colors_important(2);
+        assert_eq!(image.compact_palette(), 2);
+        assert_eq!(image.get_palette(), &[Rgba::white(), Rgba::rgb(255, 0, 0)]);
+        assert_eq!(image.get_index(0, 0), Some(0));
+        assert_eq!(image.get_index(2, 0), Some(1));
+        assert_eq!(image.get_colors_important(), 1);
+        assert_eq!(image.compact_palette(), 0);
+    }
+
+    #[test]
+    fn convert_to_and_from_bitmap() {
+        let mut image = create_image();
+        image.set_colors_important(2);
+        image.set_pixels_per_meter(100, 200);
+        let bitmap = image.to_bitmap();
+        assert_eq!(bitmap.get_pixel(2, 0), Some(&Rgba::rgb(255, 0, 0)));
+        assert_eq!(IndexedBitMap::from_bitmap(&bitmap).unwrap(), image);
+
+        let mut bitmap = BitMap::new(2, 2);
+        bitmap.set_pixel(1, 1, Rgba::black()).unwrap();
+        let image = IndexedBitMap::from_bitmap(&bitmap).unwrap();
+        assert_eq!(image.get_palette().len(), 2);
+        assert!(image.to_bitmap() == bitmap);
+    }
+
+    #[test]
+    fn save_and_read_indices_as_they_are() {
+        let mut image = create_image();
+        // the same color twice keeps both indices
+        image.set_palette_color(2, Rgba::black()).unwrap();
+        for top_down in [false, true].iter() {
+            let options = SaveOptions::new().with_top_down(*top_down);
+            let read = IndexedBitMap::from_bytes(&image.to_bytes(&options).unwrap()).unwrap();
+            assert_eq!(read, image);
+        }
+        let options = SaveOptions::new().with_bit_depth(BitDepth::Color2Bit);
+        assert!(image.to_bytes(&options).is_err());
+        let options = SaveOptions::new().with_bit_depth(BitDepth::AllColors);
+        assert!(image.to_bytes(&options).is_err());
+    }
+}
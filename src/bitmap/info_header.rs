@@ -1,5 +1,10 @@
 use super::bit_depth::BitDepth;
-use super::file_header::FileHeader;
+use super::bit_fields::BitFields;
+use super::compression::Compression;
+use super::error::BitmapError;
+use super::header_version::HeaderVersion;
+use super::rgb_quad::RgbQuad;
+use super::save_options::SaveOptions;
 use super::util;
 
 use super::image::BitMap;
@@ -8,9 +13,10 @@ pub struct InfoHeader {
     /// specifies the size of the BitMapFileHeader structure, in bytes
     size: u32,
     /// specifies the width of the image, in pixels
-    width: u32,
-    /// specifies the height of the image, in pixels
-    height: u32,
+    width: i32,
+    /// specifies the height of the image, in pixels. If the height is negative
+    /// the rows are stored from the top of the image to the bottom
+    height: i32,
     /// specifies the number of planes of the target device, must be set to zero
     planes: u16,
     /// specifies the number of bits per pixel
@@ -18,7 +24,9 @@ pub struct InfoHeader {
     ///  - 1 (black / white)
     ///  - 4 (16 colors)
     ///  - 8 (256 colors)
+    ///  - 16 (32768 or 65536 colors)
     ///  - 24 (16.7 million colors)
+    ///  - 32 (16.7 million colors and transparency)
     bit_depth: u16,
     /// specifies the type of compression, usually set to zero (no compression)
     compression: u32,
@@ -37,20 +45,54 @@ pub struct InfoHeader {
     /// specifies the number of color that are 'important' for the bitmap, if set
     /// to zero, all colors are important
     colors_important: u32,
+    /// (V4+) color mask that specifies the red component of each pixel, only
+    /// valid if compression is set to bit fields. When the header is a
+    /// BITMAPINFOHEADER the masks are stored directly after the header.
+    red_mask: u32,
+    /// (V4+) color mask that specifies the green component of each pixel
+    green_mask: u32,
+    /// (V4+) color mask that specifies the blue component of each pixel
+    blue_mask: u32,
+    /// (V4+) color mask that specifies the alpha component of each pixel
+    alpha_mask: u32,
+    /// (V4+) color space of the bitmap, usually 'sRGB'
+    cs_type: u32,
+    /// (V4+) x, y and z coordinates of the red, green and blue endpoints for
+    /// the logical color space. Only used if cs_type is calibrated RGB
+    endpoints: [u32; 9],
+    /// (V4+) toned response curve for red
+    gamma_red: u32,
+    /// (V4+) toned response curve for green
+    gamma_green: u32,
+    /// (V4+) toned response curve for blue
+    gamma_blue: u32,
+    /// (V5) rendering intent for the bitmap
+    intent: u32,
+    /// (V5) offset from the start of the info header to the color profile data
+    profile_data: u32,
+    /// (V5) size of the embedded color profile data, in bytes
+    profile_size: u32,
+    /// (V5) reserved, must be zero
+    reserved: u32,
 }
 
 ///
 /// Used for constants
 ///
 impl InfoHeader {
-    pub fn from_slice_range() -> std::ops::Range<usize> {
-        let prefix = FileHeader::estimated_byte_size();
-        prefix..(prefix + InfoHeader::estimated_byte_size())
+    pub fn size_field_byte_size() -> usize {
+        4
     }
 
-    pub fn estimated_byte_size() -> usize {
-        40
-    }
+    /// sRGB color space, written as the characters 'sRGB'
+    const LCS_SRGB: u32 = 0x7352_4742;
+
+    /// Rendering intent for pictures (perceptual)
+    const LCS_GM_IMAGES: u32 = 4;
+
+    /// Largest color table that is read in, images deeper then 8 bits only
+    /// keep a color table as a hint for displays with fewer colors
+    const MAX_COLOR_TABLE_SIZE: u32 = 256;
 }
 
 ///
@@ -58,27 +100,103 @@ impl InfoHeader {
 ///
 impl InfoHeader {
     ///
-    /// Create a header based on a bitmap.
+    /// Create a header of a specific version based on a bitmap, see
+    /// [InfoHeader::new] for how the options change the header
     ///
-    pub fn from(bitmap: &BitMap, bit_depth: BitDepth) -> InfoHeader {
+    pub fn from(bitmap: &BitMap, bit_depth: BitDepth, options: &SaveOptions) -> InfoHeader {
         let colors_used = match bit_depth {
             BitDepth::Color2Bit | BitDepth::Color16Bit | BitDepth::Color256Bit => {
                 bitmap.get_all_unique_colors().len()
             }
             _ => 0,
         } as u32;
+        let mut header = InfoHeader::new(
+            bitmap.get_width(),
+            bitmap.get_height(),
+            bit_depth,
+            colors_used,
+            options,
+        );
+        let (x_pixels_per_meter, y_pixels_per_meter) = bitmap.get_pixels_per_meter();
+        header.x_pixels_per_meter = x_pixels_per_meter;
+        header.y_pixels_per_meter = y_pixels_per_meter;
+        header
+    }
+
+    ///
+    /// Create a header of a specific version for an image of the given size
+    /// that has the given number of colors in its color table.
+    ///
+    /// V4 and V5 headers are written using the sRGB color space. The channel
+    /// masks are only filled in for 32 bit images, where they describe the
+    /// default BGRA byte order. If an alpha mask is requested, 32 bit images
+    /// are compressed using bit fields so other programs respect the alpha.
+    /// 16 bit images are stored as 5-5-5 unless 5-6-5 is requested, in which
+    /// case the masks are always written. 16 and 256 color images are run
+    /// length encoded if requested. Uncompressed images are written top-down
+    /// if requested.
+    ///
+    pub fn new(
+        width: u32,
+        height: u32,
+        bit_depth: BitDepth,
+        colors_used: u32,
+        options: &SaveOptions,
+    ) -> InfoHeader {
+        let version = options.get_header();
+        let compression = match bit_depth {
+            BitDepth::Color16Bit if options.is_rle() => Compression::Rle4,
+            BitDepth::Color256Bit if options.is_rle() => Compression::Rle8,
+            BitDepth::AllColorsAndShades if options.has_alpha_mask() => Compression::BitFields,
+            BitDepth::HighColor if options.is_rgb565() => Compression::BitFields,
+            _ => Compression::Rgb,
+        };
+        let bit_fields = match (version, bit_depth) {
+            (_, BitDepth::HighColor) if options.is_rgb565() => BitFields::rgb565(),
+            (HeaderVersion::Info, _) => BitFields::new(0, 0, 0, 0),
+            (_, BitDepth::HighColor) => BitFields::rgb555(),
+            (_, BitDepth::AllColorsAndShades) => BitFields::bgra(),
+            _ => BitFields::new(0, 0, 0, 0),
+        };
+        // compressed images can't be stored top-down
+        let height = match compression {
+            Compression::Rle8 | Compression::Rle4 => height as i32,
+            _ if options.is_top_down() => -(height as i32),
+            _ => height as i32,
+        };
+        let cs_type = match version {
+            HeaderVersion::Info => 0,
+            _ => InfoHeader::LCS_SRGB,
+        };
+        let intent = match version {
+            HeaderVersion::V5 => InfoHeader::LCS_GM_IMAGES,
+            _ => 0,
+        };
         InfoHeader {
-            size: 40,
-            width: bitmap.get_width(),
-            height: bitmap.get_height(),
+            size: version as u32,
+            width: width as i32,
+            height,
             bit_depth: bit_depth as u16,
             planes: 1,
-            compression: 0,
+            compression: compression as u32,
             size_image: 0,
             x_pixels_per_meter: 0,
             y_pixels_per_meter: 0,
             colors_used,
             colors_important: 0,
+            red_mask: bit_fields.get_red(),
+            green_mask: bit_fields.get_green(),
+            blue_mask: bit_fields.get_blue(),
+            alpha_mask: bit_fields.get_alpha(),
+            cs_type,
+            endpoints: [0; 9],
+            gamma_red: 0,
+            gamma_green: 0,
+            gamma_blue: 0,
+            intent,
+            profile_data: 0,
+            profile_size: 0,
+            reserved: 0,
         }
     }
 
@@ -88,8 +206,8 @@ impl InfoHeader {
     /// Bytes should be in correct order
     ///
     /// 1. size   as a u32
-    /// 2. width  as a u32
-    /// 3. height as a u32
+    /// 2. width  as a i32
+    /// 3. height as a i32, negative if the rows are stored top-down
     /// 4. planes as a u16
     /// 5. bit_depth as a u16
     /// 6. compression as a u32
@@ -99,16 +217,49 @@ impl InfoHeader {
     /// 10. colors_used as a u32
     /// 11. colors_important as a u32
     ///
-    pub fn from_slice(bit_stream: &[u8]) -> Result<InfoHeader, &'static str> {
+    /// If the size of the header is 108 (V4) or 124 (V5), the following fields
+    /// are read in as well
+    ///
+    /// 12. red_mask, green_mask, blue_mask, alpha_mask as u32's
+    /// 13. cs_type as a u32
+    /// 14. endpoints as 9 u32's
+    /// 15. gamma_red, gamma_green, gamma_blue as u32's
+    /// 16. (V5) intent, profile_data, profile_size, reserved as u32's
+    ///
+    /// If the header is a BITMAPINFOHEADER that uses bit fields, the red, green,
+    /// blue (and alpha) masks that follow the header are read in as well
+    ///
+    pub fn from_slice(bit_stream: &[u8]) -> Result<InfoHeader, BitmapError> {
         // starts at 14
         let mut i: usize = 0;
-        if bit_stream.len() < 40 {
-            return Err("Error reading info header, not enough data found!");
+        if bit_stream.len() < InfoHeader::size_field_byte_size() {
+            return Err(BitmapError::Truncated {
+                section: "info header",
+                offset: 0,
+                needed: InfoHeader::size_field_byte_size(),
+                available: bit_stream.len(),
+            });
+        }
+        let size = util::byte_slice_to_u32(bit_stream, &mut i);
+        let version = match HeaderVersion::from_byte_size(size) {
+            Some(v) => v,
+            None => return Err(BitmapError::UnsupportedHeader(size)),
+        };
+        if bit_stream.len() < size as usize {
+            return Err(BitmapError::Truncated {
+                section: "info header",
+                offset: 0,
+                needed: size as usize,
+                available: bit_stream.len(),
+            });
         }
-        Ok(InfoHeader {
-            size: util::byte_slice_to_u32(bit_stream, &mut i),
-            width: util::byte_slice_to_u32(bit_stream, &mut i),
-            height: util::byte_slice_to_u32(bit_stream, &mut i),
+        if version == HeaderVersion::Core || version == HeaderVersion::Os2Short {
+            return InfoHeader::from_short_slice(bit_stream, version).validate();
+        }
+        let mut header = InfoHeader {
+            size,
+            width: util::byte_slice_to_i32(bit_stream, &mut i),
+            height: util::byte_slice_to_i32(bit_stream, &mut i),
             planes: util::byte_slice_to_u16(bit_stream, &mut i),
             bit_depth: util::byte_slice_to_u16(bit_stream, &mut i),
             compression: util::byte_slice_to_u32(bit_stream, &mut i),
@@ -117,7 +268,131 @@ impl InfoHeader {
             y_pixels_per_meter: util::byte_slice_to_u32(bit_stream, &mut i),
             colors_used: util::byte_slice_to_u32(bit_stream, &mut i),
             colors_important: util::byte_slice_to_u32(bit_stream, &mut i),
-        })
+            red_mask: 0,
+            green_mask: 0,
+            blue_mask: 0,
+            alpha_mask: 0,
+            cs_type: 0,
+            endpoints: [0; 9],
+            gamma_red: 0,
+            gamma_green: 0,
+            gamma_blue: 0,
+            intent: 0,
+            profile_data: 0,
+            profile_size: 0,
+            reserved: 0,
+        };
+        if version == HeaderVersion::Os2 {
+            // OS/2 uses these values for huffman and 24 bit run length encoding
+            // instead of bit fields. The rest of the header is only used for
+            // printing, so it's skipped
+            if header.compression == 3 || header.compression == 4 {
+                return Err(BitmapError::UnsupportedCompression(header.compression));
+            }
+            return header.validate();
+        }
+        if version == HeaderVersion::Info {
+            let mask_count = header.get_trailing_mask_count();
+            if mask_count > 0 {
+                let masks = &bit_stream[size as usize..];
+                let bit_fields = BitFields::from_slice(masks, mask_count == 4)
+                    .map_err(|why| why.offset_by(size as usize))?;
+                header.red_mask = bit_fields.get_red();
+                header.green_mask = bit_fields.get_green();
+                header.blue_mask = bit_fields.get_blue();
+                header.alpha_mask = bit_fields.get_alpha();
+            }
+            return header.validate();
+        }
+        header.red_mask = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.green_mask = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.blue_mask = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.alpha_mask = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.cs_type = util::byte_slice_to_u32(bit_stream, &mut i);
+        for endpoint in header.endpoints.iter_mut() {
+            *endpoint = util::byte_slice_to_u32(bit_stream, &mut i);
+        }
+        header.gamma_red = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.gamma_green = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.gamma_blue = util::byte_slice_to_u32(bit_stream, &mut i);
+        if version == HeaderVersion::V4 {
+            return header.validate();
+        }
+        header.intent = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.profile_data = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.profile_size = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.reserved = util::byte_slice_to_u32(bit_stream, &mut i);
+        header.validate()
+    }
+
+    ///
+    /// Check that the values read in from a header describe an image that can
+    /// be decoded
+    ///
+    /// Fails if the width is negative, if the image is run length encoded and
+    /// top-down, or if the image has more pixels then can be counted with a
+    /// u32
+    ///
+    fn validate(self) -> Result<InfoHeader, BitmapError> {
+        if self.width < 0 {
+            return Err(BitmapError::InvalidData("width can't be negative"));
+        }
+        if self.is_top_down() && self.is_run_length_encoded() {
+            return Err(BitmapError::InvalidData(
+                "run length encoded images can't be top-down",
+            ));
+        }
+        if self.get_width().checked_mul(self.get_height()).is_none() {
+            return Err(BitmapError::InvalidData(
+                "image has too many pixels, width times height must fit in 32 bits",
+            ));
+        }
+        Ok(self)
+    }
+
+    ///
+    /// Read in one of the short OS/2 headers, which only store the width,
+    /// height, planes and bit depth. A BITMAPCOREHEADER stores the width and
+    /// height as u16's, while the short BITMAPINFOHEADER2 stores them as u32's
+    ///
+    fn from_short_slice(bit_stream: &[u8], version: HeaderVersion) -> InfoHeader {
+        let mut i = InfoHeader::size_field_byte_size();
+        let (width, height) = match version {
+            HeaderVersion::Core => (
+                util::byte_slice_to_u16(bit_stream, &mut i) as i32,
+                util::byte_slice_to_u16(bit_stream, &mut i) as i32,
+            ),
+            _ => (
+                util::byte_slice_to_i32(bit_stream, &mut i),
+                util::byte_slice_to_i32(bit_stream, &mut i),
+            ),
+        };
+        InfoHeader {
+            size: version as u32,
+            width,
+            height,
+            planes: util::byte_slice_to_u16(bit_stream, &mut i),
+            bit_depth: util::byte_slice_to_u16(bit_stream, &mut i),
+            compression: Compression::Rgb as u32,
+            size_image: 0,
+            x_pixels_per_meter: 0,
+            y_pixels_per_meter: 0,
+            colors_used: 0,
+            colors_important: 0,
+            red_mask: 0,
+            green_mask: 0,
+            blue_mask: 0,
+            alpha_mask: 0,
+            cs_type: 0,
+            endpoints: [0; 9],
+            gamma_red: 0,
+            gamma_green: 0,
+            gamma_blue: 0,
+            intent: 0,
+            profile_data: 0,
+            profile_size: 0,
+            reserved: 0,
+        }
     }
 
     ///
@@ -131,24 +406,178 @@ impl InfoHeader {
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
         bytes.extend_from_slice(&self.size.to_le_bytes());
-        bytes.extend_from_slice(&self.width.to_le_bytes());
-        bytes.extend_from_slice(&self.height.to_le_bytes());
+        if self.size == HeaderVersion::Core as u32 {
+            bytes.extend_from_slice(&(self.width as u16).to_le_bytes());
+            bytes.extend_from_slice(&(self.height as u16).to_le_bytes());
+        } else {
+            bytes.extend_from_slice(&self.width.to_le_bytes());
+            bytes.extend_from_slice(&self.height.to_le_bytes());
+        }
         bytes.extend_from_slice(&self.planes.to_le_bytes());
         bytes.extend_from_slice(&self.bit_depth.to_le_bytes());
+        if self.size < HeaderVersion::Info as u32 {
+            return bytes;
+        }
         bytes.extend_from_slice(&self.compression.to_le_bytes());
         bytes.extend_from_slice(&self.size_image.to_le_bytes());
         bytes.extend_from_slice(&self.x_pixels_per_meter.to_le_bytes());
         bytes.extend_from_slice(&self.y_pixels_per_meter.to_le_bytes());
         bytes.extend_from_slice(&self.colors_used.to_le_bytes());
         bytes.extend_from_slice(&self.colors_important.to_le_bytes());
+        if self.size == HeaderVersion::Os2 as u32 {
+            // the OS/2 only fields aren't kept when reading, so write them
+            // using their defaults
+            bytes.resize(HeaderVersion::Os2 as usize, 0);
+            return bytes;
+        }
+        let mask_count = self.get_trailing_mask_count();
+        if mask_count > 0 {
+            let bit_fields = BitFields::new(
+                self.red_mask,
+                self.green_mask,
+                self.blue_mask,
+                self.alpha_mask,
+            );
+            bytes.append(&mut bit_fields.as_bytes(mask_count == 4));
+        }
+        if self.size >= HeaderVersion::V4 as u32 {
+            bytes.extend_from_slice(&self.red_mask.to_le_bytes());
+            bytes.extend_from_slice(&self.green_mask.to_le_bytes());
+            bytes.extend_from_slice(&self.blue_mask.to_le_bytes());
+            bytes.extend_from_slice(&self.alpha_mask.to_le_bytes());
+            bytes.extend_from_slice(&self.cs_type.to_le_bytes());
+            for endpoint in &self.endpoints {
+                bytes.extend_from_slice(&endpoint.to_le_bytes());
+            }
+            bytes.extend_from_slice(&self.gamma_red.to_le_bytes());
+            bytes.extend_from_slice(&self.gamma_green.to_le_bytes());
+            bytes.extend_from_slice(&self.gamma_blue.to_le_bytes());
+        }
+        if self.size >= HeaderVersion::V5 as u32 {
+            bytes.extend_from_slice(&self.intent.to_le_bytes());
+            bytes.extend_from_slice(&self.profile_data.to_le_bytes());
+            bytes.extend_from_slice(&self.profile_size.to_le_bytes());
+            bytes.extend_from_slice(&self.reserved.to_le_bytes());
+        }
         bytes
     }
 
     ///
-    /// Get the size of the information header in bytes
+    /// Get the size of the information header in bytes, including any masks
+    /// that are stored after it
     ///
     pub fn get_byte_size(&self) -> u32 {
-        self.size
+        self.size + self.get_trailing_mask_count() * BitFields::single_mask_size() as u32
+    }
+
+    ///
+    /// Get the number of masks that are stored directly after the header.
+    ///
+    /// Only a BITMAPINFOHEADER stores its masks outside of the header, newer
+    /// versions of the header have the masks built in.
+    ///
+    fn get_trailing_mask_count(&self) -> u32 {
+        InfoHeader::trailing_mask_count(self.size, self.compression)
+    }
+
+    ///
+    /// Get the number of bytes used by the masks that follow the header at the
+    /// start of the given bytes, without reading in the rest of the header
+    ///
+    pub fn trailing_mask_byte_size(bit_stream: &[u8]) -> usize {
+        let mut i = 0;
+        let size = util::byte_slice_to_u32(bit_stream, &mut i);
+        // the compression comes after the size, width, height, planes and bit
+        // depth
+        let mut i = 16;
+        let compression = util::byte_slice_to_u32(bit_stream, &mut i);
+        InfoHeader::trailing_mask_count(size, compression) as usize * BitFields::single_mask_size()
+    }
+
+    ///
+    /// Get the number of masks stored after a header of the given size that
+    /// uses the given compression
+    ///
+    fn trailing_mask_count(size: u32, compression: u32) -> u32 {
+        if size != HeaderVersion::Info as u32 {
+            return 0;
+        }
+        match Compression::from_u32(compression) {
+            Some(Compression::BitFields) => 3,
+            Some(Compression::AlphaBitFields) => 4,
+            _ => 0,
+        }
+    }
+
+    ///
+    /// Get the compression used to store the pixels
+    ///
+    pub fn get_compression(&self) -> Option<Compression> {
+        Compression::from_u32(self.compression)
+    }
+
+    ///
+    /// Check if the pixels are compressed using run length encoding
+    ///
+    pub fn is_run_length_encoded(&self) -> bool {
+        matches!(
+            self.get_compression(),
+            Some(Compression::Rle8) | Some(Compression::Rle4)
+        )
+    }
+
+    ///
+    /// Set the number of bytes used to store the pixels
+    ///
+    pub fn set_size_image(&mut self, size_image: u32) {
+        self.size_image = size_image;
+    }
+
+    ///
+    /// Set the horizontal and vertical resolution in pixels per meter
+    ///
+    pub fn set_pixels_per_meter(&mut self, x_pixels_per_meter: u32, y_pixels_per_meter: u32) {
+        self.x_pixels_per_meter = x_pixels_per_meter;
+        self.y_pixels_per_meter = y_pixels_per_meter;
+    }
+
+    ///
+    /// Set the number of colors that are important for showing the image
+    ///
+    pub fn set_colors_important(&mut self, colors_important: u32) {
+        self.colors_important = colors_important;
+    }
+
+    ///
+    /// Get the raw compression value stored in the header, even if it isn't
+    /// supported
+    ///
+    pub fn get_compression_value(&self) -> u32 {
+        self.compression
+    }
+
+    ///
+    /// Get the raw number of bits per pixel stored in the header, even if it
+    /// isn't supported
+    ///
+    pub fn get_bits_per_pixel(&self) -> u16 {
+        self.bit_depth
+    }
+
+    ///
+    /// Get the masks used to read each pixel if the image is stored using bit
+    /// fields
+    ///
+    pub fn get_bit_fields(&self) -> Option<BitFields> {
+        match self.get_compression() {
+            Some(c) if c.is_bit_fields() => Some(BitFields::new(
+                self.red_mask,
+                self.green_mask,
+                self.blue_mask,
+                self.alpha_mask,
+            )),
+            _ => None,
+        }
     }
 
     ///
@@ -159,6 +588,7 @@ impl InfoHeader {
             1 => Some(BitDepth::Color2Bit),
             4 => Some(BitDepth::Color16Bit),
             8 => Some(BitDepth::Color256Bit),
+            16 => Some(BitDepth::HighColor),
             24 => Some(BitDepth::AllColors),
             32 => Some(BitDepth::AllColorsAndShades),
             _ => None,
@@ -169,14 +599,60 @@ impl InfoHeader {
     /// Get the width of the image
     ///
     pub fn get_width(&self) -> u32 {
-        self.width
+        self.width.unsigned_abs()
     }
 
     ///
-    /// Get the height of the image
+    /// Get the height of the image, no matter which way the rows are stored
     ///
     pub fn get_height(&self) -> u32 {
-        self.height
+        self.height.unsigned_abs()
+    }
+
+    ///
+    /// Check if the rows are stored from the top of the image to the bottom
+    ///
+    pub fn is_top_down(&self) -> bool {
+        self.height < 0
+    }
+
+    ///
+    /// Get the number of bytes used to store each row of the image, including
+    /// the padding that makes each row a multiple of 4 bytes long
+    ///
+    pub fn get_row_byte_size(&self) -> usize {
+        let bit_width = self.get_width() as usize * self.bit_depth as usize;
+        (bit_width + 31) / 32 * 4
+    }
+
+    ///
+    /// Create a copy of the header that describes a band of rows taken out of
+    /// the image, stored in the same order as they are in the file
+    ///
+    pub fn for_rows(&self, rows: u32) -> InfoHeader {
+        InfoHeader {
+            height: rows as i32,
+            ..*self
+        }
+    }
+
+    ///
+    /// Create a copy of the header of an image stored in an icon or cursor
+    /// that only describes the pixels, leaving out the rows of the mask that
+    /// are counted in the height. 32 bit images in icons store a full byte of
+    /// alpha, so they're described using bit fields
+    ///
+    pub fn for_icon_pixels(&self) -> InfoHeader {
+        let mut header = self.for_rows(self.get_height() / 2);
+        if header.bit_depth == 32 && header.compression == Compression::Rgb as u32 {
+            let bit_fields = BitFields::bgra();
+            header.compression = Compression::BitFields as u32;
+            header.red_mask = bit_fields.get_red();
+            header.green_mask = bit_fields.get_green();
+            header.blue_mask = bit_fields.get_blue();
+            header.alpha_mask = bit_fields.get_alpha();
+        }
+        header
     }
 
     ///
@@ -185,6 +661,76 @@ impl InfoHeader {
     pub fn get_colors_used(&self) -> u32 {
         self.colors_used
     }
+
+    ///
+    /// Get the number of colors that are important for showing the image, zero
+    /// means every color is important
+    ///
+    pub fn get_colors_important(&self) -> u32 {
+        self.colors_important
+    }
+
+    ///
+    /// Get the number of bytes used to store the pixels, which can be zero for
+    /// uncompressed images
+    ///
+    pub fn get_size_image(&self) -> u32 {
+        self.size_image
+    }
+
+    ///
+    /// Get the horizontal resolution of the image in pixels per meter
+    ///
+    pub fn get_x_pixels_per_meter(&self) -> u32 {
+        self.x_pixels_per_meter
+    }
+
+    ///
+    /// Get the vertical resolution of the image in pixels per meter
+    ///
+    pub fn get_y_pixels_per_meter(&self) -> u32 {
+        self.y_pixels_per_meter
+    }
+
+    ///
+    /// Get the number of colors stored in the color table. If the number of
+    /// colors used isn't given, images with 256 colors or less store every
+    /// color that the bit depth allows.
+    ///
+    /// The number of colors used is capped at the number of colors the bit
+    /// depth allows, and at 256 for deeper images, so that a corrupt header
+    /// can't make the color table swallow the pixels
+    ///
+    pub fn get_color_table_size(&self) -> u32 {
+        let max_colors = if self.bit_depth <= 8 {
+            1 << self.bit_depth
+        } else {
+            InfoHeader::MAX_COLOR_TABLE_SIZE
+        };
+        match self.get_colors_used() {
+            0 if self.bit_depth <= 8 => max_colors,
+            colors_used => colors_used.min(max_colors),
+        }
+    }
+
+    ///
+    /// Get the number of bytes used to store each color in the color table.
+    /// A BITMAPCOREHEADER stores colors as blue, green and red, every other
+    /// header stores an extra reserved byte
+    ///
+    pub fn get_color_table_entry_size(&self) -> usize {
+        match self.get_version() {
+            Some(HeaderVersion::Core) => RgbQuad::single_rgb_triple_size(),
+            _ => RgbQuad::single_rgb_quad_size(),
+        }
+    }
+
+    ///
+    /// Get the version of the header
+    ///
+    pub fn get_version(&self) -> Option<HeaderVersion> {
+        HeaderVersion::from_byte_size(self.size)
+    }
 }
 
 #[cfg(debug_assertions)]
@@ -196,7 +742,10 @@ impl std::fmt::Display for InfoHeader {
         Header Size: {}, Width: {}, Height: {}, Bit Count: {}
         Planes: {}, compression: {}, image size: {}
         x pxls per meter: {}, y pxls per meter: {}
-        colors_used: {} colors_important: {}",
+        colors_used: {} colors_important: {}
+        masks (r, g, b, a): {:#x}, {:#x}, {:#x}, {:#x}
+        color space: {:#x}, gamma (r, g, b): {}, {}, {}
+        intent: {}, profile offset: {}, profile size: {}",
             self.size,
             self.width,
             self.height,
@@ -207,7 +756,18 @@ impl std::fmt::Display for InfoHeader {
             self.x_pixels_per_meter,
             self.y_pixels_per_meter,
             self.colors_used,
-            self.colors_important
+            self.colors_important,
+            self.red_mask,
+            self.green_mask,
+            self.blue_mask,
+            self.alpha_mask,
+            self.cs_type,
+            self.gamma_red,
+            self.gamma_green,
+            self.gamma_blue,
+            self.intent,
+            self.profile_data,
+            self.profile_size
         )
     }
 }
@@ -215,38 +775,43 @@ impl std::fmt::Display for InfoHeader {
 #[cfg(test)]
 mod test {
     use super::BitDepth;
+    use super::BitFields;
     use super::BitMap;
+    use super::Compression;
+    use super::HeaderVersion;
     use super::InfoHeader;
+    use super::SaveOptions;
+    use crate::bitmap::rgba::Rgba;
 
     #[test]
     fn get_correct_bit_depth() {
         let b = BitMap::new(10, 10);
         assert_eq!(
-            InfoHeader::from(&b, BitDepth::Color2Bit)
+            InfoHeader::from(&b, BitDepth::Color2Bit, &SaveOptions::new())
                 .get_bit_depth()
                 .unwrap(),
             BitDepth::Color2Bit
         );
         assert_eq!(
-            InfoHeader::from(&b, BitDepth::Color16Bit)
+            InfoHeader::from(&b, BitDepth::Color16Bit, &SaveOptions::new())
                 .get_bit_depth()
                 .unwrap(),
             BitDepth::Color16Bit
         );
         assert_eq!(
-            InfoHeader::from(&b, BitDepth::Color256Bit)
+            InfoHeader::from(&b, BitDepth::Color256Bit, &SaveOptions::new())
                 .get_bit_depth()
                 .unwrap(),
             BitDepth::Color256Bit
         );
         assert_eq!(
-            InfoHeader::from(&b, BitDepth::AllColors)
+            InfoHeader::from(&b, BitDepth::AllColors, &SaveOptions::new())
                 .get_bit_depth()
                 .unwrap(),
             BitDepth::AllColors
         );
         assert_eq!(
-            InfoHeader::from(&b, BitDepth::AllColorsAndShades)
+            InfoHeader::from(&b, BitDepth::AllColorsAndShades, &SaveOptions::new())
                 .get_bit_depth()
                 .unwrap(),
             BitDepth::AllColorsAndShades
@@ -256,14 +821,14 @@ mod test {
     #[test]
     fn get_info_size_in_bytes_after_bitmap_conversion() {
         let b = BitMap::new(10, 10);
-        let data = InfoHeader::from(&b, BitDepth::AllColors);
+        let data = InfoHeader::from(&b, BitDepth::AllColors, &SaveOptions::new());
         assert_eq!(data.get_byte_size(), 40);
     }
 
     #[test]
     fn get_width_and_height_after_bitmap_conversion() {
         let b = BitMap::new(10, 10);
-        let data = InfoHeader::from(&b, BitDepth::AllColors);
+        let data = InfoHeader::from(&b, BitDepth::AllColors, &SaveOptions::new());
         assert_eq!(data.get_width(), 10);
         assert_eq!(data.get_height(), 10);
     }
@@ -271,7 +836,169 @@ mod test {
     #[test]
     fn get_colors_used_after_bitmap_conversion_24_bit() {
         let b = BitMap::new(10, 10);
-        let data = InfoHeader::from(&b, BitDepth::AllColors);
+        let data = InfoHeader::from(&b, BitDepth::AllColors, &SaveOptions::new());
         assert_eq!(data.get_colors_used(), 0);
     }
+
+    #[test]
+    fn get_color_table_size() {
+        let mut b = BitMap::new(10, 10);
+        b.set_pixel(0, 0, Rgba::black()).unwrap();
+        let data = InfoHeader::from(&b, BitDepth::Color256Bit, &SaveOptions::new());
+        assert_eq!(data.get_color_table_size(), 2);
+        // without the number of colors used, the color table is full
+        let mut bytes = data.as_bytes();
+        bytes[32..36].copy_from_slice(&[0, 0, 0, 0]);
+        let data = InfoHeader::from_slice(&bytes).unwrap();
+        assert_eq!(data.get_color_table_size(), 256);
+        let data = InfoHeader::from(&b, BitDepth::AllColors, &SaveOptions::new());
+        assert_eq!(data.get_color_table_size(), 0);
+    }
+
+    #[test]
+    fn get_color_table_size_is_capped() {
+        let b = BitMap::new(10, 10);
+        let data = InfoHeader::from(&b, BitDepth::Color16Bit, &SaveOptions::new());
+        let mut bytes = data.as_bytes();
+        bytes[32..36].copy_from_slice(&300u32.to_le_bytes());
+        let data = InfoHeader::from_slice(&bytes).unwrap();
+        assert_eq!(data.get_color_table_size(), 16);
+
+        let data = InfoHeader::from(&b, BitDepth::AllColors, &SaveOptions::new());
+        let mut bytes = data.as_bytes();
+        bytes[32..36].copy_from_slice(&u32::MAX.to_le_bytes());
+        let data = InfoHeader::from_slice(&bytes).unwrap();
+        assert_eq!(data.get_color_table_size(), 256);
+    }
+
+    #[test]
+    fn read_os2_headers() {
+        // BITMAPCOREHEADER
+        let bytes = [12, 0, 0, 0, 3, 0, 2, 0, 1, 0, 8, 0];
+        let data = InfoHeader::from_slice(&bytes).unwrap();
+        assert_eq!(data.get_version(), Some(HeaderVersion::Core));
+        assert_eq!(data.get_width(), 3);
+        assert_eq!(data.get_height(), 2);
+        assert_eq!(data.get_bit_depth(), Some(BitDepth::Color256Bit));
+        assert_eq!(data.get_compression(), Some(Compression::Rgb));
+        assert_eq!(data.get_color_table_size(), 256);
+        assert_eq!(data.get_color_table_entry_size(), 3);
+        assert_eq!(data.get_byte_size(), 12);
+        assert_eq!(data.as_bytes(), bytes.to_vec());
+        assert!(InfoHeader::from_slice(&bytes[..11]).is_err());
+
+        // short BITMAPINFOHEADER2
+        let bytes = [16, 0, 0, 0, 3, 0, 0, 0, 2, 0, 0, 0, 1, 0, 24, 0];
+        let data = InfoHeader::from_slice(&bytes).unwrap();
+        assert_eq!(data.get_version(), Some(HeaderVersion::Os2Short));
+        assert_eq!(data.get_width(), 3);
+        assert_eq!(data.get_height(), 2);
+        assert_eq!(data.get_bit_depth(), Some(BitDepth::AllColors));
+        assert_eq!(data.get_color_table_entry_size(), 4);
+        assert_eq!(data.as_bytes(), bytes.to_vec());
+
+        // BITMAPINFOHEADER2
+        let b = BitMap::new(3, 2);
+        let mut bytes = InfoHeader::from(&b, BitDepth::AllColors, &SaveOptions::new()).as_bytes();
+        bytes[0] = 64;
+        bytes.resize(64, 0);
+        let data = InfoHeader::from_slice(&bytes).unwrap();
+        assert_eq!(data.get_version(), Some(HeaderVersion::Os2));
+        assert_eq!(data.get_byte_size(), 64);
+        assert_eq!(data.get_bit_fields(), None);
+        assert_eq!(data.as_bytes(), bytes);
+        // huffman encoding
+        bytes[16] = 3;
+        assert!(InfoHeader::from_slice(&bytes).is_err());
+    }
+
+    #[test]
+    fn get_info_size_in_bytes_for_all_header_versions() {
+        let b = BitMap::new(10, 10);
+        let v4 = SaveOptions::new().with_header(HeaderVersion::V4);
+        let v5 = SaveOptions::new().with_header(HeaderVersion::V5);
+        let data = InfoHeader::from(&b, BitDepth::AllColors, &v4);
+        assert_eq!(data.get_byte_size(), 108);
+        assert_eq!(data.as_bytes().len(), 108);
+        let data = InfoHeader::from(&b, BitDepth::AllColors, &v5);
+        assert_eq!(data.get_byte_size(), 124);
+        assert_eq!(data.as_bytes().len(), 124);
+    }
+
+    #[test]
+    fn read_v4_and_v5_headers_from_bytes() {
+        let b = BitMap::new(3, 7);
+        for version in &[HeaderVersion::Info, HeaderVersion::V4, HeaderVersion::V5] {
+            let options = SaveOptions::new().with_header(*version);
+            let bytes = InfoHeader::from(&b, BitDepth::AllColorsAndShades, &options).as_bytes();
+            let data = InfoHeader::from_slice(&bytes).unwrap();
+            assert_eq!(data.get_byte_size(), *version as u32);
+            assert_eq!(data.get_width(), 3);
+            assert_eq!(data.get_height(), 7);
+            assert_eq!(data.get_bit_depth().unwrap(), BitDepth::AllColorsAndShades);
+            assert_eq!(data.as_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn fail_to_read_header_with_unknown_size() {
+        let b = BitMap::new(10, 10);
+        let v4 = SaveOptions::new().with_header(HeaderVersion::V4);
+        let v5 = SaveOptions::new().with_header(HeaderVersion::V5);
+        let mut bytes = InfoHeader::from(&b, BitDepth::AllColors, &v4).as_bytes();
+        bytes[0] = 100;
+        assert!(InfoHeader::from_slice(&bytes).is_err());
+        let bytes = InfoHeader::from(&b, BitDepth::AllColors, &v5).as_bytes();
+        assert!(InfoHeader::from_slice(&bytes[..108]).is_err());
+    }
+
+    #[test]
+    fn read_bit_fields_stored_after_info_header() {
+        let b = BitMap::new(10, 10);
+        let mut bytes =
+            InfoHeader::from(&b, BitDepth::AllColorsAndShades, &SaveOptions::new()).as_bytes();
+        assert_eq!(bytes.len(), 40);
+        // switch the compression to bit fields and add the masks
+        bytes[16] = 3;
+        bytes.append(&mut BitFields::new(0xff, 0xff00, 0xff_0000, 0).as_bytes(false));
+        let data = InfoHeader::from_slice(&bytes).unwrap();
+        assert_eq!(data.get_byte_size(), 52);
+        assert_eq!(data.get_compression(), Some(Compression::BitFields));
+        assert_eq!(
+            data.get_bit_fields(),
+            Some(BitFields::new(0xff, 0xff00, 0xff_0000, 0))
+        );
+        assert_eq!(data.as_bytes(), bytes);
+        assert!(InfoHeader::from_slice(&bytes[..48]).is_err());
+    }
+
+    #[test]
+    fn alpha_mask_uses_bit_fields() {
+        let b = BitMap::new(10, 10);
+        let options = SaveOptions::new().with_alpha_mask(true);
+        let data = InfoHeader::from(&b, BitDepth::AllColorsAndShades, &options);
+        assert_eq!(data.get_byte_size(), 108);
+        assert_eq!(data.get_compression(), Some(Compression::BitFields));
+        assert_eq!(data.get_bit_fields(), Some(BitFields::bgra()));
+        let data = InfoHeader::from(&b, BitDepth::AllColors, &options);
+        assert_eq!(data.get_compression(), Some(Compression::Rgb));
+        assert_eq!(data.get_bit_fields(), None);
+    }
+
+    #[test]
+    fn icon_pixels_leave_out_the_mask() {
+        let b = BitMap::new(10, 10);
+        for bit_depth in [BitDepth::Color16Bit, BitDepth::AllColorsAndShades].iter() {
+            let header = InfoHeader::from(&b, *bit_depth, &SaveOptions::new()).for_rows(20);
+            let pixels = header.for_icon_pixels();
+            assert_eq!(pixels.get_height(), 10);
+            assert_eq!(pixels.get_width(), 10);
+            match bit_depth {
+                BitDepth::AllColorsAndShades => {
+                    assert_eq!(pixels.get_bit_fields(), Some(BitFields::bgra()))
+                }
+                _ => assert_eq!(pixels.get_bit_fields(), None),
+            }
+        }
+    }
 }
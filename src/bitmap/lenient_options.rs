@@ -0,0 +1,63 @@
+use super::decode_limits::DecodeLimits;
+use super::rgba::Rgba;
+
+///
+/// Options that change the way a partially corrupt bitmap is repaired when
+/// it's read in using the lenient decode mode
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LenientOptions {
+    /// color given to pixels that are missing or can't be read
+    fill: Rgba,
+    /// limits on the size of the image, checked before repairing anything
+    limits: DecodeLimits,
+}
+
+impl LenientOptions {
+    ///
+    /// Create the default lenient options, which fill missing pixels with
+    /// black and use the default decode limits
+    ///
+    pub fn new() -> LenientOptions {
+        LenientOptions {
+            fill: Rgba::black(),
+            limits: DecodeLimits::default(),
+        }
+    }
+
+    ///
+    /// Give pixels that are missing or can't be read this color
+    ///
+    pub fn with_fill(mut self, fill: Rgba) -> LenientOptions {
+        self.fill = fill;
+        self
+    }
+
+    ///
+    /// Fail to read images that are bigger then the given limits
+    ///
+    pub fn with_limits(mut self, limits: DecodeLimits) -> LenientOptions {
+        self.limits = limits;
+        self
+    }
+
+    ///
+    /// Get the color given to pixels that are missing or can't be read
+    ///
+    pub fn get_fill(&self) -> Rgba {
+        self.fill
+    }
+
+    ///
+    /// Get the limits on the size of the image
+    ///
+    pub fn get_limits(&self) -> &DecodeLimits {
+        &self.limits
+    }
+}
+
+impl Default for LenientOptions {
+    fn default() -> LenientOptions {
+        LenientOptions::new()
+    }
+}
@@ -1,19 +1,108 @@
 mod bit_data;
-mod bit_depth;
+
+///
+/// Number of bits used to store each pixel
+///
+pub mod bit_depth;
+mod bit_fields;
+
+///
+/// Information read from the headers of a bitmap without decoding the pixels
+///
+pub mod bitmap_info;
+
+///
+/// Compression methods used to store the pixels of a bitmap
+///
+pub mod compression;
+
+///
+/// Limits on the size of the images that are decoded
+///
+pub mod decode_limits;
+
+///
+/// Repairs made while reading in a partially corrupt bitmap
+///
+pub mod decode_warning;
+
+///
+/// Dithering used when an image is given colors from a smaller palette
+///
+pub mod dither;
+
+///
+/// Errors returned while reading, writing and editing bitmaps
+///
+pub mod error;
 mod file;
 mod file_data;
 mod file_header;
 
+///
+/// Versions of the info header that can be read and written
+///
+pub mod header_version;
+
+///
+/// Read and write Windows icons and cursors
+///
+pub mod icon;
+
 ///
 /// Read in, create and edit bitmaps
 ///
 pub mod image;
+
+///
+/// Edit 2, 16 and 256 color images through their palette
+///
+pub mod indexed_image;
 mod info_header;
+
+///
+/// Options used when reading in a partially corrupt bitmap
+///
+pub mod lenient_options;
+
+///
+/// Ways of converting images to black and white
+///
+pub mod monochrome;
+
+///
+/// Fixed palettes that images can be saved with
+///
+pub mod palette;
 mod pixel_data;
+mod pixel_set;
+
+///
+/// Reduce images to a limited number of colors
+///
+pub mod quantizer;
 mod rgb_quad;
 
 ///
 /// Color representation (Red, Green, Blue, Alpha)
 ///
 pub mod rgba;
+mod rle;
+
+///
+/// Read a bitmap one row at a time
+///
+pub mod row_reader;
+
+///
+/// Write a bitmap one row at a time
+///
+pub mod row_writer;
+
+///
+/// Options used when saving a bitmap
+///
+pub mod save_options;
+#[cfg(test)]
+mod test_util;
 mod util;
@@ -0,0 +1,216 @@
+use super::rgba::Rgba;
+
+///
+/// The way the brightness of each pixel decides if it becomes black or white
+/// when an image is converted to monochrome
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MonochromeMethod {
+    /// Pixels brighter then the given level become white, the rest black
+    Threshold(u8),
+    /// Pick the threshold that best splits the brightness of the pixels into
+    /// a dark and a light group, using Otsu's method
+    Otsu,
+    /// Compare each pixel against a repeating 2 by 2 pattern of thresholds
+    Bayer2x2,
+    /// Compare each pixel against a repeating 4 by 4 pattern of thresholds
+    Bayer4x4,
+    /// Compare each pixel against a repeating 8 by 8 pattern of thresholds,
+    /// which shows the most shades of gray
+    Bayer8x8,
+}
+
+impl MonochromeMethod {
+    ///
+    /// Get the size of the pattern of thresholds, if the method uses one
+    ///
+    fn get_pattern_size(&self) -> Option<u32> {
+        match self {
+            MonochromeMethod::Bayer2x2 => Some(2),
+            MonochromeMethod::Bayer4x4 => Some(4),
+            MonochromeMethod::Bayer8x8 => Some(8),
+            _ => None,
+        }
+    }
+}
+
+///
+/// Get how bright a pixel is, where transparent pixels are treated as if
+/// they were drawn on white
+///
+fn get_brightness(color: &Rgba) -> u8 {
+    let mut gray = *color;
+    gray.color_to_gray();
+    let alpha = color.get_alpha().min(100) as u32;
+    ((gray.get_red() as u32 * alpha + 255 * (100 - alpha) + 50) / 100) as u8
+}
+
+///
+/// Find the threshold that splits the brightness of the pixels into two
+/// groups with the least spread inside of each group
+///
+fn get_otsu_threshold(brightness: &[u8]) -> u8 {
+    let mut histogram = [0u64; 256];
+    for level in brightness {
+        histogram[*level as usize] += 1;
+    }
+    let total = brightness.len() as f64;
+    let sum: f64 = (0..256).map(|i| i as f64 * histogram[i] as f64).sum();
+    let mut dark_sum = 0.0;
+    let mut dark_count = 0.0;
+    let mut best = (0, 0.0);
+    for (level, count) in histogram.iter().enumerate() {
+        dark_count += *count as f64;
+        dark_sum += level as f64 * *count as f64;
+        let light_count = total - dark_count;
+        if dark_count == 0.0 || light_count == 0.0 {
+            continue;
+        }
+        let dark_mean = dark_sum / dark_count;
+        let light_mean = (sum - dark_sum) / light_count;
+        let between = dark_count * light_count * (dark_mean - light_mean).powi(2);
+        if between > best.1 {
+            best = (level, between);
+        }
+    }
+    best.0 as u8
+}
+
+///
+/// Get the value of a Bayer matrix of the given size at a position, which is
+/// between 0 and the size squared. Each level of the matrix splits it into
+/// four corners that are visited in the order top left, bottom right, top
+/// right, bottom left, and the smallest split counts the most
+///
+fn get_bayer_value(size: u32, x: u32, y: u32) -> u32 {
+    let mut value = 0;
+    let mut half = size / 2;
+    let mut scale = 1;
+    while half > 0 {
+        let quadrant = match (x & half != 0, y & half != 0) {
+            (false, false) => 0,
+            (true, true) => 1,
+            (true, false) => 2,
+            (false, true) => 3,
+        };
+        value += quadrant * scale;
+        scale *= 4;
+        half /= 2;
+    }
+    value
+}
+
+///
+/// Convert pixels into black and white using the method. The pixels are in
+/// the order they are stored in a bitmap, starting with the bottom row
+///
+pub(crate) fn convert(
+    method: MonochromeMethod,
+    pixels: &[Rgba],
+    width: u32,
+    height: u32,
+) -> Vec<Rgba> {
+    let brightness: Vec<u8> = pixels.iter().map(get_brightness).collect();
+    let threshold = match method {
+        MonochromeMethod::Threshold(level) => level,
+        MonochromeMethod::Otsu => get_otsu_threshold(&brightness),
+        _ => 0,
+    };
+    brightness
+        .iter()
+        .enumerate()
+        .map(|(i, level)| {
+            let is_white = match method.get_pattern_size() {
+                Some(size) => {
+                    // the pattern starts at the top left of the image
+                    let x = i as u32 % width;
+                    let y = height - 1 - i as u32 / width;
+                    let cells = size * size;
+                    let value = get_bayer_value(size, x % size, y % size);
+                    // spread the thresholds evenly between black and white
+                    *level as u32 * cells > (value * 2 + 1) * 255 / 2
+                }
+                None => *level > threshold,
+            };
+            match is_white {
+                true => Rgba::white(),
+                false => Rgba::black(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::convert;
+    use super::get_bayer_value;
+    use super::get_brightness;
+    use super::get_otsu_threshold;
+    use super::MonochromeMethod;
+    use super::Rgba;
+
+    fn count_white(pixels: &[Rgba]) -> usize {
+        pixels.iter().filter(|p| p.is_white()).count()
+    }
+
+    #[test]
+    fn build_bayer_matrices() {
+        let values: Vec<u32> = (0..4).map(|i| get_bayer_value(2, i % 2, i / 2)).collect();
+        assert_eq!(values, vec![0, 2, 3, 1]);
+        let row: Vec<u32> = (0..4).map(|x| get_bayer_value(4, x, 0)).collect();
+        assert_eq!(row, vec![0, 8, 2, 10]);
+        let mut values: Vec<u32> = (0..64).map(|i| get_bayer_value(8, i % 8, i / 8)).collect();
+        values.sort_unstable();
+        assert_eq!(values, (0..64).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn treat_transparent_pixels_as_white() {
+        assert_eq!(get_brightness(&Rgba::rgba(0, 0, 0, 0)), 255);
+        assert_eq!(get_brightness(&Rgba::black()), 0);
+        assert_eq!(get_brightness(&Rgba::rgba(0, 0, 0, 50)), 128);
+    }
+
+    #[test]
+    fn split_pixels_at_threshold() {
+        let pixels = vec![Rgba::rgb(100, 100, 100), Rgba::rgb(101, 101, 101)];
+        let converted = convert(MonochromeMethod::Threshold(100), &pixels, 2, 1);
+        assert_eq!(converted, vec![Rgba::black(), Rgba::white()]);
+    }
+
+    #[test]
+    fn find_threshold_between_two_groups() {
+        let mut brightness = vec![40; 30];
+        brightness.extend(vec![200; 70]);
+        let threshold = get_otsu_threshold(&brightness);
+        assert!((40..200).contains(&threshold));
+        let pixels = vec![Rgba::rgb(180, 180, 180), Rgba::rgb(210, 210, 210)];
+        let converted = convert(MonochromeMethod::Otsu, &pixels, 2, 1);
+        assert_eq!(converted, vec![Rgba::black(), Rgba::white()]);
+    }
+
+    #[test]
+    fn keep_shades_of_gray_with_patterns() {
+        let pixels = vec![Rgba::rgb(64, 64, 64); 64];
+        for method in [
+            MonochromeMethod::Bayer2x2,
+            MonochromeMethod::Bayer4x4,
+            MonochromeMethod::Bayer8x8,
+        ]
+        .iter()
+        {
+            // a quarter of the pixels should be white
+            assert_eq!(count_white(&convert(*method, &pixels, 8, 8)), 16);
+        }
+        let black = vec![Rgba::black(); 64];
+        assert_eq!(
+            count_white(&convert(MonochromeMethod::Bayer8x8, &black, 8, 8)),
+            0
+        );
+        let white = vec![Rgba::white(); 64];
+        assert_eq!(
+            count_white(&convert(MonochromeMethod::Bayer8x8, &white, 8, 8)),
+            64
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use super::dither::Dither;
+use super::error::BitmapError;
+use super::image::BitMap;
+use super::indexed_image::IndexedBitMap;
+use super::quantizer;
+use super::rgba::Rgba;
+
+///
+/// The 16 colors used by EGA and VGA text modes, in the order the hardware
+/// numbers them
+///
+const VGA_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (0, 0, 170),
+    (0, 170, 0),
+    (0, 170, 170),
+    (170, 0, 0),
+    (170, 0, 170),
+    (170, 85, 0),
+    (170, 170, 170),
+    (85, 85, 85),
+    (85, 85, 255),
+    (85, 255, 85),
+    (85, 255, 255),
+    (255, 85, 85),
+    (255, 85, 255),
+    (255, 255, 85),
+    (255, 255, 255),
+];
+
+///
+/// A palette that doesn't depend on the colors of the image, so that images
+/// saved with it all share the same color table
+///
+#[derive(Debug, PartialEq, Clone)]
+pub enum Palette {
+    /// The 16 colors of EGA and VGA displays
+    Vga16,
+    /// The 216 colors that are made from six levels of red, green and blue
+    WebSafe,
+    /// Black and white
+    Grayscale2,
+    /// 16 evenly spaced shades of gray, from black to white
+    Grayscale16,
+    /// Every shade of gray, from black to white
+    Grayscale256,
+    /// A palette of 1 to 256 colors given by the user
+    Custom(Vec<Rgba>),
+}
+
+impl Palette {
+    ///
+    /// Get the colors of the palette in the order they are written to the
+    /// color table
+    ///
+    pub fn get_colors(&self) -> Vec<Rgba> {
+        match self {
+            Palette::Vga16 => VGA_COLORS
+                .iter()
+                .map(|(r, g, b)| Rgba::rgb(*r, *g, *b))
+                .collect(),
+            Palette::WebSafe => {
+                let mut colors = Vec::with_capacity(216);
+                for r in 0..6 {
+                    for g in 0..6 {
+                        for b in 0..6 {
+                            colors.push(Rgba::rgb(r * 51, g * 51, b * 51));
+                        }
+                    }
+                }
+                colors
+            }
+            Palette::Grayscale2 => vec![Rgba::black(), Rgba::white()],
+            Palette::Grayscale16 => (0..16).map(|i| Rgba::rgb(i * 17, i * 17, i * 17)).collect(),
+            Palette::Grayscale256 => (0..=255).map(|i| Rgba::rgb(i, i, i)).collect(),
+            Palette::Custom(colors) => colors.clone(),
+        }
+    }
+
+    ///
+    /// Get the number of colors in the palette
+    ///
+    pub fn len(&self) -> usize {
+        match self {
+            Palette::Vga16 | Palette::Grayscale16 => 16,
+            Palette::WebSafe => 216,
+            Palette::Grayscale2 => 2,
+            Palette::Grayscale256 => 256,
+            Palette::Custom(colors) => colors.len(),
+        }
+    }
+
+    ///
+    /// Check if the palette has no colors, which can only happen with a
+    /// custom palette
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///
+    /// Give every pixel of a bitmap the color from the palette that is
+    /// closest to it, or spread the difference to the pixels around it if a
+    /// dither is given. The resolution of the bitmap is kept
+    ///
+    /// Fails if the palette is empty or has more then 256 colors
+    ///
+    pub fn apply(
+        &self,
+        bitmap: &BitMap,
+        dither: Option<&Dither>,
+    ) -> Result<IndexedBitMap, BitmapError> {
+        let colors = self.get_colors();
+        if let Some(dither) = dither {
+            return dither.apply(bitmap, &colors);
+        }
+        let mut image = IndexedBitMap::new(bitmap.get_width(), bitmap.get_height(), colors)?;
+        let (x_pixels_per_meter, y_pixels_per_meter) = bitmap.get_pixels_per_meter();
+        image.set_pixels_per_meter(x_pixels_per_meter, y_pixels_per_meter);
+        // images often repeat colors, so only search the palette once for each
+        let mut nearest: HashMap<Rgba, u8> = HashMap::new();
+        for y in 0..bitmap.get_height() {
+            for x in 0..bitmap.get_width() {
+                let pixel = bitmap.get_pixel(x, y).unwrap();
+                let index = *nearest
+                    .entry(*pixel)
+                    .or_insert_with(|| quantizer::get_nearest_index(image.get_palette(), pixel));
+                image.set_index(x, y, index)?;
+            }
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitMap;
+    use super::Palette;
+    use super::Rgba;
+    use crate::bitmap::dither::Dither;
+    use crate::bitmap::dither::DitherMethod;
+
+    #[test]
+    fn built_in_palettes_have_unique_colors() {
+        let palettes = [
+            Palette::Vga16,
+            Palette::WebSafe,
+            Palette::Grayscale2,
+            Palette::Grayscale16,
+            Palette::Grayscale256,
+        ];
+        for palette in palettes.iter() {
+            let mut colors = palette.get_colors();
+            assert_eq!(colors.len(), palette.len());
+            assert_eq!(colors.first(), Some(&Rgba::black()));
+            assert_eq!(colors.last(), Some(&Rgba::white()));
+            colors.dedup();
+            assert_eq!(colors.len(), palette.len());
+        }
+    }
+
+    #[test]
+    fn map_pixels_to_the_closest_color() {
+        let bitmap = BitMap::create(
+            3,
+            1,
+            vec![
+                Rgba::rgb(250, 90, 80),
+                Rgba::rgb(90, 80, 90),
+                Rgba::rgb(30, 30, 160),
+            ],
+        )
+        .unwrap();
+        let image = Palette::Vga16.apply(&bitmap, None).unwrap();
+        assert_eq!(image.get_palette().len(), 16);
+        assert_eq!(image.get_indices(), &[12, 8, 1]);
+        assert_eq!(image.get_pixel(0, 0), Some(&Rgba::rgb(255, 85, 85)));
+    }
+
+    #[test]
+    fn dither_to_a_fixed_palette() {
+        let mut bitmap = BitMap::new(16, 16);
+        bitmap.clear_image(Rgba::rgb(128, 128, 128));
+        let dither = Dither::new(DitherMethod::FloydSteinberg);
+        let image = Palette::Grayscale2.apply(&bitmap, Some(&dither)).unwrap();
+        let white = image.get_indices().iter().filter(|i| **i == 1).count();
+        assert!(white > 100 && white < 156);
+        let plain = Palette::Grayscale2.apply(&bitmap, None).unwrap();
+        assert!(plain.get_indices().iter().all(|i| *i == 1));
+    }
+
+    #[test]
+    fn fail_with_custom_palettes_that_dont_fit() {
+        let bitmap = BitMap::new(2, 2);
+        assert!(Palette::Custom(vec![]).apply(&bitmap, None).is_err());
+        let colors = vec![Rgba::black(); 257];
+        assert!(Palette::Custom(colors).apply(&bitmap, None).is_err());
+        let colors = vec![Rgba::rgb(1, 2, 3)];
+        let image = Palette::Custom(colors).apply(&bitmap, None).unwrap();
+        assert_eq!(image.get_indices(), &[0, 0, 0, 0]);
+    }
+}
@@ -1,9 +1,14 @@
 use std::ops::{Index, IndexMut};
 
 use super::bit_depth::BitDepth;
+use super::bit_fields::BitFields;
+use super::decode_warning::DecodeWarning;
+use super::decode_warning::Recovery;
+use super::error::BitmapError;
 use super::image::BitMap;
 use super::info_header::InfoHeader;
 use super::rgba::Rgba;
+use super::util;
 
 pub struct PixelData {
     pixels: Vec<Rgba>,
@@ -11,20 +16,50 @@ pub struct PixelData {
     width: u32,
     height: u32,
     bit_depth: BitDepth,
+    /// masks used to pack each pixel, if none the pixels are stored as blue,
+    /// green, red (and alpha)
+    bit_fields: Option<BitFields>,
 }
 
 impl PixelData {
     ///
     /// Convert a bitmap image into pixel data
     ///
-    pub fn from_bitmap(bitmap: &BitMap, bit_depth: BitDepth) -> PixelData {
+    pub fn from_bitmap(
+        bitmap: &BitMap,
+        bit_depth: BitDepth,
+        bit_fields: Option<BitFields>,
+    ) -> PixelData {
+        PixelData::from_pixels(
+            bitmap.get_pixels().clone(),
+            bitmap.get_width(),
+            bit_depth,
+            bit_fields,
+        )
+    }
+
+    ///
+    /// Create pixel data from rows of pixels that are in the same order they
+    /// are stored in
+    ///
+    pub fn from_pixels(
+        pixels: Vec<Rgba>,
+        width: u32,
+        bit_depth: BitDepth,
+        bit_fields: Option<BitFields>,
+    ) -> PixelData {
         // TODO: Stop assuming that this is all colors
+        let height = match width {
+            0 => 0,
+            width => (pixels.len() / width as usize) as u32,
+        };
         PixelData {
-            pixels: bitmap.get_pixels().clone(),
-            padding: PixelData::get_row_padding_size(bitmap.get_width(), bit_depth),
-            width: bitmap.get_width(),
-            height: bitmap.get_height(),
+            pixels,
+            padding: PixelData::get_row_padding_size(width, bit_depth),
+            width,
+            height,
             bit_depth,
+            bit_fields: PixelData::get_bit_fields_or_default(bit_fields, bit_depth),
         }
     }
 
@@ -32,24 +67,67 @@ impl PixelData {
     /// Create pixel data from slice of bytes
     /// and some data that supports it
     ///
-    pub fn from_slice(bit_stream: &[u8], info: &InfoHeader, bit_depth: BitDepth) -> PixelData {
-        // check the bit_stream length and compare it to how big the file is
-        // supposed to be
-        let mut pixels: Vec<Rgba> = Vec::new();
+    /// Fails if there aren't enough bytes for every pixel, unless there's a
+    /// recovery in which case the missing pixels are given the fill color.
+    /// The padding after the last row is optional, since some applications
+    /// leave it out
+    ///
+    pub fn from_slice(
+        bit_stream: &[u8],
+        info: &InfoHeader,
+        bit_depth: BitDepth,
+        recovery: Option<&mut Recovery>,
+    ) -> Result<PixelData, BitmapError> {
+        let bit_fields = PixelData::get_bit_fields_or_default(info.get_bit_fields(), bit_depth);
         let padding = PixelData::get_row_padding_size(info.get_width(), bit_depth);
         let step = bit_depth.get_step_counter() as usize;
+        let row_size = info.get_width() as usize * step + padding as usize;
+        let needed = match info.get_height() as usize {
+            0 => 0,
+            height => row_size * (height - 1) + info.get_width() as usize * step,
+        };
+        let mut fill = Rgba::black();
+        if bit_stream.len() < needed {
+            match recovery {
+                Some(recovery) => {
+                    recovery.warn(DecodeWarning::PixelDataTruncated {
+                        needed,
+                        available: bit_stream.len(),
+                    });
+                    fill = recovery.get_fill();
+                }
+                None => {
+                    return Err(BitmapError::Truncated {
+                        section: "pixel data",
+                        offset: 0,
+                        needed,
+                        available: bit_stream.len(),
+                    })
+                }
+            }
+        }
+        let mut pixels: Vec<Rgba> =
+            Vec::with_capacity(info.get_width() as usize * info.get_height() as usize);
         let mut counter = 0;
 
         for _ in 0..info.get_height() {
             for _ in 0..info.get_width() {
-                // TODO: check if the number of bytes needed exists
-                //       If they don't, throw error
                 let i = counter;
-                let pixel = match bit_depth {
-                    BitDepth::AllColors => {
+                let pixel = match (bit_depth, bit_fields) {
+                    // only happens in the lenient mode, the data ran out
+                    _ if i + step > bit_stream.len() => fill,
+                    (BitDepth::HighColor, Some(fields)) => {
+                        let mut index = i;
+                        fields.decode(util::byte_slice_to_u16(bit_stream, &mut index) as u32)
+                    }
+                    (BitDepth::AllColorsAndShades, Some(fields)) => {
+                        let mut index = i;
+                        fields.decode(util::byte_slice_to_u32(bit_stream, &mut index))
+                    }
+                    (BitDepth::AllColors, _) => {
                         Rgba::bgr(bit_stream[i], bit_stream[i + 1], bit_stream[i + 2])
                     }
-                    BitDepth::AllColorsAndShades => Rgba::bgra(
+                    (BitDepth::AllColorsAndShades, None) => Rgba::bgra(
                         bit_stream[i],
                         bit_stream[i + 1],
                         bit_stream[i + 2],
@@ -62,13 +140,14 @@ impl PixelData {
             }
             counter += padding as usize;
         }
-        PixelData {
+        Ok(PixelData {
             pixels,
             padding,
             width: info.get_width(),
             height: info.get_height(),
             bit_depth,
-        }
+            bit_fields,
+        })
     }
 
     ///
@@ -76,21 +155,29 @@ impl PixelData {
     ///
     /// The bytes in the list need to go one after another in a certain form.
     /// That form being blue, green, and red as well as alpha IF the bit depth
-    /// is 32
+    /// is 32. If the pixels use bit fields, each pixel is packed using the masks
     ///
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
         let mut counter = self.width;
         for p in &self.pixels {
             // i need to watch the row count
-            bytes.push(p.get_blue());
-            bytes.push(p.get_green());
-            bytes.push(p.get_red());
-            if self.bit_depth == BitDepth::AllColorsAndShades {
-                bytes.push(p.get_alpha())
+            match self.bit_fields {
+                Some(fields) if self.bit_depth == BitDepth::HighColor => {
+                    bytes.extend_from_slice(&(fields.encode(p) as u16).to_le_bytes())
+                }
+                Some(fields) => bytes.extend_from_slice(&fields.encode(p).to_le_bytes()),
+                None => {
+                    bytes.push(p.get_blue());
+                    bytes.push(p.get_green());
+                    bytes.push(p.get_red());
+                    if self.bit_depth == BitDepth::AllColorsAndShades {
+                        bytes.push(p.get_alpha())
+                    }
+                }
             }
             // after row has been written, pad the bytes to a number divisible by 4
-            counter = counter - 1;
+            counter -= 1;
             if counter == 0 {
                 while bytes.len() % 4 != 0 {
                     bytes.push(0);
@@ -134,27 +221,36 @@ impl PixelData {
     /// Bitmaps must be divisible by 4
     ///
     fn get_row_padding_size(width: u32, bit_depth: BitDepth) -> u32 {
+        match (width as usize * bit_depth.get_step_counter() as usize) % 4 {
+            0 => 0,
+            remainder => 4 - remainder as u32,
+        }
+    }
+
+    ///
+    /// 16 bit images that don't specify their masks are always stored as
+    /// 5-5-5, so use those masks if none were given
+    ///
+    fn get_bit_fields_or_default(
+        bit_fields: Option<BitFields>,
+        bit_depth: BitDepth,
+    ) -> Option<BitFields> {
         match bit_depth {
-            BitDepth::AllColors => match (width * 3) % 4 {
-                1 => 3,
-                2 => 2,
-                3 => 1,
-                _ => 0,
-            },
-            _ => 0,
+            BitDepth::HighColor => Some(bit_fields.unwrap_or_else(BitFields::rgb555)),
+            _ => bit_fields,
         }
     }
 }
 
 impl Index<usize> for PixelData {
     type Output = Rgba;
-    fn index<'a>(&'a self, i: usize) -> &'a Rgba {
+    fn index(&self, i: usize) -> &Rgba {
         &self.pixels[i]
     }
 }
 
 impl IndexMut<usize> for PixelData {
-    fn index_mut<'a>(&'a mut self, i: usize) -> &'a mut Rgba {
+    fn index_mut(&mut self, i: usize) -> &mut Rgba {
         &mut self.pixels[i]
     }
 }
@@ -163,7 +259,7 @@ impl IndexMut<usize> for PixelData {
 impl std::fmt::Display for PixelData {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for p in 0..self.pixels.len() {
-            write!(f, "{}: {}\n", p, self.pixels[p]).unwrap();
+            writeln!(f, "{}: {}", p, self.pixels[p]).unwrap();
         }
         write!(f, "")
     }
@@ -172,6 +268,7 @@ impl std::fmt::Display for PixelData {
 #[cfg(test)]
 mod test {
     use super::BitDepth;
+    use super::BitFields;
     use super::BitMap;
     use super::PixelData;
     use super::Rgba;
@@ -179,24 +276,24 @@ mod test {
     #[test]
     fn get_length_of_pixel_data_from_bitmap() {
         let b = BitMap::new(10, 10);
-        let data = PixelData::from_bitmap(&b, BitDepth::AllColors);
+        let data = PixelData::from_bitmap(&b, BitDepth::AllColors, None);
         assert_eq!(data.len(), 100);
     }
 
     #[test]
     fn get_size_of_bytes_in_pixel_data() {
         let b = BitMap::new(10, 10);
-        let data = PixelData::from_bitmap(&b, BitDepth::AllColors);
+        let data = PixelData::from_bitmap(&b, BitDepth::AllColors, None);
         assert_eq!(data.get_bytes_size(), 320);
         let b = BitMap::new(546, 879);
-        let data = PixelData::from_bitmap(&b, BitDepth::AllColors);
+        let data = PixelData::from_bitmap(&b, BitDepth::AllColors, None);
         assert_eq!(data.get_bytes_size(), 1441560);
     }
 
     #[test]
     fn get_pixel_data_as_rgb() {
         let b = BitMap::new(10, 10);
-        let data = PixelData::from_bitmap(&b, BitDepth::AllColors);
+        let data = PixelData::from_bitmap(&b, BitDepth::AllColors, None);
         let colors = data.as_rgba();
         for c in &colors {
             assert!(c == &Rgba::white());
@@ -207,7 +304,29 @@ mod test {
     #[should_panic]
     fn get_data_from_outside_of_pixel_data_range() {
         let b = BitMap::new(10, 10);
-        let data = PixelData::from_bitmap(&b, BitDepth::AllColors);
+        let data = PixelData::from_bitmap(&b, BitDepth::AllColors, None);
         data[100];
     }
+
+    #[test]
+    fn get_size_of_bytes_in_16_bit_pixel_data() {
+        let b = BitMap::new(3, 3);
+        let data = PixelData::from_bitmap(&b, BitDepth::HighColor, None);
+        assert_eq!(data.get_bytes_size(), 24);
+        assert_eq!(data.as_bytes().len(), 24);
+        let b = BitMap::new(2, 3);
+        let data = PixelData::from_bitmap(&b, BitDepth::HighColor, None);
+        assert_eq!(data.get_bytes_size(), 12);
+        assert_eq!(data.as_bytes().len(), 12);
+    }
+
+    #[test]
+    fn get_16_bit_pixel_data_as_bytes() {
+        let mut b = BitMap::new(1, 1);
+        b.set_pixel(0, 0, Rgba::rgb(255, 0, 255)).unwrap();
+        let data = PixelData::from_bitmap(&b, BitDepth::HighColor, None);
+        assert_eq!(data.as_bytes(), vec![0x1f, 0x7c, 0, 0]);
+        let data = PixelData::from_bitmap(&b, BitDepth::HighColor, Some(BitFields::rgb565()));
+        assert_eq!(data.as_bytes(), vec![0x1f, 0xf8, 0, 0]);
+    }
 }
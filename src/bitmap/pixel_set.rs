@@ -0,0 +1,107 @@
+///
+/// Number of pixels that are stored in each word of a pixel set
+///
+const WORD_BITS: usize = 64;
+
+///
+/// Set of pixels in an image, stored as a single bit for each pixel so that it
+/// only takes a small part of the memory used by the pixels themselves
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PixelSet {
+    /// one bit for every pixel, which is set if the pixel is in the set. Words
+    /// past the end are treated as if they were all zeros
+    words: Vec<u64>,
+}
+
+impl PixelSet {
+    ///
+    /// Create a set without any pixels in it
+    ///
+    pub fn new() -> PixelSet {
+        PixelSet { words: Vec::new() }
+    }
+
+    ///
+    /// Create a set that has every pixel from 0 up to (not including) length
+    ///
+    pub fn full(length: usize) -> PixelSet {
+        let mut words = vec![u64::MAX; length / WORD_BITS];
+        let remainder = length % WORD_BITS;
+        if remainder > 0 {
+            words.push((1 << remainder) - 1);
+        }
+        PixelSet { words }
+    }
+
+    ///
+    /// Add a pixel to the set
+    ///
+    pub fn insert(&mut self, i: usize) {
+        let word = i / WORD_BITS;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (i % WORD_BITS);
+    }
+
+    ///
+    /// Take a pixel out of the set
+    ///
+    pub fn remove(&mut self, i: usize) {
+        if let Some(word) = self.words.get_mut(i / WORD_BITS) {
+            *word &= !(1 << (i % WORD_BITS));
+        }
+    }
+
+    ///
+    /// Check if a pixel is in the set
+    ///
+    pub fn contains(&self, i: usize) -> bool {
+        match self.words.get(i / WORD_BITS) {
+            Some(word) => (word >> (i % WORD_BITS)) & 1 == 1,
+            None => false,
+        }
+    }
+
+    ///
+    /// Check if there aren't any pixels in the set
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PixelSet;
+
+    #[test]
+    fn insert_and_remove_pixels() {
+        let mut set = PixelSet::new();
+        assert!(set.is_empty());
+        set.insert(3);
+        set.insert(130);
+        assert!(set.contains(3));
+        assert!(set.contains(130));
+        assert!(!set.contains(4));
+        assert!(!set.contains(1000));
+        set.remove(3);
+        set.remove(1000);
+        assert!(!set.contains(3));
+        set.remove(130);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn full_set_stops_at_the_length() {
+        let set = PixelSet::full(70);
+        assert!(set.contains(0));
+        assert!(set.contains(63));
+        assert!(set.contains(69));
+        assert!(!set.contains(70));
+        assert!(PixelSet::full(0).is_empty());
+        assert!(PixelSet::full(64).contains(63));
+        assert!(!PixelSet::full(64).contains(64));
+    }
+}
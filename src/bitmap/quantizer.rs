@@ -0,0 +1,475 @@
+use std::collections::HashMap;
+
+use super::dither::Dither;
+use super::error::BitmapError;
+use super::image::BitMap;
+use super::indexed_image::IndexedBitMap;
+use super::rgba::Rgba;
+
+///
+/// The method used to reduce an image to a limited number of colors, so it
+/// can be stored with a color table
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Quantizer {
+    /// Split the colors of the image into boxes until there is one box for
+    /// each color of the palette, always splitting the box that covers the
+    /// widest range of a channel. Transparency is kept apart like any other
+    /// channel
+    MedianCut,
+    /// Put the colors of the image into a tree that splits each channel in
+    /// half at every level, then merge the smallest branches until the tree
+    /// has few enough leaves. Faster then median cut for images with a lot of
+    /// colors, but colors that only differ by transparency are merged
+    Octree,
+}
+
+impl Quantizer {
+    ///
+    /// Reduce a bitmap to at most the given number of colors, giving back the
+    /// palette and the index of each pixel as an indexed image. Images that
+    /// already have few enough colors keep every color. The resolution of the
+    /// bitmap is kept
+    ///
+    /// Fails if the number of colors isn't between 1 and 256
+    ///
+    pub fn quantize(
+        &self,
+        bitmap: &BitMap,
+        max_colors: usize,
+    ) -> Result<IndexedBitMap, BitmapError> {
+        if max_colors == 0 || max_colors > 256 {
+            return Err(BitmapError::InvalidArgument(
+                "Images can only be reduced to between 1 and 256 colors.",
+            ));
+        }
+        // count each color in the order it's first found, so the palette
+        // doesn't change from one run to the next
+        let mut positions: HashMap<Rgba, usize> = HashMap::new();
+        let mut counts: Vec<(Rgba, u64)> = Vec::new();
+        for pixel in bitmap.get_pixels() {
+            let position = *positions.entry(*pixel).or_insert_with(|| {
+                counts.push((*pixel, 0));
+                counts.len() - 1
+            });
+            counts[position].1 += 1;
+        }
+
+        let (mut palette, mapping) = if counts.len() <= max_colors {
+            let palette: Vec<Rgba> = counts.iter().map(|(color, _)| *color).collect();
+            let mapping = (0..counts.len()).map(|i| i as u8).collect();
+            (palette, mapping)
+        } else {
+            match self {
+                Quantizer::MedianCut => median_cut(&counts, max_colors),
+                Quantizer::Octree => octree(&counts, max_colors),
+            }
+        };
+        if palette.is_empty() {
+            // an image without any pixels still needs a color
+            palette.push(Rgba::white());
+        }
+
+        let width = bitmap.get_width().max(1) as usize;
+        let indices: Vec<u8> = bitmap
+            .get_pixels()
+            .chunks(width)
+            .rev()
+            .flatten()
+            .map(|pixel| mapping[positions[pixel]])
+            .collect();
+        let mut image =
+            IndexedBitMap::create(bitmap.get_width(), bitmap.get_height(), palette, indices)?;
+        let (x_pixels_per_meter, y_pixels_per_meter) = bitmap.get_pixels_per_meter();
+        image.set_pixels_per_meter(x_pixels_per_meter, y_pixels_per_meter);
+        Ok(image)
+    }
+
+    ///
+    /// Reduce a bitmap to at most the given number of colors like
+    /// [Quantizer::quantize], then dither the bitmap to the palette that was
+    /// found instead of giving each pixel the closest color
+    ///
+    /// Fails if the number of colors isn't between 1 and 256
+    ///
+    pub fn quantize_with_dither(
+        &self,
+        bitmap: &BitMap,
+        max_colors: usize,
+        dither: &Dither,
+    ) -> Result<IndexedBitMap, BitmapError> {
+        let image = self.quantize(bitmap, max_colors)?;
+        dither.apply(bitmap, image.get_palette())
+    }
+}
+
+///
+/// Get the channels of a color as red, green, blue and alpha, with the alpha
+/// scaled up to the same range as the other channels
+///
+pub(crate) fn get_channels(color: &Rgba) -> [u32; 4] {
+    [
+        color.get_red() as u32,
+        color.get_green() as u32,
+        color.get_blue() as u32,
+        color.get_alpha() as u32 * 255 / 100,
+    ]
+}
+
+///
+/// Get the squared distance between the channels of two colors, counting
+/// transparency as a channel
+///
+fn get_distance(a: &[u32; 4], b: &[u32; 4]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32)
+        .sum()
+}
+
+///
+/// Get the index of the color in the palette that is closest to the given
+/// color. If more then one color is just as close, the first one is used
+///
+pub(crate) fn get_nearest_index(palette: &[Rgba], color: &Rgba) -> u8 {
+    get_nearest_channels(palette.iter().map(get_channels), &get_channels(color))
+}
+
+///
+/// Get the index of the channels in the palette that are closest to the
+/// given channels. If more then one is just as close, the first one is used
+///
+pub(crate) fn get_nearest_channels<I: Iterator<Item = [u32; 4]>>(
+    palette: I,
+    channels: &[u32; 4],
+) -> u8 {
+    let mut nearest = 0;
+    let mut nearest_distance = u32::MAX;
+    for (i, entry) in palette.enumerate() {
+        let distance = get_distance(&entry, channels);
+        if distance < nearest_distance {
+            nearest = i;
+            nearest_distance = distance;
+        }
+    }
+    nearest as u8
+}
+
+///
+/// Get the average of colors, weighted by the number of pixels of each color
+///
+fn get_average(colors: &[(Rgba, u64)]) -> Rgba {
+    let mut sums = [0u64; 4];
+    let mut total = 0;
+    for (color, count) in colors {
+        let channels = [
+            color.get_red(),
+            color.get_green(),
+            color.get_blue(),
+            color.get_alpha(),
+        ];
+        for (sum, channel) in sums.iter_mut().zip(channels.iter()) {
+            *sum += *channel as u64 * count;
+        }
+        total += count;
+    }
+    let average = |sum: u64| ((sum + total / 2) / total.max(1)) as u8;
+    Rgba::rgba(
+        average(sums[0]),
+        average(sums[1]),
+        average(sums[2]),
+        average(sums[3]),
+    )
+}
+
+///
+/// Reduce the colors using median cut, giving back the palette and the index
+/// of the palette color used for each of the colors
+///
+fn median_cut(counts: &[(Rgba, u64)], max_colors: usize) -> (Vec<Rgba>, Vec<u8>) {
+    // each box keeps its widest channel and the range of that channel
+    let (channel, range) = get_widest_channel(counts);
+    let mut boxes = vec![(counts.to_vec(), channel, range)];
+    while boxes.len() < max_colors {
+        // split the box that covers the widest range of a single channel
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, (colors, _, _))| colors.len() > 1)
+            .max_by_key(|(_, (_, _, range))| *range)
+            .map(|(i, _)| i);
+        let (mut colors, channel, _) = match widest {
+            Some(i) => boxes.swap_remove(i),
+            None => break,
+        };
+        colors.sort_by_key(|(color, _)| get_channels(color)[channel]);
+
+        // split at the median pixel, keeping at least one color on each side
+        let total: u64 = colors.iter().map(|(_, count)| count).sum();
+        let mut seen = 0;
+        let mut split = colors.len() - 1;
+        for (j, (_, count)) in colors.iter().enumerate() {
+            seen += count;
+            if seen * 2 >= total {
+                split = j + 1;
+                break;
+            }
+        }
+        let split = split.clamp(1, colors.len() - 1);
+        let rest = colors.split_off(split);
+        for colors in [colors, rest] {
+            let (channel, range) = get_widest_channel(&colors);
+            boxes.push((colors, channel, range));
+        }
+    }
+    let palette: Vec<Rgba> = boxes
+        .iter()
+        .map(|(colors, _, _)| get_average(colors))
+        .collect();
+    let mapping = counts
+        .iter()
+        .map(|(color, _)| get_nearest_index(&palette, color))
+        .collect();
+    (palette, mapping)
+}
+
+///
+/// Get the channel that has the widest range of values in the colors, and
+/// the size of that range
+///
+fn get_widest_channel(colors: &[(Rgba, u64)]) -> (usize, u32) {
+    let mut min = [u32::MAX; 4];
+    let mut max = [0; 4];
+    for (color, _) in colors {
+        for (channel, value) in get_channels(color).iter().enumerate() {
+            min[channel] = min[channel].min(*value);
+            max[channel] = max[channel].max(*value);
+        }
+    }
+    (0..4)
+        .map(|channel| (channel, max[channel] - min[channel]))
+        .max_by_key(|(_, range)| *range)
+        .unwrap()
+}
+
+///
+/// Branch or leaf of an octree. Every node keeps the sum of the colors that
+/// went through it, so that it can become a leaf when its children are merged
+///
+#[derive(Default)]
+struct OctreeNode {
+    /// nodes of the next level, zero if there is no child since the root
+    /// can't be a child
+    children: [usize; 8],
+    /// sum of the red, green, blue and alpha of every pixel in the node
+    sums: [u64; 4],
+    /// number of pixels in the node
+    count: u64,
+    /// if the node is a leaf, which is any node without children
+    is_leaf: bool,
+}
+
+impl OctreeNode {
+    ///
+    /// Add the pixels of a color to the node
+    ///
+    fn add(&mut self, color: &Rgba, count: u64) {
+        let channels = [
+            color.get_red(),
+            color.get_green(),
+            color.get_blue(),
+            color.get_alpha(),
+        ];
+        for (sum, channel) in self.sums.iter_mut().zip(channels.iter()) {
+            *sum += *channel as u64 * count;
+        }
+        self.count += count;
+    }
+
+    ///
+    /// Get the average color of the pixels in the node
+    ///
+    fn get_color(&self) -> Rgba {
+        let average = |sum: u64| ((sum + self.count / 2) / self.count.max(1)) as u8;
+        Rgba::rgba(
+            average(self.sums[0]),
+            average(self.sums[1]),
+            average(self.sums[2]),
+            average(self.sums[3]),
+        )
+    }
+}
+
+///
+/// Reduce the colors using an octree, giving back the palette and the index
+/// of the palette color used for each of the colors
+///
+fn octree(counts: &[(Rgba, u64)], max_colors: usize) -> (Vec<Rgba>, Vec<u8>) {
+    const DEPTH: usize = 8;
+    let mut nodes = vec![OctreeNode::default()];
+    // nodes with children at each level, so the deepest can be merged first
+    let mut branches: Vec<Vec<usize>> = vec![Vec::new(); DEPTH];
+    let mut leaves = 0;
+    for (color, count) in counts {
+        let mut node = 0;
+        for (level, branches) in branches.iter_mut().enumerate() {
+            nodes[node].add(color, *count);
+            let child = get_octant(color, level);
+            if nodes[node].children[child] == 0 {
+                if nodes[node].children.iter().all(|c| *c == 0) {
+                    branches.push(node);
+                }
+                nodes.push(OctreeNode::default());
+                nodes[node].children[child] = nodes.len() - 1;
+            }
+            node = nodes[node].children[child];
+        }
+        nodes[node].add(color, *count);
+        if !nodes[node].is_leaf {
+            nodes[node].is_leaf = true;
+            leaves += 1;
+        }
+    }
+
+    // merge the children of the smallest branch on the deepest level. The
+    // biggest branches go first so the smallest can be taken off the end
+    for branches in branches.iter_mut() {
+        branches.sort_by_key(|node| std::cmp::Reverse(nodes[*node].count));
+    }
+    while leaves > max_colors {
+        let node = match branches.iter_mut().rev().find_map(|b| b.pop()) {
+            Some(node) => node,
+            None => break,
+        };
+        let children: Vec<usize> = nodes[node]
+            .children
+            .iter()
+            .copied()
+            .filter(|c| *c != 0)
+            .collect();
+        for child in &children {
+            // the children are leaves, since deeper levels are merged first
+            nodes[*child].is_leaf = false;
+        }
+        let children = children.len();
+        nodes[node].children = [0; 8];
+        nodes[node].is_leaf = true;
+        leaves -= children - 1;
+    }
+
+    // give each leaf a color in the palette, then find the leaf of each color
+    let mut palette = Vec::new();
+    let mut leaf_indices = HashMap::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if node.is_leaf {
+            leaf_indices.insert(i, palette.len() as u8);
+            palette.push(node.get_color());
+        }
+    }
+    let mapping = counts
+        .iter()
+        .map(|(color, _)| {
+            let mut node = 0;
+            let mut level = 0;
+            while !nodes[node].is_leaf {
+                node = nodes[node].children[get_octant(color, level)];
+                level += 1;
+            }
+            leaf_indices[&node]
+        })
+        .collect();
+    (palette, mapping)
+}
+
+///
+/// Get which of the 8 children of a node at the given level a color goes to,
+/// using one bit of each of the red, green and blue channels
+///
+fn get_octant(color: &Rgba, level: usize) -> usize {
+    let shift = 7 - level;
+    let red = (color.get_red() >> shift) & 1;
+    let green = (color.get_green() >> shift) & 1;
+    let blue = (color.get_blue() >> shift) & 1;
+    ((red << 2) | (green << 1) | blue) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::get_nearest_index;
+    use super::BitMap;
+    use super::Quantizer;
+    use super::Rgba;
+
+    fn create_photo() -> BitMap {
+        let mut bitmap = BitMap::new(64, 48);
+        for y in 0..48 {
+            for x in 0..64 {
+                let color = Rgba::rgb((x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8);
+                bitmap.set_pixel(x, y, color).unwrap();
+            }
+        }
+        bitmap
+    }
+
+    #[test]
+    fn reduce_photo_to_few_colors() {
+        let bitmap = create_photo();
+        for quantizer in [Quantizer::MedianCut, Quantizer::Octree].iter() {
+            for max_colors in [2, 16, 256].iter() {
+                let image = quantizer.quantize(&bitmap, *max_colors).unwrap();
+                assert!(image.get_palette().len() <= *max_colors);
+                if *quantizer == Quantizer::MedianCut {
+                    assert_eq!(image.get_palette().len(), *max_colors);
+                }
+                // every pixel should end up near its color
+                let pixel = bitmap.get_pixel(10, 10).unwrap();
+                let quantized = image.get_pixel(10, 10).unwrap();
+                let limit = match max_colors {
+                    256 => 20,
+                    _ => 130,
+                };
+                assert!((pixel.get_red() as i32 - quantized.get_red() as i32).abs() < limit);
+            }
+        }
+    }
+
+    #[test]
+    fn keep_every_color_of_simple_images() {
+        let mut bitmap = BitMap::new(4, 4);
+        bitmap.set_pixel(1, 2, Rgba::black()).unwrap();
+        bitmap.set_pixel(3, 0, Rgba::rgba(1, 2, 3, 50)).unwrap();
+        for quantizer in [Quantizer::MedianCut, Quantizer::Octree].iter() {
+            let image = quantizer.quantize(&bitmap, 3).unwrap();
+            assert_eq!(image.get_palette().len(), 3);
+            assert!(image.to_bitmap() == bitmap);
+        }
+    }
+
+    #[test]
+    fn keep_transparency_apart_with_median_cut() {
+        let mut bitmap = BitMap::new(2, 1);
+        bitmap
+            .set_pixel(1, 0, Rgba::rgba(255, 255, 255, 0))
+            .unwrap();
+        bitmap.set_pixel(0, 0, Rgba::rgb(250, 250, 250)).unwrap();
+        let image = Quantizer::MedianCut.quantize(&bitmap, 1).unwrap();
+        assert_eq!(image.get_palette().len(), 1);
+        let image = Quantizer::MedianCut.quantize(&bitmap, 2).unwrap();
+        assert_eq!(image.get_pixel(1, 0).unwrap().get_alpha(), 0);
+    }
+
+    #[test]
+    fn fail_to_reduce_to_no_colors() {
+        let bitmap = create_photo();
+        assert!(Quantizer::MedianCut.quantize(&bitmap, 0).is_err());
+        assert!(Quantizer::Octree.quantize(&bitmap, 257).is_err());
+    }
+
+    #[test]
+    fn find_nearest_color_in_palette() {
+        let palette = [Rgba::black(), Rgba::white(), Rgba::rgb(255, 0, 0)];
+        assert_eq!(get_nearest_index(&palette, &Rgba::rgb(200, 30, 30)), 2);
+        assert_eq!(get_nearest_index(&palette, &Rgba::rgb(200, 200, 200)), 1);
+        assert_eq!(get_nearest_index(&palette, &Rgba::rgb(20, 0, 0)), 0);
+    }
+}
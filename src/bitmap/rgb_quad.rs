@@ -1,9 +1,12 @@
 use super::bit_depth::BitDepth;
+use super::error::BitmapError;
 use super::image::BitMap;
 use super::rgba::Rgba;
 
 pub struct RgbQuad {
     data: Vec<Rgba>,
+    /// number of bytes used to store each color
+    entry_size: usize,
 }
 
 ///
@@ -13,6 +16,10 @@ impl RgbQuad {
     pub fn single_rgb_quad_size() -> usize {
         4
     }
+
+    pub fn single_rgb_triple_size() -> usize {
+        3
+    }
 }
 
 ///
@@ -23,26 +30,30 @@ impl RgbQuad {
     /// From a from_slice of bytes, read in a list of colors used to render the
     /// bitmap image
     ///
-    pub fn from_slice(bit_stream: &[u8]) -> Result<RgbQuad, &'static str> {
-        if bit_stream.len() == 0 {
+    /// Each color takes entry_size bytes, 4 for blue, green, red and reserved
+    /// or 3 for blue, green and red
+    ///
+    pub fn from_slice(bit_stream: &[u8], entry_size: usize) -> Result<RgbQuad, BitmapError> {
+        if bit_stream.is_empty() {
             return Ok(RgbQuad::empty());
         }
         let mut data = Vec::new();
-        if bit_stream.len() % 4 != 0 {
-            return Err("Not enough data to parse Rgb quad colors");
+        if bit_stream.len() % entry_size != 0 {
+            return Err(BitmapError::Truncated {
+                section: "color table",
+                offset: 0,
+                needed: (bit_stream.len() + entry_size - 1) / entry_size * entry_size,
+                available: bit_stream.len(),
+            });
         }
-        let colors_used = bit_stream.len() / 4;
-        for index in 0..colors_used {
-            let i: usize = index * 4;
-            data.push(Rgba::bgra(
-                bit_stream[i],
-                bit_stream[i + 1],
-                bit_stream[i + 2],
-                bit_stream[i + 3],
-            ));
+        for entry in bit_stream.chunks(entry_size) {
+            data.push(match entry {
+                [blue, green, red, reserved] => Rgba::bgra(*blue, *green, *red, *reserved),
+                _ => Rgba::bgr(entry[0], entry[1], entry[2]),
+            });
         }
 
-        Ok(RgbQuad { data })
+        Ok(RgbQuad { data, entry_size })
     }
 
     ///
@@ -52,21 +63,39 @@ impl RgbQuad {
     pub fn from(bitmap: &BitMap, bit_depth: BitDepth) -> RgbQuad {
         match bit_depth {
             BitDepth::Color2Bit | BitDepth::Color16Bit | BitDepth::Color256Bit => RgbQuad {
-                data: bitmap.get_all_unique_colors().into_iter().map(Clone::clone).collect(),
+                data: bitmap
+                    .get_all_unique_colors()
+                    .into_iter()
+                    .copied()
+                    .collect(),
+                entry_size: RgbQuad::single_rgb_quad_size(),
             },
             _ => RgbQuad::empty(),
         }
     }
 
+    ///
+    /// Create a color table that holds the given colors, in the same order
+    ///
+    pub fn from_colors(colors: Vec<Rgba>) -> RgbQuad {
+        RgbQuad {
+            data: colors,
+            entry_size: RgbQuad::single_rgb_quad_size(),
+        }
+    }
+
     ///
     /// Create a empty rgb quad
     ///
     fn empty() -> RgbQuad {
-        RgbQuad { data: Vec::new() }
+        RgbQuad {
+            data: Vec::new(),
+            entry_size: RgbQuad::single_rgb_quad_size(),
+        }
     }
 
     pub fn get_bytes_size(&self) -> u32 {
-        4 * self.data.len() as u32
+        (self.entry_size * self.data.len()) as u32
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
@@ -75,11 +104,20 @@ impl RgbQuad {
             bytes.push(rgb.get_blue());
             bytes.push(rgb.get_green());
             bytes.push(rgb.get_red());
-            bytes.push(rgb.get_alpha());
+            if self.entry_size == RgbQuad::single_rgb_quad_size() {
+                bytes.push(rgb.get_alpha());
+            }
         }
         bytes
     }
 
+    ///
+    /// Get the colors in the order they are stored in
+    ///
+    pub fn get_colors(&self) -> &[Rgba] {
+        &self.data
+    }
+
     ///
     /// Clone the colors
     ///
@@ -99,7 +137,7 @@ impl RgbQuad {
 impl std::fmt::Display for RgbQuad {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         for c in &self.data {
-            write!(f, "{}\n", c).unwrap();
+            writeln!(f, "{}", c).unwrap();
         }
         write!(f, "")
     }
@@ -154,4 +192,23 @@ mod test {
         let quad = RgbQuad::from(&b, BitDepth::Color256Bit);
         assert_eq!(quad.as_bytes().len(), quad.get_bytes_size() as usize);
     }
+
+    #[test]
+    fn read_rgb_quads_and_triples() {
+        let bytes = [1, 2, 3, 100, 4, 5, 6, 100];
+        let quad = RgbQuad::from_slice(&bytes, 4).unwrap();
+        assert_eq!(
+            quad.clone_colors(),
+            vec![Rgba::rgb(3, 2, 1), Rgba::rgb(6, 5, 4)]
+        );
+        assert_eq!(quad.as_bytes(), bytes.to_vec());
+        let triple = RgbQuad::from_slice(&bytes[..6], 3).unwrap();
+        assert_eq!(
+            triple.clone_colors(),
+            vec![Rgba::rgb(3, 2, 1), Rgba::rgb(5, 4, 100)]
+        );
+        assert_eq!(triple.get_bytes_size(), 6);
+        assert_eq!(triple.as_bytes(), bytes[..6].to_vec());
+        assert!(RgbQuad::from_slice(&bytes[..7], 4).is_err());
+    }
 }
@@ -1,3 +1,5 @@
+use super::error::BitmapError;
+
 ///
 /// Rgba represents the colors red, green, blue, alpha. Alpha represents the
 /// transparency of the image while red, green and blue represent the intensity
@@ -88,6 +90,7 @@ impl Rgba {
     ///
     /// Create a color by specifying red, green, blue and alpha
     ///
+    #[allow(clippy::self_named_constructors)]
     pub fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Rgba {
         Rgba {
             red,
@@ -184,9 +187,11 @@ impl Rgba {
         lhs_factor: f32,
         rhs: &Rgba,
         rhs_factor: f32,
-    ) -> Result<Rgba, &'static str> {
+    ) -> Result<Rgba, BitmapError> {
         if lhs_factor + rhs_factor > 1.0 || lhs_factor + rhs_factor < 0.0 {
-            return Err("Error blurring colors, factors should be able to add up to 1.");
+            return Err(BitmapError::InvalidArgument(
+                "Error blurring colors, factors should be able to add up to 1.",
+            ));
         }
         // create the new colors for the left hand side
         let lhs_red = lhs.red as f32 * lhs_factor;
@@ -216,7 +221,7 @@ impl Rgba {
     ///
     pub fn cubic_interpolate(p0: &Rgba, p1: &Rgba, p2: &Rgba, p3: &Rgba, factor: f32) -> Rgba {
         if factor == 0.0 {
-            return p1.clone();
+            return *p1;
         }
         let a_red =
             -0.5 * p0.red as f32 + 1.5 * p1.red as f32 - 1.5 * p2.red as f32 + 0.5 * p3.red as f32;
@@ -261,7 +266,7 @@ impl Rgba {
             + d_alpha;
 
         // clamp values
-        let red = if red > 255.0 || red < 0.0 {
+        let red = if !(0.0..=255.0).contains(&red) {
             if red > 255.0 {
                 255
             } else {
@@ -270,7 +275,7 @@ impl Rgba {
         } else {
             red.round() as u8
         };
-        let green = if green > 255.0 || green < 0.0 {
+        let green = if !(0.0..=255.0).contains(&green) {
             if green > 255.0 {
                 255
             } else {
@@ -279,7 +284,7 @@ impl Rgba {
         } else {
             green.round() as u8
         };
-        let blue = if blue > 255.0 || blue < 0.0 {
+        let blue = if !(0.0..=255.0).contains(&blue) {
             if blue > 255.0 {
                 255
             } else {
@@ -288,7 +293,7 @@ impl Rgba {
         } else {
             blue.round() as u8
         };
-        let alpha = if alpha > 100.0 || alpha < 0.0 {
+        let alpha = if !(0.0..=100.0).contains(&alpha) {
             if alpha > 100.0 {
                 100
             } else {
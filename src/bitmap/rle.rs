@@ -0,0 +1,511 @@
+use super::decode_warning::DecodeWarning;
+use super::decode_warning::Recovery;
+use super::error::BitmapError;
+use super::pixel_set::PixelSet;
+
+///
+/// Escape codes that can follow a zero byte inside of run length encoded data
+///
+const END_OF_LINE: u8 = 0;
+const END_OF_BITMAP: u8 = 1;
+const DELTA: u8 = 2;
+
+///
+/// Runs shorter then this are written using absolute mode, because encoding
+/// them wouldn't make the file any smaller
+///
+const MIN_ENCODED_RUN: usize = 3;
+
+///
+/// The most pixels that can be written by a single run
+///
+const MAX_RUN: usize = 255;
+
+///
+/// Decode pixels that were compressed using 8 bit run length encoding.
+///
+/// The indices are returned from the bottom row to the top, the same order
+/// they are stored in when the image isn't compressed. Pixels that are jumped
+/// over by the encoding (using a delta, or by ending a line or the bitmap
+/// early) are never given a value, so they are left as 0 and returned in the
+/// set of skipped pixels.
+///
+/// If there's a recovery, data that ends early is treated like an end of
+/// bitmap instead of failing
+///
+pub fn decode_rle8(
+    bit_stream: &[u8],
+    width: u32,
+    height: u32,
+    recovery: Option<&mut Recovery>,
+) -> Result<(Vec<u8>, PixelSet), BitmapError> {
+    decode(bit_stream, width, height, 8, recovery)
+}
+
+///
+/// Decode pixels that were compressed using 4 bit run length encoding.
+///
+/// Works the same as 8 bit run length encoding, except that each byte holds
+/// two pixels. Encoded runs alternate between the high and the low half of
+/// the byte.
+///
+pub fn decode_rle4(
+    bit_stream: &[u8],
+    width: u32,
+    height: u32,
+    recovery: Option<&mut Recovery>,
+) -> Result<(Vec<u8>, PixelSet), BitmapError> {
+    decode(bit_stream, width, height, 4, recovery)
+}
+
+///
+/// Decode run length encoded pixels that take up the given number of bits
+///
+fn decode(
+    bit_stream: &[u8],
+    width: u32,
+    height: u32,
+    bits: usize,
+    recovery: Option<&mut Recovery>,
+) -> Result<(Vec<u8>, PixelSet), BitmapError> {
+    let width = width as usize;
+    let height = height as usize;
+    let mut indices = vec![0; width * height];
+    let mut skipped = PixelSet::full(width * height);
+    let mut x = 0;
+    let mut y = 0;
+    let mut i = 0;
+
+    let mut missing = None;
+    while y < height {
+        if i + 1 >= bit_stream.len() {
+            missing = Some(truncated(i, 2, bit_stream.len()));
+            break;
+        }
+        let count = bit_stream[i] as usize;
+        let value = bit_stream[i + 1];
+        i += 2;
+
+        // encoded mode, the pixels in the next byte are repeated until count
+        // pixels have been written
+        if count > 0 {
+            for pixel in 0..count {
+                let index = get_pixel(value, pixel, bits);
+                set_index(&mut indices, &mut skipped, width, x, y, index);
+                x += 1;
+            }
+            continue;
+        }
+
+        match value {
+            END_OF_LINE => {
+                x = 0;
+                y += 1;
+            }
+            END_OF_BITMAP => break,
+            DELTA => {
+                if i + 1 >= bit_stream.len() {
+                    missing = Some(truncated(i, 2, bit_stream.len()));
+                    break;
+                }
+                x += bit_stream[i] as usize;
+                y += bit_stream[i + 1] as usize;
+                i += 2;
+            }
+            // absolute mode, the next value pixels are copied as is and are
+            // padded to finish on a 16 bit boundary
+            absolute => {
+                let length = absolute as usize;
+                let byte_length = (length * bits + 7) / 8;
+                if i + byte_length > bit_stream.len() {
+                    missing = Some(truncated(i, byte_length, bit_stream.len()));
+                    break;
+                }
+                for pixel in 0..length {
+                    let byte = bit_stream[i + pixel * bits / 8];
+                    let index = get_pixel(byte, pixel, bits);
+                    set_index(&mut indices, &mut skipped, width, x, y, index);
+                    x += 1;
+                }
+                i += byte_length + byte_length % 2;
+            }
+        }
+    }
+    match (missing, recovery) {
+        (None, _) => Ok((indices, skipped)),
+        (Some(why), Some(recovery)) => {
+            let needed = match why {
+                BitmapError::Truncated { offset, needed, .. } => offset + needed,
+                _ => i,
+            };
+            recovery.warn(DecodeWarning::PixelDataTruncated {
+                needed,
+                available: bit_stream.len(),
+            });
+            Ok((indices, skipped))
+        }
+        (Some(why), None) => Err(why),
+    }
+}
+
+///
+/// Compress a list of color indices using 8 bit run length encoding.
+///
+/// The indices are read from the bottom row to the top. Pixels that repeat
+/// are written as encoded runs and everything else is copied using absolute
+/// mode.
+///
+pub fn encode_rle8(indices: &[u8], width: u32) -> Vec<u8> {
+    encode(indices, width, 8)
+}
+
+///
+/// Compress a list of color indices using 4 bit run length encoding
+///
+pub fn encode_rle4(indices: &[u8], width: u32) -> Vec<u8> {
+    encode(indices, width, 4)
+}
+
+///
+/// Compress color indices that take up the given number of bits. Every row
+/// ends with an end of line marker, except for the last which ends the bitmap.
+///
+fn encode(indices: &[u8], width: u32, bits: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    if width == 0 {
+        bytes.extend_from_slice(&[0, END_OF_BITMAP]);
+        return bytes;
+    }
+    let rows = indices.chunks(width as usize);
+    let row_count = rows.len();
+    for (y, row) in rows.enumerate() {
+        let mut x = 0;
+        while x < row.len() {
+            let run = get_run_length(row, x);
+            if run >= MIN_ENCODED_RUN {
+                bytes.push(run as u8);
+                bytes.push(set_pixels(row[x], row[x], bits));
+                x += run;
+                continue;
+            }
+
+            // collect pixels until a run that is worth encoding is found
+            let mut end = x + 1;
+            while end < row.len() && end - x < MAX_RUN && get_run_length(row, end) < MIN_ENCODED_RUN
+            {
+                end += 1;
+            }
+            let literal = &row[x..end];
+            if literal.len() < MIN_ENCODED_RUN {
+                // absolute mode can't hold less then 3 pixels, because the
+                // lengths 0, 1 and 2 are used for the escape codes
+                for index in literal {
+                    bytes.push(1);
+                    bytes.push(set_pixels(*index, *index, bits));
+                }
+            } else {
+                bytes.push(0);
+                bytes.push(literal.len() as u8);
+                let packed: Vec<u8> = match bits {
+                    4 => literal
+                        .chunks(2)
+                        .map(|pair| set_pixels(pair[0], *pair.get(1).unwrap_or(&0), bits))
+                        .collect(),
+                    _ => literal.to_vec(),
+                };
+                let padding = packed.len() % 2;
+                bytes.extend_from_slice(&packed);
+                bytes.resize(bytes.len() + padding, 0);
+            }
+            x = end;
+        }
+        let escape = if y + 1 == row_count {
+            END_OF_BITMAP
+        } else {
+            END_OF_LINE
+        };
+        bytes.extend_from_slice(&[0, escape]);
+    }
+    if row_count == 0 {
+        bytes.extend_from_slice(&[0, END_OF_BITMAP]);
+    }
+    bytes
+}
+
+///
+/// Count how many times the pixel at x repeats, up to the longest run that
+/// can be written at once
+///
+fn get_run_length(row: &[u8], x: usize) -> usize {
+    row[x..]
+        .iter()
+        .take(MAX_RUN)
+        .take_while(|index| **index == row[x])
+        .count()
+}
+
+///
+/// Put two pixels into a single byte. When pixels are 8 bits, only the first
+/// pixel is stored.
+///
+fn set_pixels(first: u8, second: u8, bits: usize) -> u8 {
+    match bits {
+        4 => (first << 4) | (second & 0x0f),
+        _ => first,
+    }
+}
+
+///
+/// Get the nth pixel stored in a byte. When pixels are 4 bits, the even
+/// pixels are stored in the high half of the byte and the odd pixels in the
+/// low half.
+///
+fn get_pixel(byte: u8, pixel: usize, bits: usize) -> u8 {
+    match bits {
+        4 if pixel % 2 == 0 => byte >> 4,
+        4 => byte & 0x0f,
+        _ => byte,
+    }
+}
+
+///
+/// Create an error for when the run length encoded data ends early
+///
+fn truncated(offset: usize, needed: usize, length: usize) -> BitmapError {
+    BitmapError::Truncated {
+        section: "run length encoded pixels",
+        offset,
+        needed,
+        available: length.saturating_sub(offset),
+    }
+}
+
+///
+/// Set the index of a pixel so that it's no longer skipped, ignoring any
+/// pixels that fall outside of the image
+///
+fn set_index(
+    indices: &mut [u8],
+    skipped: &mut PixelSet,
+    width: usize,
+    x: usize,
+    y: usize,
+    index: u8,
+) {
+    if x < width {
+        if let Some(slot) = indices.get_mut(y * width + x) {
+            *slot = index;
+            skipped.remove(y * width + x);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::decode_rle4;
+    use super::decode_rle8;
+    use super::encode_rle4;
+    use super::encode_rle8;
+    use super::DecodeWarning;
+    use super::PixelSet;
+    use super::Recovery;
+    use crate::bitmap::rgba::Rgba;
+
+    fn as_options((indices, skipped): (Vec<u8>, PixelSet)) -> Vec<Option<u8>> {
+        indices
+            .iter()
+            .enumerate()
+            .map(|(i, index)| match skipped.contains(i) {
+                true => None,
+                false => Some(*index),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decode_encoded_runs() {
+        let bytes = [3, 1, 1, 2, 0, 0, 2, 3, 2, 4, 0, 1];
+        let indices = as_options(decode_rle8(&bytes, 4, 2, None).unwrap());
+        assert_eq!(
+            indices,
+            vec![
+                Some(1),
+                Some(1),
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(3),
+                Some(4),
+                Some(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_absolute_runs() {
+        // an odd number of absolute bytes is padded to a 16 bit boundary
+        let bytes = [0, 3, 5, 6, 7, 0, 1, 8, 0, 0, 0, 4, 1, 2, 3, 4, 0, 1];
+        let indices = as_options(decode_rle8(&bytes, 4, 2, None).unwrap());
+        assert_eq!(
+            indices,
+            vec![
+                Some(5),
+                Some(6),
+                Some(7),
+                Some(8),
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn skipped_pixels_have_no_index() {
+        // move 1 right and 1 up, then end the bitmap early
+        let bytes = [1, 9, 0, 2, 1, 1, 1, 7, 0, 1];
+        let indices = as_options(decode_rle8(&bytes, 3, 3, None).unwrap());
+        assert_eq!(
+            indices,
+            vec![Some(9), None, None, None, None, Some(7), None, None, None]
+        );
+    }
+
+    #[test]
+    fn runs_past_the_end_of_a_row_are_ignored() {
+        let bytes = [5, 1, 0, 0, 2, 2, 0, 1];
+        let indices = as_options(decode_rle8(&bytes, 2, 2, None).unwrap());
+        assert_eq!(indices, vec![Some(1), Some(1), Some(2), Some(2)]);
+    }
+
+    #[test]
+    fn fail_to_decode_truncated_data() {
+        assert!(decode_rle8(&[2, 1], 2, 2, None).is_err());
+        assert!(decode_rle8(&[0, 4, 1, 2], 4, 1, None).is_err());
+        assert!(decode_rle8(&[0, 2, 1], 4, 1, None).is_err());
+    }
+
+    #[test]
+    fn stop_decoding_truncated_data_when_recovering() {
+        let mut recovery = Recovery::new(Rgba::black());
+        let indices = as_options(decode_rle8(&[2, 1, 0, 0, 2], 2, 2, Some(&mut recovery)).unwrap());
+        assert_eq!(indices, vec![Some(1), Some(1), None, None]);
+        assert_eq!(
+            recovery.into_warnings(),
+            vec![DecodeWarning::PixelDataTruncated {
+                needed: 6,
+                available: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn decode_rle4_encoded_runs() {
+        // runs alternate between the high and the low nibble
+        let bytes = [3, 0x12, 1, 0x30, 0, 0, 4, 0x44, 0, 1];
+        let indices = as_options(decode_rle4(&bytes, 4, 2, None).unwrap());
+        assert_eq!(
+            indices,
+            vec![
+                Some(1),
+                Some(2),
+                Some(1),
+                Some(3),
+                Some(4),
+                Some(4),
+                Some(4),
+                Some(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_rle4_absolute_runs() {
+        // 3 pixels take 2 bytes which are already on a 16 bit boundary, 5
+        // pixels take 3 bytes and are padded with another byte
+        let bytes = [
+            0, 3, 0x56, 0x70, 1, 0x80, 0, 0, 0, 5, 0x12, 0x34, 0x50, 0, 0, 1,
+        ];
+        let indices = as_options(decode_rle4(&bytes, 5, 2, None).unwrap());
+        assert_eq!(
+            indices,
+            vec![
+                Some(5),
+                Some(6),
+                Some(7),
+                Some(8),
+                None,
+                Some(1),
+                Some(2),
+                Some(3),
+                Some(4),
+                Some(5)
+            ]
+        );
+    }
+
+    #[test]
+    fn fail_to_decode_truncated_rle4_data() {
+        assert!(decode_rle4(&[0, 5, 0x12, 0x34], 5, 1, None).is_err());
+        assert!(decode_rle4(&[4, 0x12], 4, 2, None).is_err());
+    }
+
+    #[test]
+    fn encode_repeated_and_unique_pixels() {
+        // a run of 2 at the end of a row is cheaper to keep in absolute mode
+        let indices = [1, 1, 1, 1, 2, 3, 4, 5, 6, 6];
+        let bytes = encode_rle8(&indices, 5);
+        assert_eq!(bytes, vec![4, 1, 1, 2, 0, 0, 0, 5, 3, 4, 5, 6, 6, 0, 0, 1]);
+        let decoded = as_options(decode_rle8(&bytes, 5, 2, None).unwrap());
+        assert_eq!(
+            decoded,
+            indices.iter().map(|i| Some(*i)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn encode_rle4_pixels() {
+        let indices = [7, 7, 7, 7, 7, 1, 2, 3, 4, 5, 9];
+        let bytes = encode_rle4(&indices, 11);
+        assert_eq!(bytes, vec![5, 0x77, 0, 6, 0x12, 0x34, 0x59, 0, 0, 1]);
+        let decoded = as_options(decode_rle4(&bytes, 11, 1, None).unwrap());
+        assert_eq!(
+            decoded,
+            indices.iter().map(|i| Some(*i)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn encode_and_decode_long_rows() {
+        for bits in &[4, 8] {
+            let mask = if *bits == 4 { 0x0f } else { 0xff };
+            let width = 700;
+            let indices: Vec<u8> = (0..width * 3)
+                .map(|i| match i % 700 {
+                    0..=299 => 3,
+                    300..=599 => ((i * 7) % 13) as u8,
+                    _ => (i / 50) as u8 & mask,
+                })
+                .collect();
+            let bytes = match bits {
+                4 => encode_rle4(&indices, width as u32),
+                _ => encode_rle8(&indices, width as u32),
+            };
+            let decoded = match bits {
+                4 => as_options(decode_rle4(&bytes, width as u32, 3, None).unwrap()),
+                _ => as_options(decode_rle8(&bytes, width as u32, 3, None).unwrap()),
+            };
+            assert_eq!(
+                decoded,
+                indices.iter().map(|i| Some(*i)).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn encode_empty_image() {
+        assert_eq!(encode_rle8(&[], 0), vec![0, 1]);
+        assert_eq!(encode_rle8(&[], 4), vec![0, 1]);
+    }
+}
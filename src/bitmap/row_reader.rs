@@ -0,0 +1,270 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use super::bitmap_info::BitmapInfo;
+use super::error::BitmapError;
+use super::file::File;
+use super::file_data::FileData;
+use super::info_header::InfoHeader;
+use super::rgb_quad::RgbQuad;
+use super::rgba::Rgba;
+
+///
+/// Decodes a bitmap one row, or one band of rows, at a time so that images
+/// too big to fit in memory can be processed. Only the rows that are asked for
+/// are read from the reader, so memory stays proportional to the width of the
+/// image.
+///
+/// Rows are always given back from the top of the image to the bottom, no
+/// matter which way they are stored in the file. Run length encoded images
+/// can't be read this way, since their rows can't be found without decoding
+/// every row that comes before them.
+///
+pub struct RowReader<R> {
+    /// reader the file is read from
+    reader: R,
+    /// information about the image that is being read
+    info: BitmapInfo,
+    /// info header of the file, used to decode the rows
+    header: InfoHeader,
+    /// color table of the file
+    colors: RgbQuad,
+    /// position of the first row of pixel data in the reader
+    data_start: u64,
+    /// number of bytes used by each row in the file, including padding
+    row_size: usize,
+    /// row that is read next, counting from the top of the image
+    next_row: u32,
+}
+
+impl<R: Read + Seek> RowReader<R> {
+    ///
+    /// Read the headers and color table of a bitmap file that starts at the
+    /// current position of the reader
+    ///
+    /// Fails if the headers can't be read, if the pixels are run length
+    /// encoded, or if the image uses a bit depth or compression that can't be
+    /// decoded
+    ///
+    pub fn new(mut reader: R) -> Result<RowReader<R>, BitmapError> {
+        let start = reader.stream_position()?;
+        let (file, header, colors) = File::read_headers(&mut reader)?;
+        if header.is_run_length_encoded() {
+            return Err(BitmapError::UnsupportedCompression(
+                header.get_compression_value(),
+            ));
+        }
+        // decoding no rows checks that the bit depth and compression work
+        FileData::from_slice(&[], &header.for_rows(0), &colors, None)?;
+        Ok(RowReader {
+            info: BitmapInfo::new(&file, &header, &colors)?,
+            data_start: start + file.get_off_bits() as u64,
+            row_size: header.get_row_byte_size(),
+            reader,
+            header,
+            colors,
+            next_row: 0,
+        })
+    }
+
+    ///
+    /// Get the information read from the headers of the file
+    ///
+    pub fn get_info(&self) -> &BitmapInfo {
+        &self.info
+    }
+
+    ///
+    /// Get the width of the image
+    ///
+    pub fn get_width(&self) -> u32 {
+        self.header.get_width()
+    }
+
+    ///
+    /// Get the height of the image
+    ///
+    pub fn get_height(&self) -> u32 {
+        self.header.get_height()
+    }
+
+    ///
+    /// Get the row that is read next, counting from the top of the image
+    ///
+    pub fn get_next_row(&self) -> u32 {
+        self.next_row
+    }
+
+    ///
+    /// Read the next row of the image, or nothing if every row has been read
+    ///
+    pub fn read_row(&mut self) -> Result<Option<Vec<Rgba>>, BitmapError> {
+        if self.next_row >= self.get_height() {
+            return Ok(None);
+        }
+        self.read_rows(1).map(Some)
+    }
+
+    ///
+    /// Read up to the given number of rows, starting with the next row. The
+    /// pixels of each row follow the pixels of the row above it
+    ///
+    /// Gives back fewer rows if the bottom of the image is reached, and no
+    /// pixels at all once every row has been read. If the rows can't be read
+    /// they are still skipped over, so the rows after them can be read
+    ///
+    pub fn read_rows(&mut self, count: u32) -> Result<Vec<Rgba>, BitmapError> {
+        let count = count.min(self.get_height() - self.next_row);
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        // the rows are stored one after another in the file, but in the
+        // opposite order if the image is stored from the bottom up
+        let first_stored_row = match self.header.is_top_down() {
+            true => self.next_row,
+            false => self.get_height() - self.next_row - count,
+        };
+        let offset = first_stored_row as u64 * self.row_size as u64;
+        self.next_row += count;
+        self.reader
+            .seek(SeekFrom::Start(self.data_start + offset))?;
+        let mut bytes = Vec::new();
+        (&mut self.reader)
+            .take(count as u64 * self.row_size as u64)
+            .read_to_end(&mut bytes)?;
+
+        // errors are reported from the start of the file
+        let file_offset = self.info.get_data_offset() as usize + offset as usize;
+        let header = self.header.for_rows(count);
+        let data = FileData::from_slice(&bytes, &header, &self.colors, None)
+            .map_err(|why| why.offset_by(file_offset))?;
+
+        let pixels = data.as_rgba();
+        let width = self.get_width() as usize;
+        if self.header.is_top_down() || width == 0 {
+            return Ok(pixels);
+        }
+        Ok(pixels.chunks(width).rev().flatten().copied().collect())
+    }
+}
+
+impl<R: Read + Seek> Iterator for RowReader<R> {
+    type Item = Result<Vec<Rgba>, BitmapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_row().transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::BitmapError;
+    use super::RowReader;
+    use crate::bitmap::bit_depth::BitDepth;
+    use crate::bitmap::image::BitMap;
+    use crate::bitmap::rgba::Rgba;
+    use crate::bitmap::save_options::SaveOptions;
+    use crate::bitmap::test_util::{create_bitmap, get_row};
+
+    #[test]
+    fn read_rows_from_the_top_down() {
+        let bitmap = create_bitmap();
+        for bit_depth in [BitDepth::Color256Bit, BitDepth::AllColors].iter() {
+            for top_down in [false, true].iter() {
+                let options = SaveOptions::new()
+                    .with_bit_depth(*bit_depth)
+                    .with_top_down(*top_down);
+                let bytes = bitmap.to_bytes(&options).unwrap();
+                let reader = RowReader::new(Cursor::new(bytes)).unwrap();
+                let rows: Vec<Vec<Rgba>> = reader.map(|row| row.unwrap()).collect();
+                assert_eq!(rows.len(), 4);
+                for (y, row) in rows.iter().enumerate() {
+                    assert_eq!(row, &get_row(&bitmap, y as u32));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn read_bands_of_rows() {
+        let bitmap = create_bitmap();
+        let options = SaveOptions::new().with_bit_depth(BitDepth::Color16Bit);
+        let bytes = bitmap.to_bytes(&options).unwrap();
+        let mut reader = RowReader::new(Cursor::new(bytes)).unwrap();
+        let band = reader.read_rows(3).unwrap();
+        assert_eq!(band[..5], get_row(&bitmap, 0)[..]);
+        assert_eq!(band[10..], get_row(&bitmap, 2)[..]);
+        assert_eq!(reader.get_next_row(), 3);
+        assert_eq!(reader.read_rows(3).unwrap(), get_row(&bitmap, 3));
+        assert!(reader.read_rows(3).unwrap().is_empty());
+        assert!(reader.read_row().unwrap().is_none());
+    }
+
+    #[test]
+    fn read_a_file_that_doesnt_start_at_the_beginning() {
+        let bitmap = create_bitmap();
+        let mut bytes = vec![0xff; 7];
+        bytes.append(&mut bitmap.to_bytes(&SaveOptions::new()).unwrap());
+        let mut cursor = Cursor::new(bytes);
+        cursor.set_position(7);
+        let mut reader = RowReader::new(cursor).unwrap();
+        assert_eq!(reader.read_row().unwrap(), Some(get_row(&bitmap, 0)));
+    }
+
+    #[test]
+    fn read_rows_with_a_huge_number_of_colors_used() {
+        let mut bitmap = BitMap::new(100, 100);
+        bitmap.set_pixel(3, 0, Rgba::rgb(10, 20, 30)).unwrap();
+        bitmap.set_pixel(99, 99, Rgba::black()).unwrap();
+        let mut bytes = bitmap.to_bytes(&SaveOptions::new()).unwrap();
+        // colors used of a 24 bit image, which has no color table
+        bytes[46..50].copy_from_slice(&u32::MAX.to_le_bytes());
+        let reader = RowReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.get_info().get_palette().len(), 256);
+        let rows: Vec<Vec<Rgba>> = reader.map(|row| row.unwrap()).collect();
+        assert_eq!(rows.len(), 100);
+        for (y, row) in rows.iter().enumerate() {
+            assert_eq!(row, &get_row(&bitmap, y as u32));
+        }
+    }
+
+    #[test]
+    fn fail_to_read_a_header_with_an_unknown_size() {
+        let bitmap = create_bitmap();
+        let mut bytes = bitmap.to_bytes(&SaveOptions::new()).unwrap();
+        bytes[14..18].copy_from_slice(&0xffff_fff0u32.to_le_bytes());
+        // only the file header and the size of the info header are read
+        let mut cursor = Cursor::new(bytes);
+        match RowReader::new(&mut cursor) {
+            Err(BitmapError::UnsupportedHeader(0xffff_fff0)) => (),
+            _ => panic!("expected the header size to be unsupported"),
+        }
+        assert_eq!(cursor.position(), 18);
+    }
+
+    #[test]
+    fn fail_to_read_run_length_encoded_rows() {
+        let bitmap = create_bitmap();
+        let options = SaveOptions::new()
+            .with_bit_depth(BitDepth::Color256Bit)
+            .with_rle(true);
+        let bytes = bitmap.to_bytes(&options).unwrap();
+        match RowReader::new(Cursor::new(bytes)) {
+            Err(BitmapError::UnsupportedCompression(1)) => (),
+            _ => panic!("expected run length encoding to be unsupported"),
+        }
+    }
+
+    #[test]
+    fn fail_to_read_missing_rows() {
+        let bitmap = create_bitmap();
+        let mut bytes = bitmap.to_bytes(&SaveOptions::new()).unwrap();
+        // the top row is stored last
+        bytes.truncate(bytes.len() - 10);
+        let mut reader = RowReader::new(Cursor::new(bytes)).unwrap();
+        assert!(reader.read_row().is_err());
+        assert_eq!(reader.get_next_row(), 1);
+        assert_eq!(reader.filter(|row| row.is_ok()).count(), 3);
+    }
+}
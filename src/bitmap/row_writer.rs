@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::io::{Seek, SeekFrom, Write};
+
+use super::bit_data::BitData;
+use super::bit_depth::BitDepth;
+use super::error::BitmapError;
+use super::file_header::FileHeader;
+use super::info_header::InfoHeader;
+use super::pixel_data::PixelData;
+use super::rgb_quad::RgbQuad;
+use super::rgba::Rgba;
+use super::save_options::SaveOptions;
+
+///
+/// Encodes a bitmap one row, or one band of rows, at a time so that images
+/// too big to fit in memory can be written. The headers and color table are
+/// written as soon as the writer is created, and each row is written as soon
+/// as it's given.
+///
+/// Rows are always given from the top of the image to the bottom. Unless the
+/// image is saved top-down, the rows are stored from the bottom up by seeking
+/// to where each row goes in the file. Run length encoded images can't be
+/// written this way.
+///
+pub struct RowWriter<W> {
+    /// writer the file is written to
+    writer: W,
+    /// info header of the file, used to encode the rows
+    header: InfoHeader,
+    /// bit depth the rows are written at
+    bit_depth: BitDepth,
+    /// index of each color in the color table, empty if there isn't one
+    indices: HashMap<Rgba, u8>,
+    /// position of the first row of pixel data in the writer
+    data_start: u64,
+    /// number of bytes used by each row in the file, including padding
+    row_size: usize,
+    /// row that is written next, counting from the top of the image
+    next_row: u32,
+}
+
+impl<W: Write + Seek> RowWriter<W> {
+    ///
+    /// Start writing a 16, 24 or 32 bit image of the given size at the current
+    /// position of the writer
+    ///
+    /// If the options don't give a bit depth, the image is written as a 24 bit
+    /// image, or a 32 bit image if an alpha mask is requested. Fails if the
+    /// bit depth needs a color table, if run length encoding is requested, or
+    /// if the image is too big to be stored in a bitmap file
+    ///
+    pub fn new(
+        writer: W,
+        width: u32,
+        height: u32,
+        options: &SaveOptions,
+    ) -> Result<RowWriter<W>, BitmapError> {
+        let bit_depth = match options.get_bit_depth() {
+            Some(bit_depth) => bit_depth,
+            None if options.has_alpha_mask() => BitDepth::AllColorsAndShades,
+            None => BitDepth::AllColors,
+        };
+        if bit_depth.get_max_colors().is_some() {
+            return Err(BitmapError::InvalidArgument(
+                "2, 16 and 256 color images need a palette, use RowWriter::with_palette",
+            ));
+        }
+        RowWriter::create(writer, width, height, bit_depth, Vec::new(), options)
+    }
+
+    ///
+    /// Start writing a 2, 16 or 256 color image of the given size at the
+    /// current position of the writer. Each pixel is stored as the index of
+    /// its color in the palette, which is written in the same order
+    ///
+    /// If the options don't give a bit depth, the smallest one that can hold
+    /// the palette is used. Fails if the bit depth doesn't use a color table
+    /// or can't hold the palette, if the palette has the same color more then
+    /// once, if run length encoding is requested, or if the image is too big
+    /// to be stored in a bitmap file
+    ///
+    pub fn with_palette(
+        writer: W,
+        width: u32,
+        height: u32,
+        options: &SaveOptions,
+        palette: &[Rgba],
+    ) -> Result<RowWriter<W>, BitmapError> {
+        let bit_depth = match options.get_bit_depth() {
+            Some(bit_depth) => bit_depth,
+            None if palette.len() <= 2 => BitDepth::Color2Bit,
+            None if palette.len() <= 16 => BitDepth::Color16Bit,
+            None => BitDepth::Color256Bit,
+        };
+        match bit_depth.get_max_colors() {
+            None => {
+                return Err(BitmapError::InvalidArgument(
+                    "Only 2, 16 and 256 color images are written using a palette.",
+                ))
+            }
+            Some(max_colors) if palette.len() > max_colors => {
+                return Err(BitmapError::InvalidArgument(
+                    "Palette has too many colors for the requested bit depth.",
+                ))
+            }
+            _ => (),
+        }
+        RowWriter::create(writer, width, height, bit_depth, palette.to_vec(), options)
+    }
+
+    ///
+    /// Write the headers and color table, and set up the writer for the rows
+    ///
+    fn create(
+        mut writer: W,
+        width: u32,
+        height: u32,
+        bit_depth: BitDepth,
+        palette: Vec<Rgba>,
+        options: &SaveOptions,
+    ) -> Result<RowWriter<W>, BitmapError> {
+        if options.is_rle() {
+            return Err(BitmapError::InvalidArgument(
+                "Run length encoded images can't be written a row at a time.",
+            ));
+        }
+        if width > i32::MAX as u32 || height > i32::MAX as u32 {
+            return Err(BitmapError::InvalidArgument(
+                "The width and height of the image must fit in 31 bits.",
+            ));
+        }
+        let mut indices = HashMap::new();
+        for (i, color) in palette.iter().enumerate() {
+            if indices.insert(*color, i as u8).is_some() {
+                return Err(BitmapError::InvalidArgument(
+                    "Palette can't have the same color more then once.",
+                ));
+            }
+        }
+
+        let header = InfoHeader::new(width, height, bit_depth, palette.len() as u32, options);
+        let colors = RgbQuad::from_colors(palette);
+        let row_size = header.get_row_byte_size();
+        let data_size = row_size as u64 * height as u64;
+        let header_size = (colors.get_bytes_size() + header.get_byte_size()) as u64 + 14;
+        if data_size + header_size > u32::MAX as u64 {
+            return Err(BitmapError::InvalidArgument(
+                "Image is too big to be stored in a bitmap file.",
+            ));
+        }
+        let file = FileHeader::new(
+            data_size as u32,
+            colors.get_bytes_size(),
+            header.get_byte_size(),
+        );
+
+        let start = writer.stream_position()?;
+        writer.write_all(&file.as_bytes())?;
+        writer.write_all(&header.as_bytes())?;
+        writer.write_all(&colors.as_bytes())?;
+        Ok(RowWriter {
+            writer,
+            header,
+            bit_depth,
+            indices,
+            data_start: start + file.get_off_bits() as u64,
+            row_size,
+            next_row: 0,
+        })
+    }
+
+    ///
+    /// Get the width of the image
+    ///
+    pub fn get_width(&self) -> u32 {
+        self.header.get_width()
+    }
+
+    ///
+    /// Get the height of the image
+    ///
+    pub fn get_height(&self) -> u32 {
+        self.header.get_height()
+    }
+
+    ///
+    /// Get the row that is written next, counting from the top of the image
+    ///
+    pub fn get_next_row(&self) -> u32 {
+        self.next_row
+    }
+
+    ///
+    /// Write the next row of the image
+    ///
+    /// Fails if the row isn't as wide as the image, if every row has already
+    /// been written, or if a color isn't in the palette
+    ///
+    pub fn write_row(&mut self, row: &[Rgba]) -> Result<(), BitmapError> {
+        if row.len() != self.get_width() as usize {
+            return Err(BitmapError::InvalidArgument(
+                "Row must have as many pixels as the image is wide.",
+            ));
+        }
+        self.write_rows(row)
+    }
+
+    ///
+    /// Write the next rows of the image. The pixels of each row follow the
+    /// pixels of the row above it
+    ///
+    /// Fails if the pixels don't make up whole rows, if there are more rows
+    /// then are left to write, or if a color isn't in the palette. Nothing is
+    /// written if it fails
+    ///
+    pub fn write_rows(&mut self, rows: &[Rgba]) -> Result<(), BitmapError> {
+        let width = self.get_width() as usize;
+        let count = match width {
+            0 => 0,
+            width if rows.len() % width == 0 => rows.len() / width,
+            _ => {
+                return Err(BitmapError::InvalidArgument(
+                    "Rows must have as many pixels as the image is wide.",
+                ))
+            }
+        };
+        if count > (self.get_height() - self.next_row) as usize {
+            return Err(BitmapError::InvalidArgument(
+                "There are more rows then are left in the image.",
+            ));
+        }
+        if count == 0 {
+            return Ok(());
+        }
+
+        // rows that are stored from the bottom up have to be reversed
+        let pixels: Vec<Rgba> = match self.header.is_top_down() {
+            true => rows.to_vec(),
+            false => rows.chunks(width).rev().flatten().copied().collect(),
+        };
+        let bytes = self.encode(pixels)?;
+        let first_stored_row = match self.header.is_top_down() {
+            true => self.next_row,
+            false => self.get_height() - self.next_row - count as u32,
+        };
+        let offset = first_stored_row as u64 * self.row_size as u64;
+        self.writer
+            .seek(SeekFrom::Start(self.data_start + offset))?;
+        self.writer.write_all(&bytes)?;
+        self.next_row += count as u32;
+        Ok(())
+    }
+
+    ///
+    /// Encode rows that are in the order they are stored in the file
+    ///
+    fn encode(&self, pixels: Vec<Rgba>) -> Result<Vec<u8>, BitmapError> {
+        if self.bit_depth.get_max_colors().is_none() {
+            let bit_fields = self.header.get_bit_fields();
+            let data = PixelData::from_pixels(pixels, self.get_width(), self.bit_depth, bit_fields);
+            return Ok(data.as_bytes());
+        }
+        let mut indices = Vec::with_capacity(pixels.len());
+        for pixel in &pixels {
+            match self.indices.get(pixel) {
+                Some(index) => indices.push(*index),
+                None => {
+                    return Err(BitmapError::InvalidArgument(
+                        "Row has a color that isn't in the palette.",
+                    ))
+                }
+            }
+        }
+        Ok(BitData::pack_indices(
+            &indices,
+            self.get_width(),
+            self.bit_depth,
+        ))
+    }
+
+    ///
+    /// Finish writing the image, leaving the writer at the end of the file,
+    /// and give back the writer
+    ///
+    /// Fails if not every row was written
+    ///
+    pub fn finish(mut self) -> Result<W, BitmapError> {
+        if self.next_row != self.get_height() {
+            return Err(BitmapError::InvalidArgument(
+                "Every row must be written before the image is finished.",
+            ));
+        }
+        let data_size = self.row_size as u64 * self.get_height() as u64;
+        self.writer
+            .seek(SeekFrom::Start(self.data_start + data_size))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::BitDepth;
+    use super::BitmapError;
+    use super::Rgba;
+    use super::RowWriter;
+    use super::SaveOptions;
+    use crate::bitmap::image::BitMap;
+    use crate::bitmap::test_util::{create_bitmap, get_row};
+
+    fn write(bitmap: &BitMap, options: &SaveOptions, palette: &[Rgba]) -> Vec<u8> {
+        let cursor = Cursor::new(Vec::new());
+        let mut writer = match palette.len() {
+            0 => RowWriter::new(cursor, 5, 4, options).unwrap(),
+            _ => RowWriter::with_palette(cursor, 5, 4, options, palette).unwrap(),
+        };
+        for y in 0..4 {
+            writer.write_row(&get_row(bitmap, y)).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn write_the_same_bytes_as_saving() {
+        let bitmap = create_bitmap();
+        for bit_depth in [BitDepth::HighColor, BitDepth::AllColors].iter() {
+            for top_down in [false, true].iter() {
+                let options = SaveOptions::new()
+                    .with_bit_depth(*bit_depth)
+                    .with_top_down(*top_down);
+                let bytes = write(&bitmap, &options, &[]);
+                assert_eq!(bytes, bitmap.to_bytes(&options).unwrap());
+            }
+        }
+        let options = SaveOptions::new().with_alpha_mask(true);
+        let bytes = write(&bitmap, &options, &[]);
+        assert_eq!(bytes, bitmap.to_bytes(&options).unwrap());
+    }
+
+    #[test]
+    fn write_rows_using_a_palette() {
+        let bitmap = create_bitmap();
+        let palette: Vec<Rgba> = bitmap
+            .get_all_unique_colors()
+            .into_iter()
+            .copied()
+            .collect();
+        let bytes = write(&bitmap, &SaveOptions::new(), &palette);
+        let options = SaveOptions::new().with_bit_depth(BitDepth::Color16Bit);
+        assert_eq!(bytes, bitmap.to_bytes(&options).unwrap());
+
+        let options = SaveOptions::new().with_bit_depth(BitDepth::Color256Bit);
+        let bytes = write(&bitmap, &options, &palette);
+        assert!(BitMap::from_bytes(&bytes).unwrap() == bitmap);
+    }
+
+    #[test]
+    fn write_bands_of_rows() {
+        let bitmap = create_bitmap();
+        let options = SaveOptions::new().with_bit_depth(BitDepth::AllColors);
+        let mut writer = RowWriter::new(Cursor::new(Vec::new()), 5, 4, &options).unwrap();
+        let mut band = get_row(&bitmap, 0);
+        band.extend(get_row(&bitmap, 1));
+        band.extend(get_row(&bitmap, 2));
+        writer.write_rows(&band).unwrap();
+        assert_eq!(writer.get_next_row(), 3);
+        assert!(writer.write_rows(&band).is_err());
+        writer.write_rows(&get_row(&bitmap, 3)).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+        assert!(BitMap::from_bytes(&bytes).unwrap() == bitmap);
+    }
+
+    #[test]
+    fn fail_to_write_bad_rows() {
+        let cursor = Cursor::new(Vec::new());
+        let palette = [Rgba::black(), Rgba::white()];
+        let mut writer =
+            RowWriter::with_palette(cursor, 2, 2, &SaveOptions::new(), &palette).unwrap();
+        assert!(writer.write_row(&[Rgba::black()]).is_err());
+        assert!(writer
+            .write_row(&[Rgba::black(), Rgba::rgb(1, 2, 3)])
+            .is_err());
+        assert_eq!(writer.get_next_row(), 0);
+        writer.write_row(&[Rgba::black(), Rgba::white()]).unwrap();
+        match writer.finish() {
+            Err(BitmapError::InvalidArgument(_)) => (),
+            _ => panic!("expected the missing row to be reported"),
+        }
+    }
+
+    #[test]
+    fn fail_to_start_writing_with_bad_options() {
+        let options = SaveOptions::new().with_bit_depth(BitDepth::Color256Bit);
+        assert!(RowWriter::new(Cursor::new(Vec::new()), 2, 2, &options).is_err());
+        let palette = vec![Rgba::black(); 3];
+        assert!(
+            RowWriter::with_palette(Cursor::new(Vec::new()), 2, 2, &options, &palette).is_err()
+        );
+        let palette = [Rgba::black(), Rgba::white(), Rgba::rgb(1, 2, 3)];
+        let options = SaveOptions::new().with_bit_depth(BitDepth::Color2Bit);
+        assert!(
+            RowWriter::with_palette(Cursor::new(Vec::new()), 2, 2, &options, &palette).is_err()
+        );
+        let options = SaveOptions::new().with_rle(true);
+        assert!(
+            RowWriter::with_palette(Cursor::new(Vec::new()), 2, 2, &options, &palette).is_err()
+        );
+        let options = SaveOptions::new().with_bit_depth(BitDepth::AllColorsAndShades);
+        assert!(RowWriter::new(Cursor::new(Vec::new()), 0x10000, 0x10000, &options).is_err());
+    }
+}
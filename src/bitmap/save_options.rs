@@ -0,0 +1,316 @@
+use super::bit_depth::BitDepth;
+use super::dither::Dither;
+use super::header_version::HeaderVersion;
+use super::palette::Palette;
+use super::quantizer::Quantizer;
+
+///
+/// Options that change the way a bitmap is written to disk
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct SaveOptions {
+    /// bit depth to write the image at, if none it's picked from the image
+    bit_depth: Option<BitDepth>,
+    /// version of the info header to write
+    header: HeaderVersion,
+    /// write the 32 bit pixels using bit fields with an explicit alpha mask
+    alpha_mask: bool,
+    /// write the 16 bit pixels as 5-6-5 instead of 5-5-5
+    rgb565: bool,
+    /// compress 16 and 256 color images using run length encoding
+    rle: bool,
+    /// store the rows from the top of the image to the bottom
+    top_down: bool,
+    /// write the palette of the bitmap instead of building a new one
+    keep_palette: bool,
+    /// reduces the colors of images that have too many for the bit depth
+    quantizer: Option<Quantizer>,
+    /// dithers images that are given colors from a smaller palette
+    dither: Option<Dither>,
+    /// palette every image is saved with, whatever colors it has
+    palette: Option<Palette>,
+}
+
+impl SaveOptions {
+    ///
+    /// Create the default save options, which write a plain BITMAPINFOHEADER
+    /// without any channel masks
+    ///
+    pub fn new() -> SaveOptions {
+        SaveOptions {
+            bit_depth: None,
+            header: HeaderVersion::Info,
+            alpha_mask: false,
+            rgb565: false,
+            rle: false,
+            top_down: false,
+            keep_palette: false,
+            quantizer: None,
+            dither: None,
+            palette: None,
+        }
+    }
+
+    ///
+    /// Write the image using the given bit depth instead of picking one based
+    /// on the colors in the image
+    ///
+    pub fn with_bit_depth(mut self, bit_depth: BitDepth) -> SaveOptions {
+        self.bit_depth = Some(bit_depth);
+        self
+    }
+
+    ///
+    /// Write the image using the given version of the info header.
+    ///
+    /// OS/2 headers can only be read, asking for one writes a
+    /// BITMAPINFOHEADER instead.
+    ///
+    pub fn with_header(mut self, header: HeaderVersion) -> SaveOptions {
+        self.header = header;
+        self
+    }
+
+    ///
+    /// Write the image as a 32 bit image with explicit red, green, blue and
+    /// alpha masks so that other programs know the image is transparent.
+    ///
+    /// Because only V4 headers and newer can hold an alpha mask, a
+    /// BITMAPINFOHEADER will be upgraded to a BITMAPV4HEADER.
+    ///
+    pub fn with_alpha_mask(mut self, alpha_mask: bool) -> SaveOptions {
+        self.alpha_mask = alpha_mask;
+        self
+    }
+
+    ///
+    /// Write 16 bit images using 5 bits for red and blue and 6 bits for green.
+    /// By default 16 bit images use 5 bits for each color.
+    ///
+    pub fn with_rgb565(mut self, rgb565: bool) -> SaveOptions {
+        self.rgb565 = rgb565;
+        self
+    }
+
+    ///
+    /// Compress 16 color images using RLE4 and 256 color images using RLE8.
+    /// Images that have large areas of the same color become a lot smaller.
+    ///
+    /// Run length encoding only works for 16 and 256 color images, images
+    /// saved at any other bit depth are written uncompressed. If no bit depth
+    /// is given, the smallest bit depth that can be compressed is picked.
+    ///
+    pub fn with_rle(mut self, rle: bool) -> SaveOptions {
+        self.rle = rle;
+        self
+    }
+
+    ///
+    /// Store the rows of the image starting at the top instead of the bottom.
+    /// Top-down images are marked by writing a negative height.
+    ///
+    /// Run length encoded images can't be top-down, so they are always written
+    /// starting at the bottom.
+    ///
+    pub fn with_top_down(mut self, top_down: bool) -> SaveOptions {
+        self.top_down = top_down;
+        self
+    }
+
+    ///
+    /// Write 2, 16 and 256 color images using the palette of the bitmap, which
+    /// is the color table of the file it was read from, instead of building a
+    /// new one from the colors in the image. Every entry is written in the
+    /// same order, even the ones that aren't used, and each pixel that is
+    /// still the color it was read in as keeps its index, even if the color
+    /// is in the palette more then once.
+    ///
+    /// If no bit depth is given, the smallest one that can hold the palette is
+    /// picked. Bitmaps without a palette are written as usual.
+    ///
+    pub fn with_keep_palette(mut self, keep_palette: bool) -> SaveOptions {
+        self.keep_palette = keep_palette;
+        self
+    }
+
+    ///
+    /// Reduce the colors of images that have more colors then the color table
+    /// of the bit depth can hold, instead of failing to save them. Only
+    /// matters when the image is saved as a 2, 16 or 256 color image
+    ///
+    pub fn with_quantizer(mut self, quantizer: Quantizer) -> SaveOptions {
+        self.quantizer = Some(quantizer);
+        self
+    }
+
+    ///
+    /// Dither images that have more colors then they can be saved with, when
+    /// the colors are reduced by the quantizer or when the image is saved
+    /// with a kept palette that is missing some of its colors. If there's no
+    /// quantizer, median cut is used to reduce the colors
+    ///
+    pub fn with_dither(mut self, dither: Dither) -> SaveOptions {
+        self.dither = Some(dither);
+        self
+    }
+
+    ///
+    /// Save the image with a fixed palette, giving each pixel the closest
+    /// color in it, so that images saved with the same palette share one
+    /// color table. The pixels are dithered if a dither is given.
+    ///
+    /// If no bit depth is given, the smallest one that can hold the palette is
+    /// picked. This replaces the palette of the bitmap, even if it's kept
+    ///
+    pub fn with_palette(mut self, palette: Palette) -> SaveOptions {
+        self.palette = Some(palette);
+        self
+    }
+
+    ///
+    /// Get the bit depth the image will be written at, if one was given
+    ///
+    pub fn get_bit_depth(&self) -> Option<BitDepth> {
+        self.bit_depth
+    }
+
+    ///
+    /// Get the version of the info header that will be written
+    ///
+    pub fn get_header(&self) -> HeaderVersion {
+        match self.header {
+            HeaderVersion::Info if self.alpha_mask => HeaderVersion::V4,
+            header if header.is_os2() && self.alpha_mask => HeaderVersion::V4,
+            header if header.is_os2() => HeaderVersion::Info,
+            header => header,
+        }
+    }
+
+    ///
+    /// Check if the image will be written with an explicit alpha mask
+    ///
+    pub fn has_alpha_mask(&self) -> bool {
+        self.alpha_mask
+    }
+
+    ///
+    /// Check if 16 bit images will be written as 5-6-5
+    ///
+    pub fn is_rgb565(&self) -> bool {
+        self.rgb565
+    }
+
+    ///
+    /// Check if 16 and 256 color images will be run length encoded
+    ///
+    pub fn is_rle(&self) -> bool {
+        self.rle
+    }
+
+    ///
+    /// Check if the rows will be stored from the top of the image to the bottom
+    ///
+    pub fn is_top_down(&self) -> bool {
+        self.top_down
+    }
+
+    ///
+    /// Check if the palette of the bitmap will be written instead of a new one
+    ///
+    pub fn keeps_palette(&self) -> bool {
+        self.keep_palette
+    }
+
+    ///
+    /// Get the quantizer used to reduce the colors of the image, if one was
+    /// given
+    ///
+    pub fn get_quantizer(&self) -> Option<Quantizer> {
+        self.quantizer
+    }
+
+    ///
+    /// Get the dither used when the image is given colors from a smaller
+    /// palette, if one was given
+    ///
+    pub fn get_dither(&self) -> Option<Dither> {
+        self.dither
+    }
+
+    ///
+    /// Get the fixed palette the image is saved with, if one was given
+    ///
+    pub fn get_palette(&self) -> Option<&Palette> {
+        self.palette.as_ref()
+    }
+}
+
+impl Default for SaveOptions {
+    fn default() -> SaveOptions {
+        SaveOptions::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Dither;
+    use super::HeaderVersion;
+    use super::Palette;
+    use super::Quantizer;
+    use super::SaveOptions;
+
+    #[test]
+    fn alpha_mask_upgrades_info_header() {
+        let options = SaveOptions::new();
+        assert_eq!(options.get_header(), HeaderVersion::Info);
+        let options = options.with_alpha_mask(true);
+        assert_eq!(options.get_header(), HeaderVersion::V4);
+        let options = options.with_header(HeaderVersion::V5);
+        assert_eq!(options.get_header(), HeaderVersion::V5);
+    }
+
+    #[test]
+    fn os2_headers_are_written_as_info_headers() {
+        let options = SaveOptions::new().with_header(HeaderVersion::Core);
+        assert_eq!(options.get_header(), HeaderVersion::Info);
+        let options = options.with_header(HeaderVersion::Os2);
+        assert_eq!(options.get_header(), HeaderVersion::Info);
+        let options = options.with_alpha_mask(true);
+        assert_eq!(options.get_header(), HeaderVersion::V4);
+    }
+
+    #[test]
+    fn palette_is_only_kept_when_asked() {
+        let options = SaveOptions::new();
+        assert!(!options.keeps_palette());
+        assert!(options.with_keep_palette(true).keeps_palette());
+    }
+
+    #[test]
+    fn colors_are_only_reduced_when_asked() {
+        let options = SaveOptions::new();
+        assert_eq!(options.get_quantizer(), None);
+        let options = options.with_quantizer(Quantizer::Octree);
+        assert_eq!(options.get_quantizer(), Some(Quantizer::Octree));
+        assert_eq!(options.get_dither(), None);
+    }
+
+    #[test]
+    fn dither_when_asked() {
+        use crate::bitmap::dither::DitherMethod;
+
+        let dither = Dither::new(DitherMethod::Sierra).with_serpentine(true);
+        let options = SaveOptions::new().with_dither(dither);
+        assert_eq!(options.get_dither(), Some(dither));
+        assert_eq!(options.get_quantizer(), None);
+    }
+
+    #[test]
+    fn fixed_palette_is_only_used_when_given() {
+        let options = SaveOptions::new();
+        assert_eq!(options.get_palette(), None);
+        let options = options.with_palette(Palette::WebSafe);
+        assert_eq!(options.get_palette(), Some(&Palette::WebSafe));
+        assert!(!options.keeps_palette());
+    }
+}
@@ -0,0 +1,26 @@
+use super::image::BitMap;
+use super::rgba::Rgba;
+
+///
+/// Create a small image where every pixel in a row has a different color,
+/// used by the row reader and row writer tests
+///
+pub fn create_bitmap() -> BitMap {
+    let mut bitmap = BitMap::new(5, 4);
+    for y in 0..4 {
+        for x in 0..5 {
+            let c = ((x * 2 + y * 5) % 16 * 10) as u8;
+            bitmap.set_pixel(x, y, Rgba::rgb(c, 0, 0)).unwrap();
+        }
+    }
+    bitmap
+}
+
+///
+/// Get a row of pixels from an image, counting from the top
+///
+pub fn get_row(bitmap: &BitMap, y: u32) -> Vec<Rgba> {
+    (0..bitmap.get_width())
+        .map(|x| *bitmap.get_pixel(x, y).unwrap())
+        .collect()
+}
@@ -1,15 +1,49 @@
+///
+/// Read the N bytes starting at index and move the index past them
+///
+/// Bytes past the end of the array are read as zero. Callers check the length
+/// of the array before reading, so missing data is reported as an error
+/// instead of being read
+///
+fn read_bytes<const N: usize>(array: &[u8], index: &mut usize) -> [u8; N] {
+    let mut bytes = [0; N];
+    for (offset, byte) in bytes.iter_mut().enumerate() {
+        *byte = index
+            .checked_add(offset)
+            .and_then(|i| array.get(i))
+            .copied()
+            .unwrap_or(0);
+    }
+    *index = index.saturating_add(N);
+    bytes
+}
+
 pub fn byte_slice_to_u32(array: &[u8], index: &mut usize) -> u32 {
-    let i: usize = *index;
-    let a = [array[i], array[i + 1], array[i + 2], array[i + 3]];
-    *index += 4;
-    u32::from_le_bytes(a)
+    u32::from_le_bytes(read_bytes(array, index))
+}
+
+pub fn byte_slice_to_i32(array: &[u8], index: &mut usize) -> i32 {
+    i32::from_le_bytes(read_bytes(array, index))
 }
 
 pub fn byte_slice_to_u16(array: &[u8], index: &mut usize) -> u16 {
-    let i: usize = *index;
-    let a = [array[i], array[i + 1]];
-    *index += 2;
-    u16::from_le_bytes(a)
+    u16::from_le_bytes(read_bytes(array, index))
+}
+
+///
+/// Convert a resolution in pixels per meter to dots per inch, rounded to the
+/// nearest whole number
+///
+pub fn pixels_per_meter_to_dpi(pixels_per_meter: u32) -> u32 {
+    (pixels_per_meter as f64 * 0.0254).round() as u32
+}
+
+///
+/// Convert a resolution in dots per inch to pixels per meter, rounded to the
+/// nearest whole number
+///
+pub fn dpi_to_pixels_per_meter(dpi: u32) -> u32 {
+    (dpi as f64 / 0.0254).round() as u32
 }
 
 pub fn calculate_crop(max_x: u32, max_y: u32, x: u32, y: u32, crop_factor: f32) -> (u32, u32, u32, u32) {
@@ -62,9 +96,41 @@ pub fn calculate_crop(max_x: u32, max_y: u32, x: u32, y: u32, crop_factor: f32)
 
 #[cfg(test)]
 mod test {
+    use crate::bitmap::util::byte_slice_to_u16;
+    use crate::bitmap::util::byte_slice_to_u32;
     use crate::bitmap::util::calculate_crop;
+    use crate::bitmap::util::dpi_to_pixels_per_meter;
+    use crate::bitmap::util::pixels_per_meter_to_dpi;
 
 
+    #[test]
+    pub fn read_past_the_end_of_a_slice() {
+        let bytes = [1, 2, 3];
+        let mut i = 0;
+        assert_eq!(byte_slice_to_u16(&bytes, &mut i), 0x0201);
+        assert_eq!(byte_slice_to_u16(&bytes, &mut i), 0x0003);
+        assert_eq!(byte_slice_to_u32(&bytes, &mut i), 0);
+        assert_eq!(i, 8);
+        let mut i = usize::MAX - 1;
+        assert_eq!(byte_slice_to_u32(&bytes, &mut i), 0);
+        assert_eq!(i, usize::MAX);
+    }
+
+    #[test]
+    pub fn convert_pixels_per_meter_to_dpi() {
+        assert_eq!(pixels_per_meter_to_dpi(0), 0);
+        assert_eq!(pixels_per_meter_to_dpi(2835), 72);
+        assert_eq!(pixels_per_meter_to_dpi(11811), 300);
+    }
+
+    #[test]
+    pub fn convert_dpi_to_pixels_per_meter() {
+        assert_eq!(dpi_to_pixels_per_meter(0), 0);
+        assert_eq!(dpi_to_pixels_per_meter(72), 2835);
+        assert_eq!(dpi_to_pixels_per_meter(300), 11811);
+        assert_eq!(pixels_per_meter_to_dpi(dpi_to_pixels_per_meter(96)), 96);
+    }
+
     #[test]
     pub fn calculate_crop_from_0_0() {
         let (x1, y1, x2, y2) = calculate_crop(100, 100, 0, 0, 0.5);
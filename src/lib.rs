@@ -16,5 +16,25 @@
 ///
 pub mod bitmap;
 
+pub use bitmap::bit_depth::BitDepth;
+pub use bitmap::bitmap_info::BitmapInfo;
+pub use bitmap::compression::Compression;
+pub use bitmap::decode_limits::DecodeLimits;
+pub use bitmap::decode_warning::DecodeWarning;
+pub use bitmap::dither::Dither;
+pub use bitmap::dither::DitherMethod;
+pub use bitmap::error::BitmapError;
+pub use bitmap::header_version::HeaderVersion;
+pub use bitmap::icon::Icon;
+pub use bitmap::icon::IconImage;
+pub use bitmap::icon::IconKind;
 pub use bitmap::image::BitMap;
+pub use bitmap::indexed_image::IndexedBitMap;
+pub use bitmap::lenient_options::LenientOptions;
+pub use bitmap::monochrome::MonochromeMethod;
+pub use bitmap::palette::Palette;
+pub use bitmap::quantizer::Quantizer;
 pub use bitmap::rgba::Rgba;
+pub use bitmap::row_reader::RowReader;
+pub use bitmap::row_writer::RowWriter;
+pub use bitmap::save_options::SaveOptions;
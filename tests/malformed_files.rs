@@ -0,0 +1,460 @@
+extern crate rustbitmap;
+
+use std::io::Cursor;
+
+use rustbitmap::BitDepth;
+use rustbitmap::BitMap;
+use rustbitmap::BitmapError;
+use rustbitmap::BitmapInfo;
+use rustbitmap::DecodeLimits;
+use rustbitmap::DecodeWarning;
+use rustbitmap::HeaderVersion;
+use rustbitmap::LenientOptions;
+use rustbitmap::Rgba;
+use rustbitmap::RowReader;
+use rustbitmap::SaveOptions;
+
+///
+/// Values written over the fields of a header, picked to hit the edges of
+/// every check done while decoding
+///
+const FIELD_VALUES: [u32; 8] = [
+    0,
+    1,
+    0xff,
+    0xffff,
+    0x7fff_ffff,
+    0x8000_0000,
+    0xffff_fffe,
+    0xffff_ffff,
+];
+
+///
+/// Most pixels a malformed file can decode to, since a few bytes of run length
+/// encoded data can describe a huge image
+///
+const MAX_PIXELS: u64 = 1 << 20;
+
+///
+/// Create a small image using the given number of colors
+///
+fn create_bitmap(colors: u8) -> BitMap {
+    let mut bitmap = BitMap::new(7, 5);
+    for y in 0..5 {
+        for x in 0..7 {
+            let c = ((x + y * 7) % colors as u32) as u8;
+            bitmap
+                .set_pixel(x, y, Rgba::rgba(c * 20, 255 - c * 10, c, 100 - c))
+                .unwrap();
+        }
+    }
+    bitmap
+}
+
+///
+/// Build an OS/2 1.x bitmap by hand, since they can't be saved
+///
+fn create_os2_file() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"BM");
+    bytes.extend_from_slice(&40u32.to_le_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&32u32.to_le_bytes());
+    bytes.extend_from_slice(&12u32.to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&[0, 0, 255, 255, 255, 255]);
+    bytes.extend_from_slice(&[0x40, 0, 0, 0, 0x80, 0, 0, 0]);
+    bytes
+}
+
+///
+/// Valid files covering every bit depth, header and compression that can be
+/// read, used as the starting point for the malformed files
+///
+fn valid_files() -> Vec<Vec<u8>> {
+    let options = [
+        SaveOptions::new().with_bit_depth(BitDepth::Color2Bit),
+        SaveOptions::new().with_bit_depth(BitDepth::Color16Bit),
+        SaveOptions::new()
+            .with_bit_depth(BitDepth::Color16Bit)
+            .with_rle(true),
+        SaveOptions::new().with_bit_depth(BitDepth::Color256Bit),
+        SaveOptions::new()
+            .with_bit_depth(BitDepth::Color256Bit)
+            .with_rle(true),
+        SaveOptions::new().with_bit_depth(BitDepth::HighColor),
+        SaveOptions::new()
+            .with_bit_depth(BitDepth::HighColor)
+            .with_rgb565(true),
+        SaveOptions::new().with_bit_depth(BitDepth::AllColors),
+        SaveOptions::new()
+            .with_bit_depth(BitDepth::AllColors)
+            .with_top_down(true),
+        SaveOptions::new().with_bit_depth(BitDepth::AllColorsAndShades),
+        SaveOptions::new()
+            .with_bit_depth(BitDepth::AllColorsAndShades)
+            .with_alpha_mask(true),
+        SaveOptions::new()
+            .with_bit_depth(BitDepth::AllColorsAndShades)
+            .with_header(HeaderVersion::V5),
+    ];
+    let mut files: Vec<Vec<u8>> = options
+        .iter()
+        .map(|options| {
+            let colors = match options.get_bit_depth() {
+                Some(BitDepth::Color2Bit) => 2,
+                _ => 12,
+            };
+            create_bitmap(colors).to_bytes(options).unwrap()
+        })
+        .collect();
+    files.push(create_os2_file());
+    files
+}
+
+///
+/// Simple xorshift generator so that the malformed files are the same every
+/// time the tests are run
+///
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, max: usize) -> usize {
+        (self.next() % max as u64) as usize
+    }
+}
+
+///
+/// Decode the bytes, the only thing that matters is that it doesn't panic
+///
+fn decode(bytes: &[u8]) {
+    let limits = DecodeLimits::new().with_max_pixels(MAX_PIXELS);
+    if let Ok(bitmap) = BitMap::from_bytes_with_limits(bytes, &limits) {
+        let area = bitmap.get_width() as usize * bitmap.get_height() as usize;
+        assert_eq!(bitmap.get_pixels().len(), area);
+    }
+    let options = LenientOptions::new().with_limits(limits);
+    if let Ok((bitmap, _)) = BitMap::from_bytes_lenient(bytes, &options) {
+        let area = bitmap.get_width() as usize * bitmap.get_height() as usize;
+        assert_eq!(bitmap.get_pixels().len(), area);
+    }
+    // probing never allocates the pixels, so it doesn't need any limits
+    let info = BitmapInfo::from_bytes(bytes);
+    assert_eq!(info.is_ok(), BitmapInfo::from_reader(bytes).is_ok());
+    if let Ok(reader) = RowReader::new(Cursor::new(bytes)) {
+        let width = reader.get_width() as usize;
+        if width * reader.get_height() as usize <= MAX_PIXELS as usize {
+            for row in reader.flatten() {
+                assert_eq!(row.len(), width);
+            }
+        }
+    }
+}
+
+#[test]
+fn every_valid_file_can_be_read() {
+    for bytes in valid_files() {
+        let bitmap = BitMap::from_bytes(&bytes).unwrap();
+        if let Ok(reader) = RowReader::new(Cursor::new(&bytes)) {
+            let pixels: Vec<Rgba> = reader.flat_map(|row| row.unwrap()).collect();
+            for (i, pixel) in pixels.iter().enumerate() {
+                let x = i as u32 % bitmap.get_width();
+                let y = i as u32 / bitmap.get_width();
+                assert_eq!(Some(pixel), bitmap.get_pixel(x, y));
+            }
+        }
+        let info = BitmapInfo::from_bytes(&bytes).unwrap();
+        assert_eq!(info.get_width(), bitmap.get_width());
+        assert_eq!(info.get_height(), bitmap.get_height());
+        assert!(info == BitmapInfo::from_reader(&bytes[..]).unwrap());
+    }
+}
+
+#[test]
+fn read_truncated_files() {
+    for bytes in valid_files() {
+        for length in 0..bytes.len() {
+            decode(&bytes[..length]);
+        }
+    }
+}
+
+#[test]
+fn read_files_with_bad_header_fields() {
+    for bytes in valid_files() {
+        let info_size = u32::from_le_bytes([bytes[14], bytes[15], bytes[16], bytes[17]]) as usize;
+        // include the masks and colors that follow the header
+        let end = (14 + info_size + 16).min(bytes.len());
+        for offset in (0..end).step_by(2) {
+            for value in FIELD_VALUES.iter() {
+                let mut malformed = bytes.clone();
+                for (i, byte) in value.to_le_bytes().iter().enumerate() {
+                    if let Some(b) = malformed.get_mut(offset + i) {
+                        *b = *byte;
+                    }
+                }
+                decode(&malformed);
+            }
+        }
+    }
+}
+
+#[test]
+fn read_files_with_random_bytes_changed() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for bytes in valid_files() {
+        for _ in 0..500 {
+            let mut malformed = bytes.clone();
+            for _ in 0..=random.below(4) {
+                let i = random.below(malformed.len());
+                malformed[i] = random.next() as u8;
+            }
+            decode(&malformed);
+        }
+    }
+}
+
+#[test]
+fn read_run_length_encoded_files_with_random_data() {
+    let mut random = Random(0x9e37_79b9_7f4a_7c15);
+    let options = SaveOptions::new()
+        .with_bit_depth(BitDepth::Color256Bit)
+        .with_rle(true);
+    let bytes = create_bitmap(12).to_bytes(&options).unwrap();
+    let off_bits = u32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]) as usize;
+    for _ in 0..500 {
+        let mut malformed = bytes[..off_bits].to_vec();
+        for _ in 0..random.below(64) {
+            // favor the escape codes so that every mode gets used
+            let byte = match random.below(3) {
+                0 => random.below(3) as u8,
+                _ => random.next() as u8,
+            };
+            malformed.push(byte);
+        }
+        decode(&malformed);
+    }
+}
+
+#[test]
+fn probe_huge_images_without_decoding_them() {
+    let mut bytes = BitMap::new(1, 1)
+        .to_bytes(&SaveOptions::new().with_bit_depth(BitDepth::AllColors))
+        .unwrap();
+    set_u32(&mut bytes, 18, 30_000);
+    set_u32(&mut bytes, 22, 30_000);
+    assert!(BitMap::from_bytes(&bytes).is_err());
+    let info = BitmapInfo::from_bytes(&bytes).unwrap();
+    assert_eq!(info.get_width(), 30_000);
+    assert_eq!(info.get_height(), 30_000);
+    assert_eq!(info.get_bit_depth(), Some(BitDepth::AllColors));
+}
+
+#[test]
+fn fail_before_decoding_huge_images() {
+    let options = SaveOptions::new()
+        .with_bit_depth(BitDepth::Color256Bit)
+        .with_rle(true);
+    let mut bytes = create_bitmap(12).to_bytes(&options).unwrap();
+    bytes[18..22].copy_from_slice(&0xffffu32.to_le_bytes());
+    bytes[22..26].copy_from_slice(&0xffffu32.to_le_bytes());
+    match BitMap::from_bytes(&bytes) {
+        Err(BitmapError::LimitExceeded { limit, value, .. }) => {
+            assert_eq!(limit, "pixel count");
+            assert_eq!(value, 0xffff * 0xffff);
+        }
+        _ => panic!("expected the pixel count limit to be exceeded"),
+    }
+    let limits = DecodeLimits::new().with_max_width(100);
+    let bytes = create_bitmap(12).to_bytes(&options).unwrap();
+    assert!(BitMap::from_bytes_with_limits(&bytes, &limits).is_ok());
+    let limits = limits.with_max_width(6);
+    assert!(matches!(
+        BitMap::from_bytes_with_limits(&bytes, &limits),
+        Err(BitmapError::LimitExceeded { limit: "width", .. })
+    ));
+    assert!(matches!(
+        BitMap::from_reader_with_limits(&bytes[..], &limits),
+        Err(BitmapError::LimitExceeded { limit: "width", .. })
+    ));
+}
+
+#[test]
+fn fail_before_decoding_run_length_encoded_images_that_end_right_away() {
+    // a tiny file that ends the bitmap at once still needs memory for every
+    // pixel it declares, so it has to stay under the default limits
+    let options = SaveOptions::new()
+        .with_bit_depth(BitDepth::Color256Bit)
+        .with_rle(true);
+    let bytes = create_bitmap(12).to_bytes(&options).unwrap();
+    let off_bits = u32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]) as usize;
+    let mut bytes = bytes[..off_bits].to_vec();
+    bytes.extend_from_slice(&[0, 1]);
+    set_u32(&mut bytes, 18, 16384);
+    set_u32(&mut bytes, 22, 16384);
+    match BitMap::from_bytes(&bytes) {
+        Err(BitmapError::LimitExceeded { limit, value, max }) => {
+            assert_eq!(limit, "allocation size");
+            assert!(value > max);
+        }
+        _ => panic!("expected the allocation size limit to be exceeded"),
+    }
+    set_u32(&mut bytes, 18, 64);
+    set_u32(&mut bytes, 22, 64);
+    let bitmap = BitMap::from_bytes(&bytes).unwrap();
+    assert_eq!(bitmap.get_pixel(63, 63), Some(&Rgba::rgba(0, 0, 0, 0)));
+}
+
+///
+/// Replace the u32 at the given offset
+///
+fn set_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[test]
+fn ignore_wrong_file_size_when_lenient() {
+    let bitmap = create_bitmap(12);
+    let options = SaveOptions::new().with_bit_depth(BitDepth::AllColors);
+    let mut bytes = bitmap.to_bytes(&options).unwrap();
+    let bitmap = BitMap::from_bytes(&bytes).unwrap();
+    set_u32(&mut bytes, 2, 10);
+    let (read, warnings) = BitMap::from_bytes_lenient(&bytes, &LenientOptions::new()).unwrap();
+    assert!(read == bitmap);
+    assert_eq!(
+        warnings,
+        vec![DecodeWarning::WrongFileSize {
+            declared: 10,
+            actual: bytes.len()
+        }]
+    );
+}
+
+#[test]
+fn pad_truncated_rows_with_the_fill_color() {
+    let bitmap = create_bitmap(12);
+    let red = Rgba::rgb(255, 0, 0);
+    let lenient = LenientOptions::new().with_fill(red);
+    for bit_depth in [BitDepth::Color256Bit, BitDepth::AllColors].iter() {
+        let options = SaveOptions::new().with_bit_depth(*bit_depth);
+        let bytes = bitmap.to_bytes(&options).unwrap();
+        let bitmap = BitMap::from_bytes(&bytes).unwrap();
+        // keep the bottom 3 rows and the first 3 pixels of the 4th row
+        let row_size = match bit_depth {
+            BitDepth::Color256Bit => 8,
+            _ => 24,
+        };
+        let pixel_size = row_size / 8;
+        let length = bytes.len() - row_size * 2 + pixel_size * 3;
+        assert!(BitMap::from_bytes(&bytes[..length]).is_err());
+
+        let (read, warnings) = BitMap::from_bytes_lenient(&bytes[..length], &lenient).unwrap();
+        assert!(warnings.contains(&DecodeWarning::PixelDataTruncated {
+            needed: row_size * 4 + pixel_size * 7,
+            available: row_size * 3 + pixel_size * 3,
+        }));
+        for x in 0..7 {
+            assert_eq!(read.get_pixel(x, 0), Some(&red));
+            for y in 2..5 {
+                assert_eq!(read.get_pixel(x, y), bitmap.get_pixel(x, y));
+            }
+        }
+        for x in 0..3 {
+            assert_eq!(read.get_pixel(x, 1), bitmap.get_pixel(x, 1));
+        }
+        for x in 3..7 {
+            assert_eq!(read.get_pixel(x, 1), Some(&red));
+        }
+    }
+}
+
+#[test]
+fn use_the_colors_found_when_the_color_table_is_short() {
+    let bitmap = create_bitmap(12);
+    let options = SaveOptions::new().with_bit_depth(BitDepth::Color256Bit);
+    let mut bytes = bitmap.to_bytes(&options).unwrap();
+    // zero means the color table has every color the bit depth allows
+    set_u32(&mut bytes, 46, 0);
+    assert!(BitMap::from_bytes(&bytes).is_err());
+
+    let (read, warnings) = BitMap::from_bytes_lenient(&bytes, &LenientOptions::new()).unwrap();
+    assert!(read == bitmap);
+    assert_eq!(
+        warnings,
+        vec![DecodeWarning::ColorTableTruncated {
+            declared: 256,
+            found: 12
+        }]
+    );
+}
+
+#[test]
+fn fill_pixels_outside_of_the_color_table() {
+    let bitmap = create_bitmap(2);
+    let options = SaveOptions::new().with_bit_depth(BitDepth::Color256Bit);
+    let mut bytes = bitmap.to_bytes(&options).unwrap();
+    let off_bits = u32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]) as usize;
+    bytes[off_bits] = 200;
+    bytes[off_bits + 1] = 201;
+    assert!(BitMap::from_bytes(&bytes).is_err());
+
+    let blue = Rgba::rgb(0, 0, 255);
+    let lenient = LenientOptions::new().with_fill(blue);
+    let (read, warnings) = BitMap::from_bytes_lenient(&bytes, &lenient).unwrap();
+    assert_eq!(
+        warnings,
+        vec![DecodeWarning::ColorIndexOutOfRange { count: 2 }]
+    );
+    assert_eq!(read.get_pixel(0, 4), Some(&blue));
+    assert_eq!(read.get_pixel(1, 4), Some(&blue));
+    assert_eq!(read.get_pixel(2, 4), bitmap.get_pixel(2, 4));
+}
+
+#[test]
+fn repair_pixel_data_offset() {
+    let bitmap = create_bitmap(12);
+    let options = SaveOptions::new().with_bit_depth(BitDepth::AllColors);
+    let mut bytes = bitmap.to_bytes(&options).unwrap();
+    let bitmap = BitMap::from_bytes(&bytes).unwrap();
+    set_u32(&mut bytes, 10, 0xffff);
+    assert!(BitMap::from_bytes(&bytes).is_err());
+
+    let (read, warnings) = BitMap::from_bytes_lenient(&bytes, &LenientOptions::new()).unwrap();
+    assert!(read == bitmap);
+    assert_eq!(
+        warnings,
+        vec![DecodeWarning::PixelDataOffsetRepaired {
+            declared: 0xffff,
+            used: 54
+        }]
+    );
+}
+
+#[test]
+fn leave_truncated_run_length_encoded_pixels_transparent() {
+    let bitmap = create_bitmap(12);
+    let options = SaveOptions::new()
+        .with_bit_depth(BitDepth::Color256Bit)
+        .with_rle(true);
+    let bytes = bitmap.to_bytes(&options).unwrap();
+    let length = bytes.len() - 10;
+    assert!(BitMap::from_bytes(&bytes[..length]).is_err());
+
+    let (read, warnings) =
+        BitMap::from_bytes_lenient(&bytes[..length], &LenientOptions::new()).unwrap();
+    assert!(warnings
+        .iter()
+        .any(|w| matches!(w, DecodeWarning::PixelDataTruncated { .. })));
+    assert_eq!(read.get_pixel(6, 0), Some(&Rgba::rgba(0, 0, 0, 0)));
+    assert_eq!(read.get_pixel(0, 4), bitmap.get_pixel(0, 4));
+}
@@ -1,6 +1,24 @@
 extern crate rustbitmap;
 
+use rustbitmap::BitDepth;
 use rustbitmap::BitMap;
+use rustbitmap::BitmapError;
+use rustbitmap::BitmapInfo;
+use rustbitmap::Compression;
+use rustbitmap::Dither;
+use rustbitmap::DitherMethod;
+use rustbitmap::HeaderVersion;
+use rustbitmap::Icon;
+use rustbitmap::IconImage;
+use rustbitmap::IconKind;
+use rustbitmap::IndexedBitMap;
+use rustbitmap::MonochromeMethod;
+use rustbitmap::Palette;
+use rustbitmap::Quantizer;
+use rustbitmap::Rgba;
+use rustbitmap::RowReader;
+use rustbitmap::RowWriter;
+use rustbitmap::SaveOptions;
 
 mod common;
 
@@ -30,3 +48,430 @@ fn try_to_create_and_save_file_with_no_pixels() {
     assert_eq!(bitmap.save_as("temp.bmp").is_ok(), true);
     std::fs::remove_file("temp.bmp").unwrap();
 }
+
+#[test]
+fn save_and_read_file_with_alpha_mask() {
+    let mut bitmap = BitMap::new(4, 4);
+    bitmap.set_pixel(1, 2, Rgba::rgba(10, 20, 30, 40)).unwrap();
+    let options = SaveOptions::new().with_alpha_mask(true);
+    bitmap
+        .save_as_with_options("alpha_mask.bmp", &options)
+        .unwrap();
+    let read = BitMap::read("alpha_mask.bmp").unwrap();
+    std::fs::remove_file("alpha_mask.bmp").unwrap();
+    assert!(read == bitmap);
+}
+
+#[test]
+fn save_and_read_16_bit_file() {
+    let mut bitmap = BitMap::new(5, 3);
+    bitmap.set_pixel(4, 2, Rgba::black()).unwrap();
+    let options = SaveOptions::new().with_bit_depth(BitDepth::HighColor);
+    bitmap.save_as_with_options("16_bit.bmp", &options).unwrap();
+    let read = BitMap::read("16_bit.bmp").unwrap();
+    std::fs::remove_file("16_bit.bmp").unwrap();
+    assert!(read == bitmap);
+}
+
+#[test]
+fn fails_to_save_too_many_colors_in_color_table() {
+    let mut bitmap = BitMap::new(3, 1);
+    bitmap.set_pixel(0, 0, Rgba::black()).unwrap();
+    bitmap.set_pixel(1, 0, Rgba::rgb(255, 0, 0)).unwrap();
+    let options = SaveOptions::new().with_bit_depth(BitDepth::Color2Bit);
+    assert!(bitmap
+        .save_as_with_options("too_many_colors.bmp", &options)
+        .is_err());
+}
+
+#[test]
+fn save_gradient_with_dithering() {
+    let mut bitmap = BitMap::new(64, 16);
+    for y in 0..16 {
+        for x in 0..64 {
+            let level = (x * 4) as u8;
+            bitmap
+                .set_pixel(x, y, Rgba::rgb(level, level, level))
+                .unwrap();
+        }
+    }
+    // the average of each band of columns should follow the gradient
+    let get_band_average = |image: &BitMap, band: u32| {
+        let mut sum = 0;
+        for y in 0..16 {
+            for x in band * 16..(band + 1) * 16 {
+                sum += image.get_pixel(x, y).unwrap().get_red() as u32;
+            }
+        }
+        sum / 256
+    };
+    let dither = Dither::new(DitherMethod::FloydSteinberg).with_serpentine(true);
+    let options = SaveOptions::new()
+        .with_bit_depth(BitDepth::Color2Bit)
+        .with_dither(dither);
+    let read = BitMap::from_bytes(&bitmap.to_bytes(&options).unwrap()).unwrap();
+    assert_eq!(read.get_all_unique_colors().len(), 2);
+    // the two colors found are between the darkest and lightest colors, so
+    // only the middle of the gradient can be matched
+    for band in 1..3 {
+        let wanted = get_band_average(&bitmap, band) as i32;
+        assert!((get_band_average(&read, band) as i32 - wanted).abs() < 20);
+    }
+
+    // dither to a kept palette that is missing colors
+    let palette = vec![Rgba::black(), Rgba::white(), Rgba::rgb(128, 128, 128)];
+    bitmap.set_palette(Some(palette.clone())).unwrap();
+    let options = SaveOptions::new().with_keep_palette(true);
+    assert!(bitmap.to_bytes(&options).is_err());
+    let options = options.with_dither(dither);
+    let read = BitMap::from_bytes(&bitmap.to_bytes(&options).unwrap()).unwrap();
+    assert_eq!(read.get_palette(), Some(&palette[..]));
+    assert!((get_band_average(&read, 1) as i32 - get_band_average(&bitmap, 1) as i32).abs() < 20);
+}
+
+#[test]
+fn save_monochrome_file_with_one_bit_per_pixel() {
+    let mut bitmap = BitMap::new(48, 20);
+    for y in 0..20 {
+        for x in 0..48 {
+            let color = Rgba::rgb((x * 5) as u8, (y * 10) as u8, 100);
+            bitmap.set_pixel(x, y, color).unwrap();
+        }
+    }
+    bitmap.set_pixel(0, 0, Rgba::black()).unwrap();
+    bitmap.set_pixel(47, 19, Rgba::white()).unwrap();
+    for method in [
+        MonochromeMethod::Threshold(128),
+        MonochromeMethod::Otsu,
+        MonochromeMethod::Bayer2x2,
+        MonochromeMethod::Bayer4x4,
+        MonochromeMethod::Bayer8x8,
+    ]
+    .iter()
+    {
+        let mut monochrome = bitmap.clone();
+        monochrome.color_to_monochrome(*method);
+        monochrome.simplify_and_save_as("monochrome.bmp").unwrap();
+        let info = BitmapInfo::read("monochrome.bmp").unwrap();
+        let read = BitMap::read("monochrome.bmp").unwrap();
+        std::fs::remove_file("monochrome.bmp").unwrap();
+        assert_eq!(info.get_bits_per_pixel(), 1);
+        assert!(read == monochrome);
+        // black and white pixels keep their color
+        assert!(read.get_pixel(0, 0).unwrap().is_black());
+        assert!(read.get_pixel(47, 19).unwrap().is_white());
+    }
+}
+
+#[test]
+fn save_different_images_with_one_shared_palette() {
+    let mut first = BitMap::new(20, 10);
+    first.clear_image(Rgba::rgb(200, 30, 40));
+    let mut second = BitMap::new(12, 12);
+    for y in 0..12 {
+        for x in 0..12 {
+            second
+                .set_pixel(x, y, Rgba::rgb((x * 20) as u8, 90, (y * 20) as u8))
+                .unwrap();
+        }
+    }
+    let options = SaveOptions::new().with_palette(Palette::WebSafe);
+    let colors = Palette::WebSafe.get_colors();
+    for bitmap in [&first, &second].iter() {
+        let bytes = bitmap.to_bytes(&options).unwrap();
+        let read = BitMap::from_bytes(&bytes).unwrap();
+        assert_eq!(read.get_palette(), Some(&colors[..]));
+        assert!(read.get_pixels().iter().all(|pixel| colors.contains(pixel)));
+    }
+    let read = BitMap::from_bytes(&first.to_bytes(&options).unwrap()).unwrap();
+    assert_eq!(read.get_pixel(0, 0), Some(&Rgba::rgb(204, 51, 51)));
+
+    // a palette of the users own, dithered
+    let palette = vec![Rgba::black(), Rgba::rgb(255, 0, 0), Rgba::white()];
+    let options = SaveOptions::new()
+        .with_palette(Palette::Custom(palette.clone()))
+        .with_dither(Dither::new(DitherMethod::Atkinson));
+    let bytes = second.to_bytes(&options).unwrap();
+    let info = BitmapInfo::from_bytes(&bytes).unwrap();
+    assert_eq!(info.get_bit_depth(), Some(BitDepth::Color16Bit));
+    let read = BitMap::from_bytes(&bytes).unwrap();
+    assert_eq!(read.get_palette(), Some(&palette[..]));
+
+    let options = options.with_bit_depth(BitDepth::AllColors);
+    assert!(second.to_bytes(&options).is_err());
+    let options = SaveOptions::new()
+        .with_palette(Palette::Grayscale256)
+        .with_bit_depth(BitDepth::Color16Bit);
+    assert!(second.to_bytes(&options).is_err());
+}
+
+#[test]
+fn save_and_read_icons_and_cursors() {
+    let mut bitmaps = Vec::new();
+    for size in [16, 32, 48].iter() {
+        let mut bitmap = BitMap::new(*size, *size);
+        bitmap.clear_image(Rgba::rgba(0, 0, 0, 0));
+        for i in 0..*size {
+            bitmap.set_pixel(i, i, Rgba::rgb(0, 0, 200)).unwrap();
+            bitmap
+                .set_pixel(*size - i - 1, i, Rgba::rgba(200, 0, 0, 60))
+                .unwrap();
+        }
+        bitmaps.push(bitmap);
+    }
+    let icon = Icon::from_bitmaps(&bitmaps).unwrap();
+    icon.save_as("icon.ico").unwrap();
+    let read = Icon::read("icon.ico").unwrap();
+    std::fs::remove_file("icon.ico").unwrap();
+    assert_eq!(read.get_kind(), IconKind::Icon);
+    for (image, bitmap) in read.get_images().iter().zip(bitmaps.iter()) {
+        assert!(image.get_bitmap() == Some(bitmap));
+    }
+    // icons aren't bitmap files
+    assert!(BitMap::from_bytes(&icon.to_bytes().unwrap()).is_err());
+
+    let mut cursor = Icon::new(IconKind::Cursor);
+    for (i, bitmap) in bitmaps.iter().enumerate() {
+        let image = IconImage::from_bitmap(bitmap.clone()).unwrap();
+        cursor.add_image(image.with_hotspot(i as u16, 2 * i as u16));
+    }
+    let mut bytes = Vec::new();
+    cursor.write_to(&mut bytes).unwrap();
+    let read = Icon::from_reader(&bytes[..]).unwrap();
+    assert_eq!(read.get_kind(), IconKind::Cursor);
+    let hotspots: Vec<(u16, u16)> = read.get_images().iter().map(|i| i.get_hotspot()).collect();
+    assert_eq!(hotspots, vec![(0, 0), (1, 2), (2, 4)]);
+    assert!(Icon::read("cursor.bmp").is_err());
+}
+
+#[test]
+fn save_photo_with_reduced_colors() {
+    let mut bitmap = BitMap::new(120, 80);
+    for y in 0..80 {
+        for x in 0..120 {
+            let color = Rgba::rgb((x * 2) as u8, (y * 3) as u8, (x + y) as u8);
+            bitmap.set_pixel(x, y, color).unwrap();
+        }
+    }
+    let full_size = bitmap.to_bytes(&SaveOptions::new()).unwrap().len();
+    for (bit_depth, quantizer) in [
+        (BitDepth::Color256Bit, Quantizer::MedianCut),
+        (BitDepth::Color16Bit, Quantizer::Octree),
+    ]
+    .iter()
+    {
+        let options = SaveOptions::new()
+            .with_bit_depth(*bit_depth)
+            .with_quantizer(*quantizer);
+        let bytes = bitmap.to_bytes(&options).unwrap();
+        assert!(bytes.len() < full_size / 2);
+        let info = BitmapInfo::from_bytes(&bytes).unwrap();
+        assert_eq!(info.get_bit_depth(), Some(*bit_depth));
+        let read = BitMap::from_bytes(&bytes).unwrap();
+        let pixel = read.get_pixel(60, 40).unwrap();
+        assert!((pixel.get_red() as i32 - 120).abs() < 64);
+        assert!((pixel.get_green() as i32 - 120).abs() < 64);
+    }
+}
+
+#[test]
+fn save_and_read_run_length_encoded_file() {
+    let mut bitmap = BitMap::new(40, 20);
+    bitmap.set_pixel(4, 2, Rgba::black()).unwrap();
+    bitmap.set_pixel(5, 2, Rgba::rgb(0, 0, 255)).unwrap();
+    bitmap.set_pixel(6, 2, Rgba::rgb(255, 0, 0)).unwrap();
+    let options = SaveOptions::new().with_rle(true);
+    bitmap.save_as_with_options("rle.bmp", &options).unwrap();
+    let compressed_size = std::fs::metadata("rle.bmp").unwrap().len();
+    let read = BitMap::read("rle.bmp").unwrap();
+    std::fs::remove_file("rle.bmp").unwrap();
+    assert!(read == bitmap);
+    assert!(compressed_size < bitmap.get_estimated_file_size_in_bytes() as u64 / 10);
+}
+
+#[test]
+fn save_and_read_top_down_file() {
+    let mut bitmap = BitMap::new(4, 3);
+    bitmap.set_pixel(0, 0, Rgba::black()).unwrap();
+    bitmap.set_pixel(3, 2, Rgba::rgb(0, 255, 0)).unwrap();
+    let options = SaveOptions::new().with_top_down(true);
+    bitmap
+        .save_as_with_options("top_down.bmp", &options)
+        .unwrap();
+    let read = BitMap::read("top_down.bmp").unwrap();
+    std::fs::remove_file("top_down.bmp").unwrap();
+    assert!(read == bitmap);
+}
+
+#[test]
+fn read_file_from_bytes_and_reader() {
+    let mut bitmap = BitMap::new(4, 3);
+    bitmap.set_pixel(1, 1, Rgba::rgb(0, 0, 255)).unwrap();
+    bitmap.save_as("from_bytes.bmp").unwrap();
+    let bytes = std::fs::read("from_bytes.bmp").unwrap();
+    let reader = std::fs::File::open("from_bytes.bmp").unwrap();
+    let from_reader = BitMap::from_reader(reader).unwrap();
+    std::fs::remove_file("from_bytes.bmp").unwrap();
+
+    assert!(from_reader == bitmap);
+    assert!(BitMap::from_bytes(&bytes).unwrap() == bitmap);
+    assert!(BitMap::from_reader(&bytes[..]).unwrap() == bitmap);
+    assert!(from_reader.get_filename().is_none());
+    assert!(BitMap::from_bytes(&bytes[..20]).is_err());
+    assert!(BitMap::from_bytes(b"not a bitmap").is_err());
+}
+
+#[test]
+fn io_errors_keep_their_source() {
+    use std::error::Error;
+
+    let why = BitMap::read("this_file_does_not_exist.bmp").err().unwrap();
+    match &why {
+        BitmapError::Io(io) => assert_eq!(io.kind(), std::io::ErrorKind::NotFound),
+        _ => panic!("expected an io error"),
+    }
+    assert!(why.source().is_some());
+}
+
+#[test]
+fn write_file_to_bytes_and_writer() {
+    let mut bitmap = BitMap::new(4, 3);
+    bitmap.set_pixel(2, 1, Rgba::rgba(255, 0, 0, 50)).unwrap();
+    bitmap.set_pixel(3, 2, Rgba::black()).unwrap();
+    let options = SaveOptions::new();
+    let bytes = bitmap.to_bytes(&options).unwrap();
+    let mut buffer = Vec::new();
+    bitmap.write_to(&mut buffer, &options).unwrap();
+    assert_eq!(bytes, buffer);
+
+    bitmap.save_as("to_bytes.bmp").unwrap();
+    let saved = std::fs::read("to_bytes.bmp").unwrap();
+    std::fs::remove_file("to_bytes.bmp").unwrap();
+    assert_eq!(bytes, saved);
+    assert!(BitMap::from_bytes(&bytes).unwrap() == bitmap);
+
+    let options = SaveOptions::new().with_bit_depth(BitDepth::Color2Bit);
+    assert!(bitmap.to_bytes(&options).is_err());
+    assert!(bitmap.write_to(&mut buffer, &options).is_err());
+}
+
+#[test]
+fn read_info_of_saved_file() {
+    let mut bitmap = BitMap::new(5, 3);
+    bitmap.set_pixel(1, 1, Rgba::rgb(0, 0, 255)).unwrap();
+    let options = SaveOptions::new()
+        .with_bit_depth(BitDepth::Color2Bit)
+        .with_header(HeaderVersion::V5);
+    bitmap.save_as_with_options("info.bmp", &options).unwrap();
+    let info = BitmapInfo::read("info.bmp");
+    std::fs::remove_file("info.bmp").unwrap();
+
+    let info = info.unwrap();
+    assert_eq!(info.get_width(), 5);
+    assert_eq!(info.get_height(), 3);
+    assert_eq!(info.get_bit_depth(), Some(BitDepth::Color2Bit));
+    assert_eq!(info.get_compression(), Some(Compression::Rgb));
+    assert_eq!(info.get_header_version(), HeaderVersion::V5);
+    assert_eq!(info.get_palette().len(), 2);
+    assert!(info.get_palette().contains(&Rgba::rgb(0, 0, 255)));
+    assert!(BitmapInfo::read("info.png").is_err());
+}
+
+#[test]
+fn keep_resolution_when_saving_and_reading() {
+    let mut bitmap = BitMap::new(4, 4);
+    bitmap.set_dpi(300, 300);
+    for header in [HeaderVersion::Info, HeaderVersion::V4, HeaderVersion::V5].iter() {
+        let options = SaveOptions::new().with_header(*header);
+        let bytes = bitmap.to_bytes(&options).unwrap();
+        let read = BitMap::from_bytes(&bytes).unwrap();
+        assert_eq!(read.get_dpi(), (300, 300));
+        assert_eq!(read.get_pixels_per_meter(), (11811, 11811));
+        let info = BitmapInfo::from_bytes(&bytes).unwrap();
+        assert_eq!(info.get_pixels_per_meter(), (11811, 11811));
+    }
+}
+
+#[test]
+fn write_and_read_file_a_row_at_a_time() {
+    let options = SaveOptions::new().with_bit_depth(BitDepth::AllColorsAndShades);
+    let file = std::fs::File::create("rows.bmp").unwrap();
+    let mut writer = RowWriter::new(file, 300, 200, &options).unwrap();
+    for y in 0..200 {
+        let row: Vec<Rgba> = (0..300)
+            .map(|x| Rgba::rgba(x as u8, y as u8, 0, (x % 101) as u8))
+            .collect();
+        writer.write_row(&row).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let bitmap = BitMap::read("rows.bmp").unwrap();
+    let file = std::fs::File::open("rows.bmp").unwrap();
+    let reader = RowReader::new(std::io::BufReader::new(file)).unwrap();
+    assert_eq!(
+        reader.get_info().get_bit_depth(),
+        Some(BitDepth::AllColorsAndShades)
+    );
+    for (y, row) in reader.enumerate() {
+        let row = row.unwrap();
+        assert_eq!(row[7], Rgba::rgba(7, y as u8, 0, 7));
+        assert_eq!(Some(&row[250]), bitmap.get_pixel(250, y as u32));
+    }
+    std::fs::remove_file("rows.bmp").unwrap();
+}
+
+#[test]
+fn keep_palette_and_indices_when_saving() {
+    let mut bitmap = BitMap::new(4, 2);
+    bitmap.set_pixel(1, 0, Rgba::black()).unwrap();
+    bitmap.set_pixel(2, 1, Rgba::rgb(0, 0, 255)).unwrap();
+    // unused entries, and not in the order the colors appear
+    let mut palette: Vec<Rgba> = (0..20).map(|i| Rgba::rgb(i, i, 100)).collect();
+    palette[17] = Rgba::white();
+    palette[3] = Rgba::rgb(0, 0, 255);
+    palette[11] = Rgba::black();
+    bitmap.set_palette(Some(palette.clone())).unwrap();
+    bitmap.set_colors_important(12);
+    let options = SaveOptions::new().with_keep_palette(true);
+    let bytes = bitmap.to_bytes(&options).unwrap();
+
+    let info = BitmapInfo::from_bytes(&bytes).unwrap();
+    assert_eq!(info.get_bit_depth(), Some(BitDepth::Color256Bit));
+    assert_eq!(info.get_palette(), &palette[..]);
+    assert_eq!(info.get_colors_used(), 20);
+    assert_eq!(info.get_colors_important(), 12);
+    let data = &bytes[info.get_data_offset() as usize..];
+    // the bottom row is stored first
+    assert_eq!(data[..4], [17, 17, 3, 17]);
+    assert_eq!(data[4..8], [17, 11, 17, 17]);
+
+    let read = BitMap::from_bytes(&bytes).unwrap();
+    assert_eq!(read.get_palette(), Some(&palette[..]));
+    assert_eq!(read.to_bytes(&options).unwrap(), bytes);
+}
+
+#[test]
+fn edit_indexed_file_without_changing_other_indices() {
+    let mut bitmap = BitMap::new(30, 10);
+    bitmap.set_pixel(4, 2, Rgba::black()).unwrap();
+    bitmap.set_pixel(5, 2, Rgba::rgb(0, 0, 255)).unwrap();
+    let options = SaveOptions::new().with_rle(true);
+    bitmap
+        .save_as_with_options("indexed.bmp", &options)
+        .unwrap();
+
+    let mut image = IndexedBitMap::read("indexed.bmp").unwrap();
+    let blue = image.get_index(5, 2).unwrap();
+    let red = image.add_palette_color(Rgba::rgb(255, 0, 0)).unwrap();
+    image.set_index(0, 0, red).unwrap();
+    image.swap_palette_colors(0, blue).unwrap();
+    image.save_as("indexed.bmp", &options).unwrap();
+    let read = IndexedBitMap::read("indexed.bmp").unwrap();
+    std::fs::remove_file("indexed.bmp").unwrap();
+    assert_eq!(read, image);
+    assert_eq!(read.get_palette().len(), 4);
+
+    bitmap.set_pixel(0, 0, Rgba::rgb(255, 0, 0)).unwrap();
+    assert!(read.to_bitmap() == bitmap);
+    assert!(IndexedBitMap::from_bytes(&bitmap.to_bytes(&SaveOptions::new()).unwrap()).is_err());
+}